@@ -1,3 +1,6 @@
+#[cfg(test)]
+mod tests;
+
 use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::fs::{self, File};
 use std::io;
@@ -15,7 +18,7 @@ use nondestructive::yaml;
 use relative_path::{RelativePath, RelativePathBuf};
 
 use crate::commands::StringObjectId;
-use crate::workflows::{self, Eval, Step};
+use crate::workflows::{self, Eval, RunDefaults, Step};
 
 /// Configuration of an action.
 pub(super) struct Action {
@@ -37,6 +40,25 @@ pub(super) enum ActionKind {
     Composite {
         steps: Vec<Rc<Step>>,
     },
+    Docker {
+        image: DockerImage,
+        entrypoint: Option<String>,
+        pre_entrypoint: Option<String>,
+        pre_if: Option<String>,
+        post_entrypoint: Option<String>,
+        post_if: Option<String>,
+        args: Vec<String>,
+        env: BTreeMap<String, String>,
+    },
+}
+
+/// The image used by a docker action.
+#[derive(Debug)]
+pub(super) enum DockerImage {
+    /// A pre-built image, as specified through `docker://<image>`.
+    Image(String),
+    /// An image which is built from a Dockerfile in the action.
+    Dockerfile(Rc<Path>),
 }
 
 /// Load action context from the given repository.
@@ -109,11 +131,83 @@ pub(super) fn load<'repo>(
     Ok((kind, cx))
 }
 
+/// Load an action from a local directory, such as `./.github/actions/foo` in
+/// the repo.
+///
+/// Scripts are run directly out of the directory, so nothing is exported.
+pub(super) fn load_local(dir: &Path, eval: &Eval) -> Result<Action> {
+    let mut found = None;
+
+    for name in ["action.yml", "action.yaml"] {
+        let path = dir.join(name);
+
+        match fs::read(&path) {
+            Ok(bytes) => {
+                found = Some((path, bytes));
+                break;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context(path.display().to_string()),
+        }
+    }
+
+    let Some((path, bytes)) = found else {
+        bail!("Missing action.yml in {}", dir.display());
+    };
+
+    let action_yml =
+        yaml::from_slice(bytes).with_context(|| anyhow!("Reading {}", path.display()))?;
+
+    let mut cx = ActionContext::default();
+
+    cx.process_actions_yml(&action_yml, eval)
+        .with_context(|| anyhow!("Processing {}", path.display()))?;
+
+    let kind = cx.kind.take().context("Could not determine runner kind")?;
+
+    let kind = match kind {
+        ActionRunnerKind::Node(node) => {
+            let node_version = parse_node_version(&node)?;
+            let to_path = |path: &RelativePath| Rc::<Path>::from(path.to_path(dir));
+
+            ActionKind::Node {
+                main: cx
+                    .main
+                    .as_deref()
+                    .map(to_path)
+                    .context("Missing main script")?,
+                pre: cx.pre.as_deref().map(to_path),
+                pre_if: cx.pre_if,
+                post: cx.post.as_deref().map(to_path),
+                post_if: cx.post_if,
+                node_version,
+            }
+        }
+        ActionRunnerKind::Composite => ActionKind::Composite { steps: cx.steps },
+        ActionRunnerKind::Docker => return cx.into_docker(dir),
+    };
+
+    Ok(Action {
+        kind,
+        defaults: cx.defaults,
+        outputs: cx.outputs,
+    })
+}
+
+fn parse_node_version(node: &str) -> Result<u64> {
+    let Ok(node_version) = u64::from_str(node) else {
+        bail!("Invalid node runner version `{node}`");
+    };
+
+    Ok(node_version)
+}
+
 /// A determined action runner kind.
 #[derive(Debug)]
 pub(super) enum ActionRunnerKind {
     Node(Box<str>),
     Composite,
+    Docker,
 }
 
 /// The context of an action loaded from a repo.
@@ -127,6 +221,12 @@ pub(super) struct ActionContext<'repo> {
     post: Option<RelativePathBuf>,
     post_if: Option<String>,
     steps: Vec<Rc<Step>>,
+    image: Option<String>,
+    entrypoint: Option<String>,
+    pre_entrypoint: Option<String>,
+    post_entrypoint: Option<String>,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
     defaults: BTreeMap<String, String>,
     outputs: BTreeMap<String, String>,
     required: BTreeSet<String>,
@@ -145,9 +245,7 @@ impl<'repo> ActionContext<'repo> {
     ) -> Result<Action> {
         let kind = match kind {
             ActionRunnerKind::Node(node) => {
-                let Ok(node_version) = u64::from_str(node.as_ref()) else {
-                    return Err(anyhow!("Invalid node runner version `{node}`"));
-                };
+                let node_version = parse_node_version(&node)?;
 
                 let mut out = Vec::new();
 
@@ -214,56 +312,52 @@ impl<'repo> ActionContext<'repo> {
             ActionRunnerKind::Composite => {
                 if export {
                     tracing::debug!(?dir, "Exporting composite action");
+                    self.export(dir)?;
+                }
 
-                    for (path, _) in self.dirs {
-                        let path = path.to_path(dir);
-
-                        match fs::create_dir(&path) {
-                            Ok(()) => {}
-                            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
-                            Err(e) => {
-                                return Err(e).with_context(|| {
-                                    anyhow!("Failed to create directory: {}", path.display())
-                                });
-                            }
-                        }
-                    }
-
-                    for (path, (id, mode)) in self.paths {
-                        let path = path.to_path(dir);
-                        let object = id.object()?;
-
-                        let mut f = File::create(&path).with_context(|| {
-                            anyhow!("Failed to create file: {}", path.display())
-                        })?;
-
-                        f.write_all(&object.data[..])
-                            .with_context(|| anyhow!("Failed to write file: {}", path.display()))?;
-
-                        #[cfg(unix)]
-                        {
-                            use std::os::unix::fs::PermissionsExt;
+                ActionKind::Composite { steps: self.steps }
+            }
+            ActionRunnerKind::Docker => {
+                if export {
+                    tracing::debug!(?dir, "Exporting docker action");
+                    self.export(dir)?;
+                }
 
-                            let meta = f.metadata()?;
-                            let mut perm = meta.permissions();
-                            perm.set_mode(mode.value() as u32);
+                return self.into_docker(dir);
+            }
+        };
 
-                            f.set_permissions(perm).with_context(|| {
-                                anyhow!("Failed to set permissions on file: {}", path.display())
-                            })?;
-                        }
+        Ok(Action {
+            kind,
+            defaults: self.defaults,
+            outputs: self.outputs,
+        })
+    }
 
-                        #[cfg(not(unix))]
-                        {
-                            _ = mode;
-                        }
-                    }
-                }
+    /// Construct a docker action, where a Dockerfile is resolved relative to
+    /// the given directory.
+    fn into_docker(self, dir: &Path) -> Result<Action> {
+        let image = self.image.context("Missing .runs.image")?;
 
-                ActionKind::Composite { steps: self.steps }
+        let image = match image.strip_prefix("docker://") {
+            Some(image) => DockerImage::Image(image.to_owned()),
+            None => {
+                let path = RelativePath::new(&image).to_path(dir);
+                DockerImage::Dockerfile(Rc::from(path))
             }
         };
 
+        let kind = ActionKind::Docker {
+            image,
+            entrypoint: self.entrypoint,
+            pre_entrypoint: self.pre_entrypoint,
+            pre_if: self.pre_if,
+            post_entrypoint: self.post_entrypoint,
+            post_if: self.post_if,
+            args: self.args,
+            env: self.env,
+        };
+
         Ok(Action {
             kind,
             defaults: self.defaults,
@@ -271,6 +365,54 @@ impl<'repo> ActionContext<'repo> {
         })
     }
 
+    /// Export all files in the action to the given directory.
+    fn export(&self, dir: &Path) -> Result<()> {
+        for (path, _) in &self.dirs {
+            let path = path.to_path(dir);
+
+            match fs::create_dir(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        anyhow!("Failed to create directory: {}", path.display())
+                    });
+                }
+            }
+        }
+
+        for (path, (id, mode)) in &self.paths {
+            let path = path.to_path(dir);
+            let object = id.object()?;
+
+            let mut f = File::create(&path)
+                .with_context(|| anyhow!("Failed to create file: {}", path.display()))?;
+
+            f.write_all(&object.data[..])
+                .with_context(|| anyhow!("Failed to write file: {}", path.display()))?;
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                let meta = f.metadata()?;
+                let mut perm = meta.permissions();
+                perm.set_mode(mode.value() as u32);
+
+                f.set_permissions(perm).with_context(|| {
+                    anyhow!("Failed to set permissions on file: {}", path.display())
+                })?;
+            }
+
+            #[cfg(not(unix))]
+            {
+                _ = mode;
+            }
+        }
+
+        Ok(())
+    }
+
     fn extract(
         &'repo self,
         version: &str,
@@ -312,11 +454,13 @@ impl<'repo> ActionContext<'repo> {
                 self.kind = Some(ActionRunnerKind::Node(version.trim().into()));
             } else if using == "composite" {
                 self.kind = Some(ActionRunnerKind::Composite);
+            } else if using == "docker" {
+                self.kind = Some(ActionRunnerKind::Docker);
             } else {
                 bail!("Unsupported .runs.using: {using}");
             }
 
-            let (steps, _, _) = workflows::load_steps(&runs, eval)?;
+            let (steps, _, _) = workflows::load_steps(&runs, eval, &RunDefaults::default())?;
             self.steps = steps;
 
             if let Some(s) = runs.get("pre").and_then(|v| v.as_str()) {
@@ -338,6 +482,38 @@ impl<'repo> ActionContext<'repo> {
             if let Some(s) = runs.get("post-if").and_then(|v| v.as_str()) {
                 self.post_if = Some(s.to_owned());
             }
+
+            if let Some(s) = runs.get("image").and_then(|v| v.as_str()) {
+                self.image = Some(s.trim().to_owned());
+            }
+
+            if let Some(s) = runs.get("entrypoint").and_then(|v| v.as_str()) {
+                self.entrypoint = Some(s.to_owned());
+            }
+
+            if let Some(s) = runs.get("pre-entrypoint").and_then(|v| v.as_str()) {
+                self.pre_entrypoint = Some(s.to_owned());
+            }
+
+            if let Some(s) = runs.get("post-entrypoint").and_then(|v| v.as_str()) {
+                self.post_entrypoint = Some(s.to_owned());
+            }
+
+            if let Some(args) = runs.get("args").and_then(|v| v.as_sequence()) {
+                for arg in args {
+                    self.args.push(value_to_string(arg)?);
+                }
+            }
+
+            if let Some(env) = runs.get("env").and_then(|v| v.as_mapping()) {
+                for (key, value) in env.iter() {
+                    let Ok(key) = str::from_utf8(key) else {
+                        continue;
+                    };
+
+                    self.env.insert(key.to_owned(), value_to_string(value)?);
+                }
+            }
         }
 
         let inputs = action_yml
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use super::*;
+
+/// Load an action from the given `action.yml` without exporting it.
+fn load_yml(source: &str, dir: &Path) -> Result<Action> {
+    let action_yml = yaml::from_slice(source)?;
+    let mut cx = ActionContext::default();
+    cx.process_actions_yml(&action_yml, Eval::empty())?;
+    let kind = cx.kind.take().context("Could not determine runner kind")?;
+    cx.load(kind, dir, "v1", false)
+}
+
+#[test]
+fn docker_image() -> Result<()> {
+    let action = load_yml(
+        r#"
+        runs:
+          using: docker
+          image: docker://alpine:3.20
+          entrypoint: /entrypoint.sh
+          post-entrypoint: /cleanup.sh
+          args:
+            - ${{ inputs.who }}
+            - 42
+            - true
+          env:
+            GREETING: hello
+        inputs:
+          who:
+            default: world
+        "#,
+        Path::new("/actions/docker"),
+    )?;
+
+    let ActionKind::Docker {
+        image,
+        entrypoint,
+        pre_entrypoint,
+        post_entrypoint,
+        args,
+        env,
+        ..
+    } = action.kind
+    else {
+        panic!("expected docker action, got {:?}", action.kind);
+    };
+
+    assert!(matches!(image, DockerImage::Image(image) if image == "alpine:3.20"));
+    assert_eq!(entrypoint.as_deref(), Some("/entrypoint.sh"));
+    assert_eq!(pre_entrypoint, None);
+    assert_eq!(post_entrypoint.as_deref(), Some("/cleanup.sh"));
+    // NB: Arguments are evaluated once the inputs of the step are known.
+    assert_eq!(args, ["${{ inputs.who }}", "42", "true"]);
+    assert_eq!(env.get("GREETING").map(String::as_str), Some("hello"));
+    assert_eq!(
+        action.defaults.get("who").map(String::as_str),
+        Some("world")
+    );
+    Ok(())
+}
+
+#[test]
+fn docker_dockerfile() -> Result<()> {
+    let dir = Path::new("/actions/docker");
+
+    let action = load_yml(
+        r#"
+        runs:
+          using: docker
+          image: docker/Dockerfile
+        "#,
+        dir,
+    )?;
+
+    let ActionKind::Docker { image, .. } = action.kind else {
+        panic!("expected docker action, got {:?}", action.kind);
+    };
+
+    assert!(
+        matches!(image, DockerImage::Dockerfile(path) if *path == *dir.join("docker/Dockerfile"))
+    );
+
+    let error = load_yml("runs:\n  using: docker\n", dir)
+        .err()
+        .map(|e| e.to_string());
+    assert_eq!(error.as_deref(), Some("Missing .runs.image"));
+    Ok(())
+}
+
+#[test]
+fn local_actions() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let error = load_local(dir.path(), Eval::empty())
+        .err()
+        .map(|e| e.to_string());
+    assert_eq!(
+        error,
+        Some(format!("Missing action.yml in {}", dir.path().display()))
+    );
+
+    fs::write(
+        dir.path().join("action.yaml"),
+        "runs:\n  using: node20\n  main: dist/index.js\n  post: dist/post.js\n",
+    )?;
+
+    let action = load_local(dir.path(), Eval::empty())?;
+
+    let ActionKind::Node {
+        main,
+        pre,
+        post,
+        node_version,
+        ..
+    } = action.kind
+    else {
+        panic!("expected node action, got {:?}", action.kind);
+    };
+
+    // NB: Scripts are run out of the directory of the action.
+    assert_eq!(*main, *dir.path().join("dist/index.js"));
+    assert_eq!(pre, None);
+    assert_eq!(post.as_deref(), Some(&*dir.path().join("dist/post.js")));
+    assert_eq!(node_version, 20);
+
+    fs::write(
+        dir.path().join("action.yml"),
+        "runs:\n  using: composite\n  steps:\n    - run: echo hello\n      shell: bash\n",
+    )?;
+
+    let action = load_local(dir.path(), Eval::empty())?;
+
+    let ActionKind::Composite { steps } = action.kind else {
+        panic!("expected composite action, got {:?}", action.kind);
+    };
+
+    assert_eq!(steps.len(), 1);
+    Ok(())
+}
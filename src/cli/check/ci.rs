@@ -14,7 +14,7 @@ use crate::edits;
 use crate::keys::Keys;
 use crate::model::Repo;
 use crate::rstr::RStr;
-use crate::workflows::{Job, Step, StepMapping, WorkflowManifest, WorkflowManifests};
+use crate::workflows::{Job, Step, StepMapping, Tree, WorkflowManifest, WorkflowManifests};
 use crate::workspace::Crates;
 
 pub(crate) struct Ci<'a> {
@@ -174,7 +174,13 @@ fn validate_jobs(
         validate_on(cx, ci, w, config, value);
     }
 
-    for job in w.jobs(&HashSet::new(), &[])? {
+    for job in w.jobs(&Tree::new(), &HashSet::new(), &[])? {
+        // NB: Jobs from called workflows are checked as part of the workflow
+        // they are declared in.
+        if job.called_by.is_some() {
+            continue;
+        }
+
         check_strategy_rust_version(ci, &job);
         check_actions(ci, &job)?;
 
@@ -1,15 +1,28 @@
-use std::collections::HashSet;
+#[cfg(test)]
+mod tests;
+
+mod parallel;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Write;
+use std::path::PathBuf;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use clap::Parser;
 use termcolor::{ColorChoice, StandardStream};
 
 use crate::cli::WithRepos;
-use crate::commands::{Batch, BatchOptions, Session};
+use crate::commands::{
+    Batch, BatchOptions, ResumeState, Session, StepSelector, StepWindow, collect_artifacts,
+    write_annotations, write_summaries, write_summary_file,
+};
 use crate::ctxt::Ctxt;
 use crate::model::Repo;
 use crate::release::{Date, ReleaseOpts, Version};
+use crate::rstr::RString;
+use crate::workflows::Tree;
+
+use self::parallel::{ChildNeed, ChildSpec, Combination, Parallel};
 
 #[derive(Default, Debug, Parser)]
 pub(crate) struct Opts {
@@ -36,9 +49,50 @@ pub(crate) struct Opts {
     /// Run all commands associated with a Github workflows job.
     #[arg(long)]
     job: Option<String>,
+    /// Only run the steps of the job specified with `--job` starting from the
+    /// given step, which is either the id or name of a step or its 1-based
+    /// index.
+    #[arg(long, value_name = "STEP")]
+    from_step: Option<String>,
+    /// Only run the steps of the job specified with `--job` up until and
+    /// including the given step, which is either the id or name of a step or
+    /// its 1-based index.
+    #[arg(long, value_name = "STEP")]
+    until_step: Option<String>,
+    /// Resume the job specified with `--job` from the step which failed in a
+    /// previous run.
+    ///
+    /// The environment and step outputs are restored to what they were before
+    /// the step failed.
+    #[arg(long)]
+    resume: bool,
     /// List all jobs associated with a Github workflows.
     #[arg(long)]
     list_jobs: bool,
+    /// Run up to the specified number of matrix combinations of a job in
+    /// parallel.
+    ///
+    /// Each combination runs in a separate git worktree of the repo, which
+    /// includes any uncommitted changes to tracked files. Output is prefixed
+    /// with the job and matrix it belongs to, and a summary is printed at the
+    /// end.
+    #[arg(long, value_name = "N")]
+    jobs: Option<usize>,
+    /// Run a single matrix combination as described by the given file. This is
+    /// used internally by `--jobs`.
+    #[arg(long, hide = true)]
+    batch_child: Option<PathBuf>,
+    /// Write the markdown which steps wrote to `GITHUB_STEP_SUMMARY` to the
+    /// given file.
+    ///
+    /// Summaries are always rendered to the terminal at the end of a run.
+    #[arg(long, value_name = "PATH")]
+    summary_out: Option<PathBuf>,
+    /// Copy artifacts uploaded with `actions/upload-artifact` into the given
+    /// directory once the run has completed, with one directory for each
+    /// artifact.
+    #[arg(long, value_name = "DIR")]
+    artifacts: Option<PathBuf>,
     /// Only runs command on the current OS.
     ///
     /// When loading workflows, this causes the `runs-on` directive to be
@@ -91,6 +145,10 @@ fn run(
         }
     }
 
+    if let Some(path) = &opts.batch_child {
+        return parallel::child(o, cx, &mut c, repo, path, opts.same_os);
+    }
+
     let mut all_workflows = false;
     let mut filter_workflows = HashSet::new();
     let mut all_jobs = false;
@@ -109,6 +167,31 @@ fn run(
         all_jobs = false;
     }
 
+    let steps = StepWindow::new(
+        opts.from_step
+            .as_deref()
+            .map(StepSelector::parse)
+            .transpose()?,
+        opts.until_step
+            .as_deref()
+            .map(StepSelector::parse)
+            .transpose()?,
+    );
+
+    if !steps.is_full() || opts.resume {
+        if opts.job.is_none() {
+            bail!("--from-step, --until-step and --resume require --job");
+        }
+
+        if opts.jobs.is_some_and(|jobs| jobs > 1) {
+            bail!("--from-step, --until-step and --resume cannot be used with --jobs");
+        }
+
+        if opts.resume && opts.from_step.is_some() {
+            bail!("Cannot specify both --resume and --from-step");
+        }
+    }
+
     if opts.first_os || opts.each_os {
         if opts.first_os && opts.each_os {
             bail!("Cannot specify both --first-os and --each-os");
@@ -127,70 +210,481 @@ fn run(
         batches.push(Batch::command(cx.os.clone(), command, &opts.args));
     }
 
+    let mut w = None;
+
     if opts.workflow.is_some() || opts.job.is_some() || opts.list_jobs {
-        let w = c.load_github_workflows(repo)?;
-
-        if opts.list_jobs {
-            for workflow in w.iter() {
-                writeln!(o, "Workflow: {}", workflow.id())?;
-
-                for job in workflow.jobs() {
-                    for matrix in job.matrices() {
-                        write!(o, "  Job: {}", job.id())?;
-
-                        if let Some(name) = matrix.name()
-                            && name != job.id()
-                        {
-                            write!(o, " ({name})")?;
-                        }
-
-                        if matrix.matrix().is_empty() {
-                            writeln!(o)?;
-                        } else {
-                            writeln!(o, " {}", matrix.matrix().display())?;
-                        }
+        w = Some(c.load_github_workflows(repo)?);
+    }
+
+    if let Some(w) = &w
+        && opts.list_jobs
+    {
+        for workflow in w.iter() {
+            writeln!(o, "Workflow: {}", workflow.id())?;
+
+            for job in workflow.jobs() {
+                if let Some(error) = job.error() {
+                    writeln!(o, "  Job: {} (error: {error})", job.id())?;
+                } else if let Some(uses) = job.uses() {
+                    writeln!(o, "  Job: {} (uses {uses})", job.id())?;
+                }
+
+                if job.is_deferred() {
+                    writeln!(o, "  Job: {} (depends on needs)", job.id())?;
+                }
+
+                for matrix in job.matrices() {
+                    write!(o, "  Job: {}", job.id())?;
+
+                    if let Some(name) = matrix.name()
+                        && name != job.id()
+                    {
+                        write!(o, " ({name})")?;
                     }
+
+                    if matrix.matrix().is_empty() {
+                        writeln!(o)?;
+                    } else {
+                        writeln!(o, " {}", matrix.matrix().display())?;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut jobs = Vec::new();
+
+    if let Some(w) = &w
+        && (opts.workflow.is_some() || opts.job.is_some())
+    {
+        for workflow in w.iter() {
+            if !all_workflows && !filter_workflows.contains(workflow.id()) {
+                continue;
+            }
+
+            let workflow_id = workflow.id();
+
+            if let Some(reason) = workflow.not_triggered() {
+                writeln!(o, "# Skipping workflow {workflow_id}: {reason}")?;
+                continue;
+            }
+
+            for job in workflow.resolve_jobs(|id| all_jobs || filter_jobs.contains(id))? {
+                jobs.push((workflow_id, job));
+            }
+        }
+    }
+
+    if c.is_dry_run() && !jobs.is_empty() {
+        write!(o, "# Job order:")?;
+
+        for (n, (workflow_id, job)) in jobs.iter().enumerate() {
+            let sep = if n == 0 { " " } else { " -> " };
+            write!(o, "{sep}{workflow_id}/{}", job.id())?;
+        }
+
+        writeln!(o)?;
+    }
+
+    let mut parallel = match opts.jobs {
+        Some(jobs) if jobs > 1 => Some(Parallel::new(cx, repo, &c, jobs)?),
+        _ => None,
+    };
+
+    let mut session = Session::new(&c);
+
+    for batch in batches {
+        batch.commit(o, &c, &mut session)?;
+    }
+
+    let mut completed = HashMap::new();
+    let mut failed = Vec::new();
+
+    for (workflow_id, job) in jobs {
+        let mut needs = Tree::new();
+        let mut child_needs = BTreeMap::new();
+        let mut results = Vec::new();
+
+        for need in job.needs() {
+            let Some(done) = completed.get(&(workflow_id, need.as_str())) else {
+                continue;
+            };
+
+            let CompletedJob { result, outputs } = done;
+            let name = job.need_name(need);
+
+            needs.insert(["needs", name, "result"], result.as_str());
+            needs.insert_prefix(["needs", name, "outputs"], outputs.clone());
+
+            if parallel.is_some() {
+                child_needs.insert(
+                    name.to_owned(),
+                    ChildNeed {
+                        result: result.as_str().to_owned(),
+                        outputs: parallel::to_raw_outputs(outputs),
+                    },
+                );
+            }
+
+            results.push((need.as_str(), *result));
+        }
+
+        let (status, blocked) = needs_result(results);
+
+        let mut done = CompletedJob {
+            result: JobResult::Success,
+            outputs: BTreeMap::new(),
+        };
+
+        // NB: A condition which calls a status function like `always()` or
+        // `failure()` decides by itself whether the job runs, otherwise the
+        // job is skipped if any job it needs did not succeed.
+        //
+        // A job calling a reusable workflow instead gets its result from the
+        // jobs in the called workflow.
+        if let Some(need) = blocked
+            && job.uses().is_none()
+            && !job.has_status_function()
+        {
+            writeln!(
+                o,
+                "# Skipping job {workflow_id}/{} since `{need}` did not succeed",
+                job.id()
+            )?;
+
+            done.result = JobResult::Skipped;
+            completed.insert((workflow_id, job.id()), done);
+            continue;
+        }
+
+        let skipped = job
+            .skipped(&needs, status.as_str())
+            .with_context(|| anyhow!("{workflow_id}/{}: Evaluating `if`", job.id()))?;
+
+        if let Some(condition) = skipped {
+            writeln!(
+                o,
+                "# Skipping job {workflow_id}/{} since `{condition}` is false",
+                job.id()
+            )?;
+
+            done.result = JobResult::Skipped;
+            completed.insert((workflow_id, job.id()), done);
+            continue;
+        }
+
+        if let Some(error) = job.error() {
+            tracing::error!("Job {workflow_id}/{} failed: {error}", job.id());
+            done.result = JobResult::Failure;
+            failed.push(format!("{workflow_id}/{}", job.id()));
+            completed.insert((workflow_id, job.id()), done);
+            continue;
+        }
+
+        // NB: A job calling a reusable workflow doesn't run anything by
+        // itself, it only collects the results and outputs of the jobs in the
+        // called workflow.
+        if job.uses().is_some() {
+            let mut called = Tree::new();
+
+            for need in job.needs() {
+                let (Some(name), Some(need)) = (
+                    job.called_job(need),
+                    completed.get(&(workflow_id, need.as_str())),
+                ) else {
+                    continue;
+                };
+
+                called.insert(["jobs", name, "result"], need.result.as_str());
+                called.insert_prefix(["jobs", name, "outputs"], need.outputs.clone());
+
+                done.result = done.result.and(need.result);
+            }
+
+            done.outputs = job
+                .workflow_outputs(&called)
+                .with_context(|| anyhow!("{workflow_id}/{}", job.id()))?;
+
+            completed.insert((workflow_id, job.id()), done);
+            continue;
+        }
+
+        // NB: A job whose matrix depends on the `needs` context can only be
+        // built once the jobs it needs have completed.
+        let deferred = match job.build_deferred(&needs) {
+            Ok(deferred) => deferred,
+            Err(error) => {
+                tracing::error!("Job {workflow_id}/{} failed: {error}", job.id());
+
+                for cause in error.chain().skip(1) {
+                    tracing::error!("Caused by: {cause}");
+                }
+
+                done.result = JobResult::Failure;
+                failed.push(format!("{workflow_id}/{}", job.id()));
+                completed.insert((workflow_id, job.id()), done);
+                continue;
+            }
+        };
+
+        if let Some(parallel) = &mut parallel {
+            let mut combinations = Vec::new();
+
+            for (index, matrix) in job.matrices_with(deferred.as_deref()).enumerate() {
+                if let Err(error) = matrix.build(None, opts.same_os, &cx.os, &needs) {
+                    tracing::warn!(
+                        workflow.id = workflow_id,
+                        job.id = job.id(),
+                        matrix = ?matrix.matrix(),
+                        ?error,
+                        "Failed to build job",
+                    );
+
+                    continue;
+                }
+
+                let mut label = format!("{workflow_id}/{}", job.id());
+
+                if !matrix.matrix().is_empty() {
+                    label = format!("{label} {}", matrix.matrix().display());
+                }
+
+                combinations.push(Combination {
+                    label,
+                    continue_on_error: matrix.continue_on_error(),
+                    spec: ChildSpec {
+                        repo: repo.path().to_owned(),
+                        workflow: workflow_id.to_owned(),
+                        job: job.id().to_owned(),
+                        matrix: index,
+                        needs: child_needs.clone(),
+                        workdir: None,
+                        outputs: PathBuf::new(),
+                        summary: PathBuf::new(),
+                        artifacts: None,
+                        masks: Vec::new(),
+                    },
+                });
+            }
+
+            let outcomes = parallel.run(combinations, job.fail_fast(), job.max_parallel())?;
+
+            for outcome in outcomes {
+                if let Some(summary) = outcome.summary {
+                    session.push_summary(summary);
+                }
+
+                if outcome.cancelled {
+                    continue;
+                }
+
+                if outcome.success {
+                    done.outputs.extend(outcome.outputs);
+                    continue;
+                }
+
+                if outcome.continue_on_error {
+                    writeln!(
+                        o,
+                        "# Job {} failed, continuing since continue-on-error is set",
+                        outcome.label
+                    )?;
+
+                    continue;
                 }
+
+                done.result = JobResult::Failure;
             }
+
+            if done.result == JobResult::Failure {
+                failed.push(format!("{workflow_id}/{}", job.id()));
+            }
+
+            completed.insert((workflow_id, job.id()), done);
+            continue;
         }
 
-        if opts.workflow.is_some() || opts.job.is_some() {
-            for workflow in w.iter() {
-                if !all_workflows && !filter_workflows.contains(workflow.id()) {
+        let selected = opts.job.as_deref() == Some(job.id());
+        let combinations = job.matrices_with(deferred.as_deref()).count();
+
+        for (index, matrix) in job.matrices_with(deferred.as_deref()).enumerate() {
+            let mut batch = match matrix.build(None, opts.same_os, &cx.os, &needs) {
+                Ok(batch) => batch,
+                Err(error) => {
+                    tracing::warn!(
+                        workflow.id = workflow_id,
+                        job.id = job.id(),
+                        matrix = ?matrix.matrix(),
+                        ?error,
+                        "Failed to build job",
+                    );
+
                     continue;
                 }
+            };
+
+            // NB: The state of jobs is always persisted, so that a failed job
+            // can be resumed.
+            let state_file = ResumeState::path(
+                &c,
+                &[
+                    repo.path().as_str(),
+                    workflow_id,
+                    job.id(),
+                    &matrix.matrix().display().to_string(),
+                ],
+            )?;
+
+            if selected && opts.resume {
+                let mut label = format!("{workflow_id}/{}", job.id());
+
+                if !matrix.matrix().is_empty() {
+                    label = format!("{label} {}", matrix.matrix().display());
+                }
+
+                let Some(state) = ResumeState::load(&state_file)? else {
+                    writeln!(o, "# Skipping job {label}: no failed run to resume")?;
+                    continue;
+                };
+
+                writeln!(o, "# Resuming job {label} from step #{}", state.step())?;
+
+                let from = StepSelector::Index(state.step());
+
+                batch = batch
+                    .with_step_window(steps.with_from(from))
+                    .with_resume(state);
+            } else if selected {
+                batch = batch.with_step_window(steps.clone());
+            }
+
+            let batch = batch.with_state_file(state_file);
+
+            match batch.commit(o, &c, &mut session) {
+                Ok(outputs) => {
+                    done.outputs.extend(outputs);
+                }
+                Err(error) => {
+                    tracing::error!("Job {workflow_id}/{} failed: {error}", job.id());
+
+                    for cause in error.chain().skip(1) {
+                        tracing::error!("Caused by: {cause}");
+                    }
+
+                    if matrix.continue_on_error() {
+                        writeln!(
+                            o,
+                            "# Job {workflow_id}/{} failed, continuing since continue-on-error is set",
+                            job.id()
+                        )?;
 
-                for job in workflow.jobs() {
-                    if !all_jobs && !filter_jobs.contains(job.id()) {
                         continue;
                     }
 
-                    for matrix in job.matrices() {
-                        match matrix.build(None, opts.same_os, &cx.os) {
-                            Ok(batch) => {
-                                batches.push(batch);
-                            }
-                            Err(error) => {
-                                tracing::warn!(
-                                    workflow.id = workflow.id(),
-                                    job.id = job.id(),
-                                    matrix = ?matrix.matrix(),
-                                    ?error,
-                                    "Failed to build job",
-                                );
-                            }
-                        }
+                    done.result = JobResult::Failure;
+
+                    if !job.fail_fast() {
+                        continue;
                     }
+
+                    if index + 1 < combinations {
+                        writeln!(
+                            o,
+                            "# Job {workflow_id}/{} failed, skipping the remaining combinations since fail-fast is set",
+                            job.id()
+                        )?;
+                    }
+
+                    break;
                 }
             }
         }
+
+        if done.result == JobResult::Failure {
+            failed.push(format!("{workflow_id}/{}", job.id()));
+        }
+
+        completed.insert((workflow_id, job.id()), done);
     }
 
-    let mut session = Session::new(&c);
+    if let Some(parallel) = &parallel {
+        parallel.summary(o)?;
+    }
 
-    for batch in batches {
-        batch.commit(o, &c, &mut session)?;
+    write_summaries(o, &c, session.summaries())?;
+
+    if let Some(path) = &opts.summary_out {
+        write_summary_file(path, session.summaries())?;
+    }
+
+    write_annotations(o, &c, session.annotations())?;
+
+    if let Some(dir) = &opts.artifacts {
+        collect_artifacts(o, &c, dir)?;
+    }
+
+    if !failed.is_empty() {
+        bail!("Failed jobs: {}", failed.join(", "));
     }
 
     Ok(())
 }
+
+/// A job which has completed.
+struct CompletedJob {
+    result: JobResult,
+    outputs: BTreeMap<String, RString>,
+}
+
+/// Combine the results of the jobs a job needs, returning the combined result
+/// and the first job which did not succeed.
+///
+/// Unless its condition calls a status function, a job which needs a job that
+/// did not succeed is skipped. Since a skipped job doesn't succeed either, this
+/// skips every job which transitively needs a failed job.
+fn needs_result<'a, I>(results: I) -> (JobResult, Option<&'a str>)
+where
+    I: IntoIterator<Item = (&'a str, JobResult)>,
+{
+    let mut status = JobResult::Success;
+    let mut blocked = None;
+
+    for (need, result) in results {
+        if result != JobResult::Success && blocked.is_none() {
+            blocked = Some(need);
+        }
+
+        status = status.and(result);
+    }
+
+    (status, blocked)
+}
+
+/// The result of running a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobResult {
+    Success,
+    Failure,
+    Skipped,
+}
+
+impl JobResult {
+    /// Combine two results, where a failure takes precedence over a skipped
+    /// job.
+    fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (JobResult::Failure, _) | (_, JobResult::Failure) => JobResult::Failure,
+            (JobResult::Skipped, _) | (_, JobResult::Skipped) => JobResult::Skipped,
+            _ => JobResult::Success,
+        }
+    }
+
+    /// Get the result as it is exposed in the `needs` context.
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobResult::Success => "success",
+            JobResult::Failure => "failure",
+            JobResult::Skipped => "skipped",
+        }
+    }
+}
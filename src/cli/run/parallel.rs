@@ -0,0 +1,695 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use relative_path::RelativePathBuf;
+use serde::{Deserialize, Serialize};
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+use crate::commands::{Session, SessionConfig, write_annotations, write_summary_file};
+use crate::ctxt::Ctxt;
+use crate::model::Repo;
+use crate::process::Command;
+use crate::rstr::{self, RString};
+use crate::system::Git;
+use crate::workflows::Tree;
+
+/// Hidden option used to run a single matrix combination in a child process.
+pub(super) const BATCH_CHILD: &str = "--batch-child";
+
+/// A matrix combination which is run in a child process.
+#[derive(Serialize, Deserialize)]
+pub(super) struct ChildSpec {
+    /// The repo the combination belongs to.
+    pub(super) repo: RelativePathBuf,
+    /// The workflow the job belongs to.
+    pub(super) workflow: String,
+    /// The job to run.
+    pub(super) job: String,
+    /// The index of the matrix combination to run.
+    pub(super) matrix: usize,
+    /// The results of the jobs the job needs.
+    pub(super) needs: BTreeMap<String, ChildNeed>,
+    /// The directory to run the combination in, if different from the repo.
+    #[serde(default)]
+    pub(super) workdir: Option<PathBuf>,
+    /// Where the outputs of the combination should be written, see
+    /// [`ChildOutputs`].
+    #[serde(default)]
+    pub(super) outputs: PathBuf,
+    /// Where the step summaries of the combination should be written.
+    #[serde(default)]
+    pub(super) summary: PathBuf,
+    /// The directory where artifacts of the run are stored.
+    #[serde(default)]
+    pub(super) artifacts: Option<PathBuf>,
+    /// Values which have been masked so far, which the child also has to mask.
+    #[serde(default)]
+    pub(super) masks: Vec<String>,
+}
+
+/// What a child process reports back after running a combination.
+#[derive(Default, Serialize, Deserialize)]
+struct ChildOutputs {
+    /// Outputs of the combination as raw strings, see [`to_raw_outputs`].
+    #[serde(default)]
+    outputs: BTreeMap<String, String>,
+    /// Values masked by the combination, like through the `::add-mask::`
+    /// workflow command, which the parent has to mask from then on.
+    #[serde(default)]
+    masks: Vec<String>,
+}
+
+/// The result of a job needed by a combination.
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct ChildNeed {
+    pub(super) result: String,
+    /// Outputs of the job as raw strings, which retain which parts of them are
+    /// redacted, see [`RStr::as_raw`][crate::rstr::RStr::as_raw].
+    pub(super) outputs: BTreeMap<String, String>,
+}
+
+/// A matrix combination to run.
+pub(super) struct Combination {
+    /// The label used to prefix output.
+    pub(super) label: String,
+    /// Whether the combination is allowed to fail.
+    pub(super) continue_on_error: bool,
+    pub(super) spec: ChildSpec,
+}
+
+/// The outcome of running a combination.
+pub(super) struct Outcome {
+    pub(super) label: String,
+    pub(super) continue_on_error: bool,
+    pub(super) success: bool,
+    /// The combination was cancelled before it started since another
+    /// combination failed.
+    pub(super) cancelled: bool,
+    pub(super) duration: Duration,
+    pub(super) outputs: BTreeMap<String, RString>,
+    /// Step summaries written by the combination.
+    pub(super) summary: Option<String>,
+}
+
+/// Runs matrix combinations in parallel, each in a child process with its own
+/// git worktree.
+pub(super) struct Parallel<'a> {
+    jobs: usize,
+    git: Option<&'a Git>,
+    /// Set once kick has been interrupted.
+    term: Arc<AtomicBool>,
+    repo_dir: PathBuf,
+    state_dir: PathBuf,
+    worktrees_dir: PathBuf,
+    exe: PathBuf,
+    args: Vec<OsString>,
+    process_id: u32,
+    dry_run: bool,
+    keep: bool,
+    /// The directory where artifacts of the run are stored, which is shared
+    /// with all children.
+    artifacts_dir: PathBuf,
+    /// The revision worktrees are checked out at.
+    rev: Option<String>,
+    /// Worktrees which have been created, one for each slot.
+    worktrees: Vec<PathBuf>,
+    sequence: usize,
+    /// Rows of the final summary.
+    summary: Vec<(String, Status, Duration)>,
+}
+
+/// The status of a combination in the final summary.
+#[derive(Clone, Copy)]
+enum Status {
+    Pass,
+    Fail,
+    Cancelled,
+}
+
+impl<'a> Parallel<'a> {
+    /// Construct a new parallel runner with the given number of jobs.
+    pub(super) fn new(
+        cx: &'a Ctxt<'_>,
+        repo: &Repo,
+        c: &SessionConfig<'_, '_>,
+        jobs: usize,
+    ) -> Result<Self> {
+        let cache_dir = cx.paths.cache.context("Missing cache directory")?;
+        let exe = env::current_exe().context("Finding current executable")?;
+
+        Ok(Self {
+            jobs,
+            git: cx.system.git.first(),
+            term: cx.term.clone(),
+            repo_dir: std::path::absolute(cx.to_path(repo.path()))?,
+            state_dir: cache_dir.join("state"),
+            worktrees_dir: cache_dir.join("worktrees"),
+            exe,
+            args: child_args(),
+            process_id: std::process::id(),
+            dry_run: c.is_dry_run(),
+            keep: c.is_keep(),
+            artifacts_dir: c.artifacts_dir()?,
+            rev: None,
+            worktrees: Vec::new(),
+            sequence: 0,
+            summary: Vec::new(),
+        })
+    }
+
+    /// Run the given combinations in parallel, returning their outcomes in the
+    /// order they were specified.
+    ///
+    /// At most `max_parallel` combinations are run at the same time, and if
+    /// `fail_fast` is set combinations which haven't started yet are cancelled
+    /// once one has failed.
+    ///
+    /// If kick is interrupted, combinations which haven't started are cancelled
+    /// and an error is returned once the running ones have completed.
+    pub(super) fn run(
+        &mut self,
+        combinations: Vec<Combination>,
+        fail_fast: bool,
+        max_parallel: Option<usize>,
+    ) -> Result<Vec<Outcome>> {
+        let schedule = Schedule::new(self.jobs, max_parallel, combinations.len(), fail_fast);
+        self.prepare_worktrees(schedule.slots)?;
+
+        fs::create_dir_all(&self.state_dir)
+            .with_context(|| anyhow!("Creating {}", self.state_dir.display()))?;
+
+        let mut queue = Vec::new();
+
+        for mut combination in combinations {
+            let id = self.sequence;
+            self.sequence += 1;
+
+            let base = format!("kick-{}-batch-{id}", self.process_id);
+            combination.spec.outputs = self.state_dir.join(format!("{base}-outputs.json"));
+            combination.spec.summary = self.state_dir.join(format!("{base}-summary.md"));
+            combination.spec.artifacts = Some(self.artifacts_dir.clone());
+            let spec_path = self.state_dir.join(format!("{base}.json"));
+            queue.push((combination, spec_path));
+        }
+
+        let out = Mutex::new(StandardStream::stdout(ColorChoice::Auto));
+
+        let outcomes = schedule.run(
+            queue,
+            &self.term,
+            |slot, (combination, spec_path)| {
+                self.run_one(&out, combination, &spec_path, self.worktrees.get(slot))
+            },
+            |(combination, _)| Outcome::cancelled(combination),
+            |outcome| {
+                let mut out = lock(&out);
+                _ = writeln!(
+                    out,
+                    "# {} failed, cancelling the remaining combinations since fail-fast is set",
+                    outcome.label
+                );
+            },
+        );
+
+        for o in &outcomes {
+            let status = match (o.success, o.cancelled) {
+                (_, true) => Status::Cancelled,
+                (true, _) => Status::Pass,
+                (false, _) => Status::Fail,
+            };
+
+            self.summary.push((o.label.clone(), status, o.duration));
+        }
+
+        if self.term.load(Ordering::Relaxed) {
+            bail!("Interrupted");
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Run a single combination in a child process, prefixing each line it
+    /// outputs with its label.
+    fn run_one(
+        &self,
+        out: &Mutex<StandardStream>,
+        combination: Combination,
+        spec_path: &Path,
+        workdir: Option<&PathBuf>,
+    ) -> Outcome {
+        let start = Instant::now();
+        let Combination {
+            label,
+            continue_on_error,
+            mut spec,
+        } = combination;
+
+        spec.workdir = workdir.cloned();
+        // NB: Collected here since other combinations might have added masks
+        // since the combination was set up.
+        spec.masks = rstr::registered_masks();
+
+        let result = self.spawn_child(out, &label, &spec, spec_path);
+
+        let (success, outputs) = match result {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                tracing::error!("{label}: {error}");
+
+                for cause in error.chain().skip(1) {
+                    tracing::error!("{label}: Caused by: {cause}");
+                }
+
+                (false, BTreeMap::new())
+            }
+        };
+
+        // NB: Summaries are written even if the combination failed.
+        let summary = fs::read_to_string(&spec.summary).ok();
+
+        if !self.keep {
+            _ = fs::remove_file(spec_path);
+            _ = fs::remove_file(&spec.outputs);
+            _ = fs::remove_file(&spec.summary);
+        }
+
+        Outcome {
+            label,
+            continue_on_error,
+            success,
+            cancelled: false,
+            duration: start.elapsed(),
+            outputs,
+            summary,
+        }
+    }
+
+    fn spawn_child(
+        &self,
+        out: &Mutex<StandardStream>,
+        label: &str,
+        spec: &ChildSpec,
+        spec_path: &Path,
+    ) -> Result<(bool, BTreeMap<String, RString>)> {
+        fs::write(spec_path, serde_json::to_vec(spec)?)
+            .with_context(|| anyhow!("Writing {}", spec_path.display()))?;
+
+        let (reader, writer) = io::pipe()?;
+
+        // NB: The first argument is the `run` subcommand, which the hidden
+        // option has to follow.
+        let mut command = Command::new(&self.exe);
+        command.args(self.args.iter().take(1));
+        command.arg(BATCH_CHILD).arg(spec_path);
+        command.args(self.args.iter().skip(1));
+
+        command
+            .stdin(Stdio::null())
+            .stdout(writer.try_clone()?)
+            .stderr(writer);
+
+        let mut child = command.spawn()?;
+        // NB: Drop the command so that we don't hold on to the write end of
+        // the pipe, since we'd otherwise never see the end of the output.
+        drop(command);
+
+        let mut prefix = ColorSpec::new();
+        prefix.set_fg(Some(Color::Cyan));
+
+        for line in BufReader::new(reader).split(b'\n') {
+            let line = line?;
+            let mut out = lock(out);
+            out.set_color(&prefix)?;
+            write!(out, "[{label}]")?;
+            out.reset()?;
+            write!(out, " ")?;
+            out.write_all(&line)?;
+            writeln!(out)?;
+        }
+
+        let status = child.wait()?;
+
+        // NB: Outputs are written even if the combination failed, since we
+        // still need to register the values it masked.
+        let outputs: ChildOutputs = match fs::read(&spec.outputs) {
+            Ok(outputs) => serde_json::from_slice(&outputs)
+                .with_context(|| anyhow!("Reading {}", spec.outputs.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => ChildOutputs::default(),
+            Err(e) => return Err(e).with_context(|| anyhow!("Reading {}", spec.outputs.display())),
+        };
+
+        for mask in &outputs.masks {
+            rstr::add_mask(mask);
+        }
+
+        if !status.success() {
+            return Ok((false, BTreeMap::new()));
+        }
+
+        Ok((true, from_raw_outputs(outputs.outputs)))
+    }
+
+    /// Make sure that there is a worktree for each slot.
+    ///
+    /// During a dry run no worktrees are created.
+    fn prepare_worktrees(&mut self, slots: usize) -> Result<()> {
+        if self.dry_run || self.worktrees.len() >= slots {
+            return Ok(());
+        }
+
+        let Some(git) = self.git else {
+            bail!("Git is required to run jobs in parallel");
+        };
+
+        let rev = match &self.rev {
+            Some(rev) => rev.clone(),
+            None => {
+                // NB: This captures uncommitted changes to tracked files.
+                let rev = match git.stash_create(&self.repo_dir)? {
+                    Some(rev) => rev,
+                    None => git.rev_parse(&self.repo_dir, "HEAD")?,
+                };
+
+                self.rev = Some(rev.clone());
+                rev
+            }
+        };
+
+        fs::create_dir_all(&self.worktrees_dir)
+            .with_context(|| anyhow!("Creating {}", self.worktrees_dir.display()))?;
+
+        for slot in self.worktrees.len()..slots {
+            let path = self
+                .worktrees_dir
+                .join(format!("kick-{}-{slot}", self.process_id));
+
+            git.worktree_add(&self.repo_dir, &path, &rev)
+                .with_context(|| anyhow!("Adding worktree {}", path.display()))?;
+
+            self.worktrees.push(path);
+        }
+
+        Ok(())
+    }
+
+    /// Print a summary of all combinations which have been run.
+    pub(super) fn summary<O>(&self, o: &mut O) -> Result<()>
+    where
+        O: ?Sized + WriteColor,
+    {
+        if self.summary.is_empty() {
+            return Ok(());
+        }
+
+        let width = self
+            .summary
+            .iter()
+            .map(|(label, ..)| label.chars().count())
+            .max()
+            .unwrap_or_default();
+
+        let mut pass = ColorSpec::new();
+        pass.set_fg(Some(Color::Green));
+
+        let mut fail = ColorSpec::new();
+        fail.set_fg(Some(Color::Red));
+
+        let mut cancel = ColorSpec::new();
+        cancel.set_fg(Some(Color::Yellow));
+
+        writeln!(o, "# Summary:")?;
+
+        for (label, status, duration) in &self.summary {
+            write!(o, "  {label:width$}  ")?;
+
+            match status {
+                Status::Pass => {
+                    o.set_color(&pass)?;
+                    write!(o, "pass")?;
+                }
+                Status::Fail => {
+                    o.set_color(&fail)?;
+                    write!(o, "fail")?;
+                }
+                Status::Cancelled => {
+                    o.set_color(&cancel)?;
+                    write!(o, "cancelled")?;
+                    o.reset()?;
+                    writeln!(o)?;
+                    continue;
+                }
+            }
+
+            o.reset()?;
+            writeln!(o, "  {:>8.1}s", duration.as_secs_f64())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How combinations are scheduled, which is independent of how they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Schedule {
+    /// The number of combinations which are run at the same time.
+    slots: usize,
+    /// Cancel combinations which haven't started once one has failed.
+    fail_fast: bool,
+}
+
+impl Schedule {
+    /// Construct a schedule for the given number of combinations, which runs
+    /// at most `jobs` or `max_parallel` of them at the same time.
+    fn new(jobs: usize, max_parallel: Option<usize>, len: usize, fail_fast: bool) -> Self {
+        Self {
+            slots: jobs.min(max_parallel.unwrap_or(usize::MAX)).min(len),
+            fail_fast,
+        }
+    }
+
+    /// Run the given items with `run`, which is called with the slot the item
+    /// is run in, returning their outcomes in the order they were specified.
+    ///
+    /// Items which haven't started are passed to `cancel` instead once `term`
+    /// is set, or if `fail_fast` is set and an item failed which isn't allowed
+    /// to. The outcome which caused this is passed to `on_fail_fast` if any
+    /// items remain and `term` isn't set.
+    fn run<T, R, C, F>(
+        self,
+        items: Vec<T>,
+        term: &AtomicBool,
+        run: R,
+        cancel: C,
+        on_fail_fast: F,
+    ) -> Vec<Outcome>
+    where
+        T: Send,
+        R: Fn(usize, T) -> Outcome + Sync,
+        C: Fn(T) -> Outcome + Sync,
+        F: Fn(&Outcome) + Sync,
+    {
+        let queue = Mutex::new(items.into_iter().enumerate().collect::<VecDeque<_>>());
+        let outcomes = Mutex::new(Vec::new());
+        let cancelled = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            for slot in 0..self.slots {
+                let (queue, outcomes, cancelled) = (&queue, &outcomes, &cancelled);
+                let (run, cancel, on_fail_fast) = (&run, &cancel, &on_fail_fast);
+
+                s.spawn(move || {
+                    loop {
+                        let Some((index, item)) = lock(queue).pop_front() else {
+                            break;
+                        };
+
+                        if cancelled.load(Ordering::Acquire) || term.load(Ordering::Relaxed) {
+                            lock(outcomes).push((index, cancel(item)));
+                            continue;
+                        }
+
+                        let outcome = run(slot, item);
+
+                        if self.fail_fast
+                            && !outcome.success
+                            && !outcome.continue_on_error
+                            && !cancelled.swap(true, Ordering::AcqRel)
+                            && !term.load(Ordering::Relaxed)
+                            && !lock(queue).is_empty()
+                        {
+                            on_fail_fast(&outcome);
+                        }
+
+                        lock(outcomes).push((index, outcome));
+                    }
+                });
+            }
+        });
+
+        let mut outcomes = outcomes.into_inner().unwrap_or_else(|e| e.into_inner());
+        outcomes.sort_by_key(|(index, _)| *index);
+        outcomes.into_iter().map(|(_, o)| o).collect()
+    }
+}
+
+impl Outcome {
+    /// The outcome of a combination which was cancelled before it started.
+    fn cancelled(combination: Combination) -> Self {
+        Self {
+            label: combination.label,
+            continue_on_error: combination.continue_on_error,
+            success: false,
+            cancelled: true,
+            duration: Duration::ZERO,
+            outputs: BTreeMap::new(),
+            summary: None,
+        }
+    }
+}
+
+impl Drop for Parallel<'_> {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+
+        let Some(git) = self.git else {
+            return;
+        };
+
+        for path in self.worktrees.drain(..) {
+            tracing::trace!(?path, "Removing worktree");
+
+            if let Err(error) = git.worktree_remove(&self.repo_dir, &path) {
+                tracing::warn!(?path, ?error, "Failed to remove worktree");
+            }
+        }
+    }
+}
+
+/// Run the single matrix combination described by the spec at the given path.
+pub(super) fn child<O>(
+    o: &mut O,
+    cx: &Ctxt<'_>,
+    c: &mut SessionConfig<'_, '_>,
+    repo: &Repo,
+    path: &Path,
+    same_os: bool,
+) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    let spec = fs::read(path).with_context(|| anyhow!("Reading {}", path.display()))?;
+    let spec: ChildSpec = serde_json::from_slice(&spec)?;
+
+    if spec.repo.as_relative_path() != repo.path() {
+        return Ok(());
+    }
+
+    if let Some(workdir) = &spec.workdir {
+        c.set_path(workdir.clone());
+    }
+
+    if let Some(artifacts) = &spec.artifacts {
+        c.set_artifacts_dir(artifacts);
+    }
+
+    for mask in &spec.masks {
+        rstr::add_mask(mask);
+    }
+
+    let mut needs = Tree::new();
+
+    for (id, need) in &spec.needs {
+        needs.insert(["needs", id.as_str(), "result"], need.result.as_str());
+        needs.insert_prefix(["needs", id.as_str(), "outputs"], need.outputs.clone());
+    }
+
+    let w = c.load_github_workflows(repo)?;
+
+    let Some(workflow) = w.iter().find(|w| w.id() == spec.workflow) else {
+        bail!("Missing workflow `{}`", spec.workflow);
+    };
+
+    let Some(job) = workflow.jobs().find(|job| job.id() == spec.job) else {
+        bail!("{}: Missing job `{}`", spec.workflow, spec.job);
+    };
+
+    let deferred = job.build_deferred(&needs)?;
+
+    let Some(matrix) = job.matrices_with(deferred.as_deref()).nth(spec.matrix) else {
+        bail!(
+            "{}/{}: Missing matrix #{}",
+            spec.workflow,
+            spec.job,
+            spec.matrix
+        );
+    };
+
+    let batch = matrix.build(None, same_os, &cx.os, &needs)?;
+
+    let mut session = Session::new(c);
+    let outputs = batch.commit(o, c, &mut session);
+    write_annotations(o, c, session.annotations())?;
+
+    if !session.summaries().is_empty() {
+        write_summary_file(&spec.summary, session.summaries())?;
+    }
+
+    let child_outputs = ChildOutputs {
+        outputs: outputs.as_ref().map(to_raw_outputs).unwrap_or_default(),
+        masks: rstr::registered_masks(),
+    };
+
+    fs::write(&spec.outputs, serde_json::to_vec(&child_outputs)?)
+        .with_context(|| anyhow!("Writing {}", spec.outputs.display()))?;
+
+    outputs?;
+    Ok(())
+}
+
+/// Convert outputs into raw strings which can be passed between processes.
+///
+/// Raw strings retain which parts of them are redacted, see
+/// [`RStr::as_raw`][crate::rstr::RStr::as_raw].
+pub(super) fn to_raw_outputs(outputs: &BTreeMap<String, RString>) -> BTreeMap<String, String> {
+    outputs
+        .iter()
+        .map(|(key, value)| (key.clone(), value.as_raw().to_owned()))
+        .collect()
+}
+
+/// Convert outputs from raw strings produced by [`to_raw_outputs`].
+fn from_raw_outputs(outputs: BTreeMap<String, String>) -> BTreeMap<String, RString> {
+    outputs
+        .into_iter()
+        .map(|(key, value)| (key, RString::from(value)))
+        .collect()
+}
+
+/// Arguments to pass to child processes, which are the arguments we were
+/// invoked with.
+///
+/// NB: These are passed on as-is, since they might include a command whose
+/// arguments we shouldn't touch. Children ignore `--jobs`, since with
+/// `--batch-child` they only run a single combination.
+fn child_args() -> Vec<OsString> {
+    env::args_os().skip(1).collect()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,258 @@
+use std::sync::Barrier;
+use std::sync::atomic::AtomicUsize;
+
+use super::*;
+
+/// An item which is scheduled in tests.
+struct Item {
+    label: &'static str,
+    success: bool,
+    continue_on_error: bool,
+    /// Wait for all other items waiting on the barrier before completing.
+    wait: bool,
+    /// Wait until `on_fail_fast` has been called before completing.
+    wait_fail_fast: bool,
+}
+
+fn ok(label: &'static str) -> Item {
+    Item {
+        label,
+        success: true,
+        continue_on_error: false,
+        wait: false,
+        wait_fail_fast: false,
+    }
+}
+
+fn fail(label: &'static str) -> Item {
+    Item {
+        success: false,
+        ..ok(label)
+    }
+}
+
+fn outcome(label: &str, success: bool, continue_on_error: bool) -> Outcome {
+    Outcome {
+        label: label.to_owned(),
+        continue_on_error,
+        success,
+        cancelled: false,
+        duration: Duration::ZERO,
+        outputs: BTreeMap::new(),
+        summary: None,
+    }
+}
+
+/// Run items with the given schedule, returning the status of each item and
+/// the labels passed to `on_fail_fast`.
+fn schedule(schedule: Schedule, items: Vec<Item>, term: &AtomicBool) -> (Vec<String>, Vec<String>) {
+    let barrier = Barrier::new(items.iter().filter(|i| i.wait).count());
+    let notified = Mutex::new(Vec::new());
+
+    let outcomes = schedule.run(
+        items,
+        term,
+        |_, item| {
+            if item.wait {
+                barrier.wait();
+            }
+
+            while item.wait_fail_fast && lock(&notified).is_empty() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            outcome(item.label, item.success, item.continue_on_error)
+        },
+        |item| Outcome {
+            cancelled: true,
+            ..outcome(item.label, false, item.continue_on_error)
+        },
+        |outcome| lock(&notified).push(outcome.label.clone()),
+    );
+
+    let statuses = outcomes
+        .iter()
+        .map(|o| {
+            let status = match (o.success, o.cancelled) {
+                (_, true) => "cancelled",
+                (true, _) => "pass",
+                (false, _) => "fail",
+            };
+
+            format!("{}: {status}", o.label)
+        })
+        .collect();
+
+    (statuses, notified.into_inner().unwrap())
+}
+
+#[test]
+fn slots() {
+    assert_eq!(Schedule::new(4, None, 10, true).slots, 4);
+    assert_eq!(Schedule::new(4, Some(2), 10, true).slots, 2);
+    assert_eq!(Schedule::new(2, Some(4), 10, true).slots, 2);
+    assert_eq!(Schedule::new(4, None, 3, true).slots, 3);
+    assert_eq!(Schedule::new(4, Some(8), 0, true).slots, 0);
+}
+
+#[test]
+fn max_parallel() {
+    let schedule = Schedule::new(8, Some(2), 6, false);
+    let running = AtomicUsize::new(0);
+    let max = AtomicUsize::new(0);
+    let busy = [AtomicBool::new(false), AtomicBool::new(false)];
+
+    let outcomes = schedule.run(
+        (0..6).collect::<Vec<usize>>(),
+        &AtomicBool::new(false),
+        |slot, index| {
+            assert!(
+                !busy[slot].swap(true, Ordering::SeqCst),
+                "slot {slot} in use"
+            );
+            let n = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max.fetch_max(n, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10 * (6 - index) as u64));
+            running.fetch_sub(1, Ordering::SeqCst);
+            busy[slot].store(false, Ordering::SeqCst);
+            outcome(&index.to_string(), true, false)
+        },
+        |_| unreachable!(),
+        |_| unreachable!(),
+    );
+
+    assert_eq!(max.load(Ordering::SeqCst), 2);
+
+    let labels = outcomes
+        .iter()
+        .map(|o| o.label.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(labels, ["0", "1", "2", "3", "4", "5"]);
+}
+
+#[test]
+fn fail_fast() {
+    let term = AtomicBool::new(false);
+
+    let (statuses, notified) = schedule(
+        Schedule::new(1, None, 4, true),
+        vec![ok("a"), fail("b"), ok("c"), ok("d")],
+        &term,
+    );
+
+    assert_eq!(
+        statuses,
+        ["a: pass", "b: fail", "c: cancelled", "d: cancelled"]
+    );
+    assert_eq!(notified, ["b"]);
+
+    // Failing as the last combination has nothing left to cancel.
+    let (statuses, notified) = schedule(
+        Schedule::new(1, None, 2, true),
+        vec![ok("a"), fail("b")],
+        &term,
+    );
+
+    assert_eq!(statuses, ["a: pass", "b: fail"]);
+    assert!(notified.is_empty());
+}
+
+#[test]
+fn fail_fast_running() {
+    let a = Item {
+        wait: true,
+        ..fail("a")
+    };
+
+    let b = Item {
+        wait: true,
+        wait_fail_fast: true,
+        ..ok("b")
+    };
+
+    // Combinations which are already running when another fails are allowed to
+    // complete.
+    let (statuses, notified) = schedule(
+        Schedule::new(2, None, 4, true),
+        vec![a, b, ok("c"), ok("d")],
+        &AtomicBool::new(false),
+    );
+
+    assert_eq!(
+        statuses,
+        ["a: fail", "b: pass", "c: cancelled", "d: cancelled"]
+    );
+    assert_eq!(notified, ["a"]);
+}
+
+#[test]
+fn no_fail_fast() {
+    let (statuses, notified) = schedule(
+        Schedule::new(1, None, 3, false),
+        vec![fail("a"), ok("b"), fail("c")],
+        &AtomicBool::new(false),
+    );
+
+    assert_eq!(statuses, ["a: fail", "b: pass", "c: fail"]);
+    assert!(notified.is_empty());
+}
+
+#[test]
+fn continue_on_error() {
+    let allowed = Item {
+        continue_on_error: true,
+        ..fail("a")
+    };
+
+    let (statuses, notified) = schedule(
+        Schedule::new(1, None, 3, true),
+        vec![allowed, ok("b"), fail("c"), ok("d")],
+        &AtomicBool::new(false),
+    );
+
+    assert_eq!(statuses, ["a: fail", "b: pass", "c: fail", "d: cancelled"]);
+    assert_eq!(notified, ["c"]);
+}
+
+#[test]
+fn interrupted() {
+    let term = AtomicBool::new(false);
+
+    // Interrupting cancels the remaining combinations without reporting the
+    // failures it caused as fail-fast.
+    let outcomes = Schedule::new(1, None, 3, true).run(
+        vec!["a", "b", "c"],
+        &term,
+        |_, label| {
+            term.store(true, Ordering::Relaxed);
+            outcome(label, false, false)
+        },
+        |label| Outcome {
+            cancelled: true,
+            ..outcome(label, false, false)
+        },
+        |_| unreachable!(),
+    );
+
+    let cancelled = outcomes.iter().map(|o| o.cancelled).collect::<Vec<_>>();
+    assert_eq!(cancelled, [false, true, true]);
+}
+
+#[test]
+fn raw_outputs() {
+    let mut value = RString::from("token=");
+    value.push_redacted("secret");
+
+    let mut outputs = BTreeMap::new();
+    outputs.insert(String::from("token"), value);
+    outputs.insert(String::from("plain"), RString::from("public"));
+
+    // Outputs pass between processes as JSON, which must retain which parts
+    // of them are redacted.
+    let json = serde_json::to_string(&to_raw_outputs(&outputs)).unwrap();
+    let outputs = from_raw_outputs(serde_json::from_str(&json).unwrap());
+
+    assert_eq!(outputs["token"].to_string(), "token=***");
+    assert_eq!(outputs["token"].to_exposed(), "token=secret");
+    assert_eq!(outputs["plain"].to_string(), "public");
+}
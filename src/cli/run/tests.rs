@@ -0,0 +1,61 @@
+use super::*;
+
+#[test]
+fn needs_succeeded() {
+    assert_eq!(needs_result([]), (JobResult::Success, None));
+    assert_eq!(
+        needs_result([("a", JobResult::Success), ("b", JobResult::Success)]),
+        (JobResult::Success, None)
+    );
+}
+
+#[test]
+fn needs_blocked() {
+    assert_eq!(
+        needs_result([
+            ("a", JobResult::Success),
+            ("b", JobResult::Skipped),
+            ("c", JobResult::Failure),
+        ]),
+        (JobResult::Failure, Some("b"))
+    );
+    assert_eq!(
+        needs_result([("a", JobResult::Skipped), ("b", JobResult::Success)]),
+        (JobResult::Skipped, Some("a"))
+    );
+}
+
+#[test]
+fn skip_dependents() {
+    // A chain of jobs `build -> test -> deploy` along with `lint` which only
+    // needs `build`, where `build` fails.
+    let graph: [(&str, &[&str]); 4] = [
+        ("build", &[]),
+        ("test", &["build"]),
+        ("lint", &["build"]),
+        ("deploy", &["test"]),
+    ];
+
+    let mut completed = HashMap::new();
+    completed.insert("build", JobResult::Failure);
+    let mut skipped = Vec::new();
+
+    for (id, needs) in &graph[1..] {
+        let (_, blocked) = needs_result(needs.iter().map(|need| (*need, completed[need])));
+
+        let result = match blocked {
+            Some(need) => {
+                skipped.push(format!("{id} since {need}"));
+                JobResult::Skipped
+            }
+            None => JobResult::Success,
+        };
+
+        completed.insert(id, result);
+    }
+
+    assert_eq!(
+        skipped,
+        ["test since build", "lint since build", "deploy since test"]
+    );
+}
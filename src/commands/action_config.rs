@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::rc::Rc;
+use std::time::Duration;
 
 use anyhow::Result;
 
@@ -16,6 +17,8 @@ pub(crate) struct ActionConfig<'a> {
     skipped: Option<String>,
     inputs: BTreeMap<String, RString>,
     pub(super) repo: Option<&'a RStr>,
+    continue_on_error: bool,
+    timeout: Option<Duration>,
 }
 
 impl<'a> ActionConfig<'a> {
@@ -28,6 +31,8 @@ impl<'a> ActionConfig<'a> {
             skipped: None,
             inputs: BTreeMap::new(),
             repo: None,
+            continue_on_error: false,
+            timeout: None,
         }
     }
 
@@ -68,6 +73,16 @@ impl<'a> ActionConfig<'a> {
         self.inputs.iter().map(|(k, v)| (k.as_str(), v.as_rstr()))
     }
 
+    /// Test if the action is allowed to fail.
+    pub(crate) fn continue_on_error(&self) -> bool {
+        self.continue_on_error
+    }
+
+    /// Get the timeout of the action.
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
     /// Set the id of the action.
     pub(crate) fn with_id(mut self, id: Option<&'a Rc<RStr>>) -> Self {
         self.id = id;
@@ -89,6 +104,18 @@ impl<'a> ActionConfig<'a> {
         self
     }
 
+    /// Set whether the action is allowed to fail.
+    pub(crate) fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Set the timeout of the action.
+    pub(crate) fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
     /// Construct a new use batch.
     pub(crate) fn new_use_batch(
         &self,
@@ -5,12 +5,12 @@ use std::str;
 
 use anyhow::{Result, bail};
 
-use crate::action::ActionKind;
-use crate::rstr::rformat;
+use crate::action::{ActionKind, DockerImage};
+use crate::rstr::{RString, rformat};
 
 use super::{
-    ActionConfig, Env, Schedule, ScheduleGroup, ScheduleNodeAction, ScheduleOutputs, SessionConfig,
-    build_steps,
+    ActionConfig, Env, Schedule, ScheduleDockerAction, ScheduleGroup, ScheduleNodeAction,
+    ScheduleOutputs, ScheduleStaticSetup, SessionConfig, build_steps,
 };
 
 #[derive(Debug)]
@@ -88,7 +88,7 @@ impl ActionRunners {
             bail!("Could not find action runner for {}", c.action_name());
         };
 
-        let main;
+        let mut main = Vec::new();
         let mut pre = None;
         let mut post = None;
 
@@ -139,13 +139,17 @@ impl ActionRunners {
                 let mut group = ScheduleGroup::new(
                     Some(c.action_name().as_rc()),
                     c.id().cloned(),
-                    Rc::from([Schedule::NodeAction(ScheduleNodeAction::new(
-                        main_path.clone(),
-                        *node_version,
-                        c.skipped(),
-                        env.clone(),
-                        None,
-                    ))]),
+                    Rc::from([Schedule::NodeAction(
+                        ScheduleNodeAction::new(
+                            main_path.clone(),
+                            *node_version,
+                            c.skipped(),
+                            env.clone(),
+                            None,
+                        )
+                        .with_continue_on_error(c.continue_on_error())
+                        .with_timeout(c.timeout()),
+                    )]),
                 );
 
                 if !outputs.is_empty() {
@@ -155,17 +159,108 @@ impl ActionRunners {
                     });
                 }
 
-                main = Schedule::Group(group);
+                main.push(Schedule::Group(group));
             }
             ActionKind::Composite { steps } => {
-                main = build_steps(
+                main.push(Schedule::Group(build_steps(
                     batch,
                     Some(c),
                     c.id(),
                     Some(c.action_name()),
                     steps,
                     Some(action),
-                )?;
+                )?));
+            }
+            ActionKind::Docker {
+                image,
+                entrypoint,
+                pre_entrypoint,
+                pre_if,
+                post_entrypoint,
+                post_if,
+                args,
+                env: action_env,
+            } => {
+                let env = Env::new(batch, Some(action), Some(c))?;
+
+                let image = match image {
+                    DockerImage::Image(image) => Rc::<str>::from(image.as_str()),
+                    DockerImage::Dockerfile(dockerfile) => {
+                        let Some(podman) = batch.cx.system.podman.first() else {
+                            bail!(
+                                "Podman is needed to build the image of {}, see https://podman.io/docs/installation",
+                                c.action_name()
+                            );
+                        };
+
+                        let name = c.action_name().to_exposed();
+                        let tag = image_tag(&name);
+
+                        // NB: Local actions are always rebuilt since they
+                        // change along with the repo, which is cheap if they
+                        // haven't since podman caches the layers of images.
+                        // Other actions are pinned to a version, so their
+                        // image is only built once.
+                        if name.starts_with("./") || !podman.image_exists(&tag)? {
+                            let args = vec![
+                                RString::from("build"),
+                                RString::from("--tag"),
+                                RString::from(tag.as_str()),
+                                RString::from("--file"),
+                                RString::from(dockerfile.to_string_lossy()),
+                                RString::from(action.action_path.to_string_lossy()),
+                            ];
+
+                            main.push(Schedule::StaticSetup(ScheduleStaticSetup::new(
+                                &podman.path,
+                                "build docker image",
+                                args,
+                                c.skipped().map(str::to_owned),
+                            )));
+                        }
+
+                        Rc::from(tag)
+                    }
+                };
+
+                let args = Rc::<[String]>::from(args.as_slice());
+                let action_env = Rc::new(action_env.clone());
+
+                let docker = |entrypoint: Option<&String>, condition: Option<&String>| {
+                    ScheduleDockerAction::new(
+                        image.clone(),
+                        entrypoint.map(|e| Rc::from(e.as_str())),
+                        args.clone(),
+                        action_env.clone(),
+                        c.skipped(),
+                        env.clone(),
+                        condition.cloned(),
+                    )
+                };
+
+                if let Some(entrypoint) = pre_entrypoint {
+                    pre = Some(Schedule::DockerAction(
+                        docker(Some(entrypoint), pre_if.as_ref())
+                            .with_name(rformat!("{} (pre)", c.action_name()).as_rc()),
+                    ));
+                }
+
+                if let Some(entrypoint) = post_entrypoint {
+                    post = Some(Schedule::DockerAction(
+                        docker(Some(entrypoint), post_if.as_ref())
+                            .with_name(rformat!("{} (post)", c.action_name()).as_rc()),
+                    ));
+                }
+
+                // NB: The action is not run in a group of its own, so that the
+                // outputs it produces are stored under its id.
+                main.push(Schedule::DockerAction(
+                    docker(entrypoint.as_ref(), None)
+                        .with_name(c.action_name().as_rc())
+                        .with_id(c.id().cloned())
+                        .with_continue_on_error(c.continue_on_error())
+                        .with_timeout(c.timeout()),
+                ));
             }
         }
 
@@ -174,7 +269,55 @@ impl ActionRunners {
 }
 
 pub(super) struct RunnerSteps {
-    pub(super) main: Schedule,
+    pub(super) main: Vec<Schedule>,
     pub(super) pre: Option<Schedule>,
     pub(super) post: Option<Schedule>,
 }
+
+/// Construct the tag of a locally built image for the given action, like
+/// `localhost/kick/<user>/<repo>:<version>`.
+///
+/// Actions loaded from a path in the repo, like `./.github/actions/foo`, are
+/// tagged as `localhost/kick/local/github/actions/foo:latest`.
+fn image_tag(action_name: &str) -> String {
+    let (name, version) = match action_name.strip_prefix("./") {
+        Some(path) => (path, "latest"),
+        None => action_name
+            .split_once('@')
+            .unwrap_or((action_name, "latest")),
+    };
+
+    let mut tag = String::from("localhost/kick/");
+
+    if action_name.starts_with("./") {
+        tag.push_str("local/");
+    }
+
+    for (n, component) in name.split('/').filter(|c| !c.is_empty()).enumerate() {
+        if n > 0 {
+            tag.push('/');
+        }
+
+        // NB: Path components must start and end with an alphanumeric
+        // character.
+        let component = component.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+
+        for c in component.chars() {
+            tag.push(match c.to_ascii_lowercase() {
+                c @ ('a'..='z' | '0'..='9' | '.' | '_' | '-') => c,
+                _ => '-',
+            });
+        }
+    }
+
+    tag.push(':');
+
+    for c in version.chars() {
+        tag.push(match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '-',
+        });
+    }
+
+    tag
+}
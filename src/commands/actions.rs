@@ -1,14 +1,17 @@
+#[cfg(test)]
+mod tests;
+
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{self};
-use std::path::Path;
+use std::path::{self, Path, PathBuf};
 use std::rc::Rc;
 use std::str;
 
 use anyhow::{Context, Result, anyhow, bail};
 use bstr::BString;
 use gix::ObjectId;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use tracing::Level;
@@ -70,6 +73,9 @@ struct KickMetaRef<'a> {
 pub(crate) struct Actions {
     actions: BTreeSet<(String, String, String)>,
     changed: Vec<(String, String, String)>,
+    /// Actions which are loaded from a path in the repo.
+    local: BTreeSet<String>,
+    changed_local: Vec<String>,
     pub(crate) found_node_versions: BTreeSet<Version>,
 }
 
@@ -89,18 +95,39 @@ impl Actions {
                     self.changed.push((repo, name, version));
                 }
 
+                Ok(())
+            }
+            Use::Local(path) => {
+                if self.local.insert(path.clone()) {
+                    self.changed_local.push(path);
+                }
+
                 Ok(())
             }
         }
     }
 
-    /// Synchronize github uses.
+    /// Synchronize github uses and load local uses relative to the given
+    /// repo directory.
     pub(super) fn synchronize(
         &mut self,
         runners: &mut ActionRunners,
         cx: &Ctxt<'_>,
+        repo_dir: &Path,
         eval: &Eval,
     ) -> Result<()> {
+        for path in self.changed_local.drain(..) {
+            load_local_action(
+                runners,
+                cx,
+                eval,
+                repo_dir,
+                &path,
+                &mut self.found_node_versions,
+            )
+            .with_context(|| anyhow!("Failed to load local action {path}"))?;
+        }
+
         for (repo, name, version) in self.changed.drain(..) {
             sync_action(
                 runners,
@@ -118,6 +145,48 @@ impl Actions {
     }
 }
 
+fn load_local_action(
+    runners: &mut ActionRunners,
+    cx: &Ctxt<'_>,
+    eval: &Eval,
+    repo_dir: &Path,
+    path: &str,
+    node_versions: &mut BTreeSet<Version>,
+) -> Result<()> {
+    if runners.contains(path) {
+        return Ok(());
+    }
+
+    let cache_dir = cx
+        .paths
+        .cache
+        .context("Kick does not have project directories")?;
+
+    let action_dir = local_action_dir(repo_dir, path)?;
+
+    let span = tracing::span!(Level::DEBUG, "load_local_action", ?path, ?action_dir);
+    let _enter = span.enter();
+
+    let action = crate::action::load_local(&action_dir, eval)?;
+
+    if let ActionKind::Node { node_version, .. } = action.kind {
+        node_versions.insert(Version::new(node_version, 0, 0));
+    }
+
+    // NB: Local actions share the state directory of the session, since they
+    // don't have a cache directory of their own.
+    let runner = ActionRunner::new(
+        action.kind,
+        action.defaults,
+        action.outputs,
+        Rc::from(action_dir),
+        Rc::from(cache_dir.join("state")),
+    );
+
+    runners.insert(path.to_owned(), runner);
+    Ok(())
+}
+
 fn sync_action(
     runners: &mut ActionRunners,
     cx: &Ctxt<'_>,
@@ -322,12 +391,25 @@ fn write_meta(path: &Path, value: KickMetaRef<'_>) -> Result<()> {
     Ok(())
 }
 
+/// Resolve the directory of an action referenced by path, like
+/// `./.github/actions/foo`, which is relative to the root of the repo.
+fn local_action_dir(repo_dir: &Path, path: &str) -> Result<PathBuf> {
+    Ok(path::absolute(
+        RelativePath::new(path).to_logical_path(repo_dir),
+    )?)
+}
+
 enum Use {
     Github(String, String, String),
+    Local(String),
 }
 
 impl Use {
     fn parse(uses: &str) -> Result<Self> {
+        if uses.starts_with("./") {
+            return Ok(Self::Local(uses.to_owned()));
+        }
+
         let ((repo, name), version) = uses
             .split_once('@')
             .and_then(|(k, v)| Some((k.split_once('/')?, v)))
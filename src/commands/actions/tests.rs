@@ -0,0 +1,37 @@
+use super::*;
+
+#[test]
+fn parse_uses() -> Result<()> {
+    let Use::Local(path) = Use::parse("./.github/actions/foo")? else {
+        panic!("expected local action");
+    };
+
+    assert_eq!(path, "./.github/actions/foo");
+
+    let Use::Github(repo, name, version) = Use::parse("actions/checkout@v4")? else {
+        panic!("expected github action");
+    };
+
+    assert_eq!((&*repo, &*name, &*version), ("actions", "checkout", "v4"));
+
+    assert!(Use::parse("checkout@v4").is_err());
+    assert!(Use::parse(".github/actions/foo").is_err());
+    Ok(())
+}
+
+#[test]
+fn local_action_dirs() -> Result<()> {
+    let repo_dir = path::absolute("repo")?;
+
+    assert_eq!(
+        local_action_dir(&repo_dir, "./.github/actions/foo")?,
+        repo_dir.join(".github").join("actions").join("foo")
+    );
+
+    assert_eq!(
+        local_action_dir(&repo_dir, "./actions/../shared/")?,
+        repo_dir.join("shared")
+    );
+
+    Ok(())
+}
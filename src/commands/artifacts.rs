@@ -0,0 +1,630 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{self, Component, Path, PathBuf};
+use std::rc::Rc;
+use std::slice;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, anyhow, bail};
+use termcolor::WriteColor;
+
+use crate::glob::Fragment;
+use crate::rstr::{RStr, RString};
+
+use super::SessionConfig;
+use super::cache::{self, write_header};
+
+/// The directory in the kick cache where artifacts are stored, with one
+/// directory for each run.
+pub(super) const ARTIFACTS_DIR: &str = "artifacts";
+
+/// The number of days artifacts are retained unless `retention-days` is
+/// specified.
+const DEFAULT_RETENTION_DAYS: u64 = 1;
+
+/// The directory in an artifact which contains its files.
+const FILES_DIR: &str = "files";
+
+/// The file in an artifact which contains the unix timestamp of when it
+/// expires. It is written last, so only artifacts which have it are complete.
+const EXPIRES_FILE: &str = "expires";
+
+/// Characters which are not permitted in artifact names.
+const INVALID_NAME_CHARACTERS: [char; 11] =
+    ['"', ':', '<', '>', '|', '*', '?', '\r', '\n', '\\', '/'];
+
+/// The operation performed by an artifact step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ArtifactOperation {
+    Upload,
+    Download,
+}
+
+impl ArtifactOperation {
+    /// Get the artifact operation performed by the given action, which is
+    /// either `upload-artifact` or `download-artifact` in the `actions`
+    /// organization.
+    pub(super) fn from_action(repo: &RStr) -> Option<Self> {
+        if repo.str_eq("upload-artifact") {
+            return Some(Self::Upload);
+        }
+
+        if repo.str_eq("download-artifact") {
+            return Some(Self::Download);
+        }
+
+        None
+    }
+}
+
+/// What to do if no files are found when uploading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IfNoFilesFound {
+    Warn,
+    Error,
+    Ignore,
+}
+
+#[derive(Clone)]
+enum Kind {
+    Upload {
+        name: String,
+        paths: Vec<String>,
+        if_no_files_found: IfNoFilesFound,
+        retention_days: u64,
+        overwrite: bool,
+        include_hidden_files: bool,
+    },
+    Download {
+        name: Option<String>,
+        path: Option<String>,
+        pattern: Option<String>,
+        merge_multiple: bool,
+    },
+}
+
+/// A builtin emulation of `actions/upload-artifact` and
+/// `actions/download-artifact`, which stores artifacts in a directory for the
+/// current run in the kick cache directory.
+#[derive(Clone)]
+pub(crate) struct ScheduleArtifact {
+    id: Option<Rc<RStr>>,
+    kind: Kind,
+    skipped: Option<String>,
+}
+
+impl ScheduleArtifact {
+    /// Construct a new artifact operation from the inputs of a step.
+    pub(super) fn new(
+        operation: ArtifactOperation,
+        id: Option<&Rc<RStr>>,
+        with: &BTreeMap<String, RString>,
+        skipped: Option<&str>,
+    ) -> Result<Self> {
+        let get = |key: &str| {
+            with.get(key)
+                .map(|v| v.to_exposed().trim().to_owned())
+                .filter(|v| !v.is_empty())
+        };
+
+        let flag = |key: &str| get(key).is_some_and(|v| v == "true");
+
+        let kind = match operation {
+            ArtifactOperation::Upload => {
+                let name = get("name").unwrap_or_else(|| String::from("artifact"));
+                validate_name(&name)?;
+
+                let Some(path) = get("path") else {
+                    bail!("Missing required input `path`");
+                };
+
+                let if_no_files_found = match get("if-no-files-found").as_deref() {
+                    None | Some("warn") => IfNoFilesFound::Warn,
+                    Some("error") => IfNoFilesFound::Error,
+                    Some("ignore") => IfNoFilesFound::Ignore,
+                    Some(other) => bail!("Unsupported value for `if-no-files-found`: {other}"),
+                };
+
+                let retention_days = match get("retention-days") {
+                    Some(days) => days
+                        .parse::<u64>()
+                        .with_context(|| anyhow!("Invalid `retention-days`: {days}"))?,
+                    None => 0,
+                };
+
+                Kind::Upload {
+                    name,
+                    paths: cache::lines(&path),
+                    if_no_files_found,
+                    retention_days: match retention_days {
+                        0 => DEFAULT_RETENTION_DAYS,
+                        days => days,
+                    },
+                    overwrite: flag("overwrite"),
+                    include_hidden_files: flag("include-hidden-files"),
+                }
+            }
+            ArtifactOperation::Download => Kind::Download {
+                name: get("name"),
+                path: get("path"),
+                pattern: get("pattern"),
+                merge_multiple: flag("merge-multiple"),
+            },
+        };
+
+        Ok(Self {
+            id: id.cloned(),
+            kind,
+            skipped: skipped.map(str::to_owned),
+        })
+    }
+
+    /// The id of the step, under which outputs are stored.
+    pub(super) fn id(&self) -> Option<&RStr> {
+        self.id.as_deref()
+    }
+
+    /// The name of the operation.
+    pub(super) fn name(&self) -> &'static RStr {
+        match self.kind {
+            Kind::Upload { .. } => RStr::new("upload artifact (builtin)"),
+            Kind::Download { .. } => RStr::new("download artifact (builtin)"),
+        }
+    }
+
+    /// Perform the artifact operation, returning the outputs it produced.
+    ///
+    /// Paths prefixed with `~/` are resolved against `home`, see
+    /// [`cache::resolve_path`].
+    pub(super) fn run<O>(
+        &self,
+        o: &mut O,
+        c: &SessionConfig<'_, '_>,
+        name: Option<&RStr>,
+        home: Option<&Path>,
+    ) -> Result<Vec<(String, String)>>
+    where
+        O: ?Sized + WriteColor,
+    {
+        write_header(o, c, name, self.skipped.as_deref())?;
+
+        if self.skipped.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let dir = c.artifacts_dir()?;
+        let workspace = path::absolute(&c.path)?;
+
+        match &self.kind {
+            Kind::Upload {
+                name,
+                paths,
+                if_no_files_found,
+                retention_days,
+                overwrite,
+                include_hidden_files,
+            } => {
+                let files = find_files(&workspace, home, paths, *include_hidden_files)?;
+
+                if files.is_empty() {
+                    no_files_found(o, *if_no_files_found, paths)?;
+                    return Ok(Vec::new());
+                }
+
+                let root = search_root(&workspace, home, paths)?;
+                let target = dir.join(name);
+
+                if target.join(EXPIRES_FILE).is_file() && !*overwrite {
+                    bail!(
+                        "An artifact with the name `{name}` already exists, set `overwrite: true` to replace it"
+                    );
+                }
+
+                if c.dry_run {
+                    writeln!(
+                        o,
+                        "Would upload artifact {name} with {} file(s)",
+                        files.len()
+                    )?;
+                    return Ok(Vec::new());
+                }
+
+                if let Some(parent) = dir.parent() {
+                    prune(parent)?;
+                }
+
+                upload(&target, &root, &files, *retention_days)
+                    .with_context(|| anyhow!("Uploading artifact `{name}`"))?;
+
+                writeln!(
+                    o,
+                    "Artifact {name} has been uploaded with {} file(s)",
+                    files.len()
+                )?;
+
+                Ok(Vec::new())
+            }
+            Kind::Download {
+                name,
+                path,
+                pattern,
+                merge_multiple,
+            } => {
+                let base = match path {
+                    Some(path) => cache::resolve_path(&workspace, home, path)?,
+                    None => workspace.clone(),
+                };
+
+                let downloads = downloads(
+                    &dir,
+                    &base,
+                    name.as_deref(),
+                    pattern.as_deref(),
+                    *merge_multiple,
+                )?;
+
+                for (name, target) in downloads {
+                    if c.dry_run {
+                        writeln!(o, "Would download artifact {name} to {}", target.display())?;
+                        continue;
+                    }
+
+                    copy_dir(&dir.join(&name).join(FILES_DIR), &target)
+                        .with_context(|| anyhow!("Downloading artifact `{name}`"))?;
+
+                    writeln!(o, "Artifact {name} was downloaded to {}", target.display())?;
+                }
+
+                Ok(vec![(
+                    String::from("download-path"),
+                    base.display().to_string(),
+                )])
+            }
+        }
+    }
+}
+
+/// Copy all artifacts uploaded during the run into the given directory, with
+/// one directory for each artifact.
+pub(crate) fn collect_artifacts<O>(
+    o: &mut O,
+    c: &SessionConfig<'_, '_>,
+    output: &Path,
+) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    let dir = c.artifacts_dir()?;
+    let names = list(&dir)?;
+
+    if names.is_empty() {
+        writeln!(o, "# No artifacts to collect")?;
+        return Ok(());
+    }
+
+    for name in &names {
+        copy_dir(&dir.join(name).join(FILES_DIR), &output.join(name))
+            .with_context(|| anyhow!("Collecting artifact `{name}`"))?;
+    }
+
+    writeln!(
+        o,
+        "# Collected {} artifact(s) into {}",
+        names.len(),
+        output.display()
+    )?;
+
+    Ok(())
+}
+
+/// Report that no files were found when uploading, which is an error if
+/// `if-no-files-found` is `error`.
+fn no_files_found<O>(o: &mut O, if_no_files_found: IfNoFilesFound, paths: &[String]) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    let paths = paths.join(", ");
+
+    match if_no_files_found {
+        IfNoFilesFound::Error => bail!("No files were found with the provided path: {paths}"),
+        IfNoFilesFound::Warn => writeln!(
+            o,
+            "No files were found with the provided path: {paths}. No artifacts will be uploaded."
+        )?,
+        IfNoFilesFound::Ignore => {}
+    }
+
+    Ok(())
+}
+
+/// Get the artifacts to download from the given directory and the directory
+/// each of them is downloaded to.
+///
+/// Without a name all artifacts matching `pattern` are downloaded in name
+/// order. With `merge_multiple` they are all downloaded into `base`, so if
+/// several artifacts contain a file with the same path the one from the
+/// artifact which sorts last is kept.
+fn downloads(
+    dir: &Path,
+    base: &Path,
+    name: Option<&str>,
+    pattern: Option<&str>,
+    merge_multiple: bool,
+) -> Result<Vec<(String, PathBuf)>> {
+    if let Some(name) = name {
+        if !dir.join(name).join(EXPIRES_FILE).is_file() {
+            bail!("Unable to download artifact: Artifact not found for name: {name}");
+        }
+
+        return Ok(vec![(name.to_owned(), base.to_owned())]);
+    }
+
+    let pattern = pattern.map(Fragment::parse);
+    let mut downloads = Vec::new();
+
+    for name in list(dir)? {
+        if pattern.as_ref().is_some_and(|p| !p.is_match(&name)) {
+            continue;
+        }
+
+        let target = if merge_multiple {
+            base.to_owned()
+        } else {
+            base.join(&name)
+        };
+
+        downloads.push((name, target));
+    }
+
+    Ok(downloads)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name == "." || name == ".." || name.contains(INVALID_NAME_CHARACTERS) {
+        bail!("Invalid artifact name `{name}`");
+    }
+
+    Ok(())
+}
+
+/// Store files in an artifact, relative to the given root.
+fn upload(target: &Path, root: &Path, files: &[PathBuf], retention_days: u64) -> Result<()> {
+    if target.exists() {
+        fs::remove_dir_all(target).with_context(|| anyhow!("{}", target.display()))?;
+    }
+
+    let files_dir = target.join(FILES_DIR);
+
+    for file in files {
+        let Ok(rest) = file.strip_prefix(root) else {
+            bail!(
+                "{}: File is not inside of {}",
+                file.display(),
+                root.display()
+            );
+        };
+
+        let to = files_dir.join(rest);
+
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).with_context(|| anyhow!("{}", parent.display()))?;
+        }
+
+        fs::copy(file, &to).with_context(|| anyhow!("{}", file.display()))?;
+    }
+
+    let expires = SystemTime::now() + Duration::from_secs(retention_days.saturating_mul(86400));
+    let expires = expires.duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+    fs::write(target.join(EXPIRES_FILE), expires.to_string())?;
+    Ok(())
+}
+
+/// Find all files matched by the given patterns, where directories are
+/// searched recursively.
+///
+/// Patterns prefixed with `!` exclude any matched file which is or is inside
+/// of a path they match. Hidden files matched by a glob or found inside of a
+/// directory are excluded unless `include_hidden_files` is set.
+fn find_files(
+    workspace: &Path,
+    home: Option<&Path>,
+    patterns: &[String],
+    include_hidden_files: bool,
+) -> Result<Vec<PathBuf>> {
+    let is_hidden = |path: &Path| {
+        !include_hidden_files
+            && path
+                .file_name()
+                .is_some_and(|n| n.as_encoded_bytes().starts_with(b"."))
+    };
+
+    let mut queue = Vec::new();
+    let mut excluded = Vec::new();
+
+    for pattern in patterns {
+        if let Some(pattern) = pattern.strip_prefix('!') {
+            let pattern = [pattern.trim().to_owned()];
+            excluded.extend(cache::resolve_paths(workspace, home, &pattern)?);
+            continue;
+        }
+
+        let glob = cache::is_glob(pattern);
+
+        for path in cache::resolve_paths(workspace, home, slice::from_ref(pattern))? {
+            if glob && is_hidden(&path) {
+                continue;
+            }
+
+            queue.push(path);
+        }
+    }
+
+    let mut files = Vec::new();
+
+    while let Some(path) = queue.pop() {
+        if excluded.iter().any(|e| path.starts_with(e)) {
+            continue;
+        }
+
+        let m = fs::metadata(&path).with_context(|| anyhow!("{}", path.display()))?;
+
+        if !m.is_dir() {
+            files.push(path);
+            continue;
+        }
+
+        for e in fs::read_dir(&path).with_context(|| anyhow!("{}", path.display()))? {
+            let path = e?.path();
+
+            if is_hidden(&path) {
+                continue;
+            }
+
+            queue.push(path);
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Get the root directory that uploaded files are stored relative to, which is
+/// the least common ancestor of all paths searched.
+///
+/// For glob patterns, the path searched is the part preceding the first
+/// component containing a wildcard.
+fn search_root(workspace: &Path, home: Option<&Path>, patterns: &[String]) -> Result<PathBuf> {
+    let mut root = None::<PathBuf>;
+
+    for pattern in patterns {
+        if pattern.starts_with('!') {
+            continue;
+        }
+
+        let mut path = cache::resolve_path(workspace, home, pattern)?;
+
+        if cache::is_glob(pattern) {
+            path = path
+                .components()
+                .take_while(|c| !is_glob_component(c))
+                .collect();
+        } else if !path.is_dir()
+            && let Some(parent) = path.parent()
+        {
+            path = parent.to_owned();
+        }
+
+        root = Some(match root {
+            None => path,
+            Some(root) => root
+                .ancestors()
+                .find(|a| path.starts_with(a))
+                .map(Path::to_owned)
+                .unwrap_or_default(),
+        });
+    }
+
+    Ok(root.unwrap_or_else(|| workspace.to_owned()))
+}
+
+fn is_glob_component(c: &Component<'_>) -> bool {
+    matches!(c, Component::Normal(c) if c.to_str().is_some_and(cache::is_glob))
+}
+
+/// List the names of all complete artifacts in the given directory.
+fn list(dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => return Err(e).with_context(|| anyhow!("{}", dir.display())),
+    };
+
+    for e in read_dir {
+        let path = e.with_context(|| anyhow!("{}", dir.display()))?.path();
+
+        if !path.join(EXPIRES_FILE).is_file() {
+            continue;
+        }
+
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            names.push(name.to_owned());
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Remove artifacts which have expired across all runs, and the directories of
+/// runs which no longer have any artifacts.
+fn prune(root: &Path) -> Result<()> {
+    let Ok(runs) = fs::read_dir(root) else {
+        return Ok(());
+    };
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+
+    for run in runs {
+        let run = run?.path();
+
+        let Ok(artifacts) = fs::read_dir(&run) else {
+            continue;
+        };
+
+        let mut remaining = 0usize;
+
+        for artifact in artifacts {
+            let artifact = artifact?.path();
+
+            let expires = fs::read_to_string(artifact.join(EXPIRES_FILE))
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok());
+
+            match expires {
+                Some(expires) if expires <= now => {
+                    tracing::debug!(path = ?artifact, "Removing expired artifact");
+                    fs::remove_dir_all(&artifact)
+                        .with_context(|| anyhow!("{}", artifact.display()))?;
+                }
+                _ => remaining += 1,
+            }
+        }
+
+        if remaining == 0 {
+            _ = fs::remove_dir(&run);
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively copy the contents of one directory into another.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    let mut queue = vec![(from.to_owned(), to.to_owned())];
+
+    while let Some((from, to)) = queue.pop() {
+        fs::create_dir_all(&to).with_context(|| anyhow!("{}", to.display()))?;
+
+        for e in fs::read_dir(&from).with_context(|| anyhow!("{}", from.display()))? {
+            let e = e?;
+            let target = to.join(e.file_name());
+
+            if e.file_type()?.is_dir() {
+                queue.push((e.path(), target));
+            } else {
+                fs::copy(e.path(), &target).with_context(|| anyhow!("{}", target.display()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use termcolor::NoColor;
+
+use super::*;
+
+/// Write a file with the given contents, creating its parent directories.
+fn write(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+fn patterns(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+/// Get the files in a directory relative to it, sorted and with `/` as the
+/// separator.
+fn relative_files(root: &Path, files: &[PathBuf]) -> Vec<String> {
+    let mut out = files
+        .iter()
+        .map(|f| {
+            f.strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect::<Vec<_>>();
+
+    out.sort();
+    out
+}
+
+/// Upload an artifact from the workspace into the artifacts directory.
+fn upload_artifact(artifacts: &Path, name: &str, workspace: &Path, paths: &[&str]) -> Result<()> {
+    let paths = patterns(paths);
+    let files = find_files(workspace, None, &paths, false)?;
+    let root = search_root(workspace, None, &paths)?;
+    upload(&artifacts.join(name), &root, &files, 1)
+}
+
+#[test]
+fn upload_glob() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ws = dir.path();
+    write(&ws.join("target/debug/app"), "debug")?;
+    write(&ws.join("target/release/app"), "release")?;
+    write(&ws.join("target/release/app.d"), "deps")?;
+    write(&ws.join("target/release/.fingerprint"), "hidden")?;
+    write(&ws.join("src/main.rs"), "main")?;
+
+    let paths = patterns(&["target/*/app"]);
+    let files = find_files(ws, None, &paths, false)?;
+    assert_eq!(
+        relative_files(ws, &files),
+        ["target/debug/app", "target/release/app"]
+    );
+    assert_eq!(search_root(ws, None, &paths)?, ws.join("target"));
+
+    let paths = patterns(&["target/**", "!target/debug/app"]);
+    let files = find_files(ws, None, &paths, false)?;
+    assert_eq!(
+        relative_files(ws, &files),
+        ["target/release/app", "target/release/app.d"]
+    );
+
+    let files = find_files(ws, None, &patterns(&["target/release"]), true)?;
+    assert_eq!(
+        relative_files(ws, &files),
+        [
+            "target/release/.fingerprint",
+            "target/release/app",
+            "target/release/app.d"
+        ]
+    );
+
+    let artifacts = ws.join("artifacts");
+    upload_artifact(&artifacts, "bin", ws, &["target/*/app"])?;
+    assert_eq!(list(&artifacts)?, ["bin"]);
+    assert_eq!(
+        fs::read_to_string(artifacts.join("bin").join(FILES_DIR).join("release/app"))?,
+        "release"
+    );
+    Ok(())
+}
+
+#[test]
+fn upload_search_root() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ws = dir.path();
+    write(&ws.join("a/b/one.txt"), "1")?;
+    write(&ws.join("a/c/two.txt"), "2")?;
+
+    let root = search_root(ws, None, &patterns(&["a/b/one.txt", "a/c/two.txt"]))?;
+    assert_eq!(root, ws.join("a"));
+
+    let root = search_root(ws, None, &patterns(&["a/b/one.txt"]))?;
+    assert_eq!(root, ws.join("a/b"));
+
+    let root = search_root(ws, None, &patterns(&["a/b", "!a/b/one.txt"]))?;
+    assert_eq!(root, ws.join("a/b"));
+    Ok(())
+}
+
+#[test]
+fn if_no_files_found() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let paths = patterns(&["missing/*.txt"]);
+    assert!(find_files(dir.path(), None, &paths, false)?.is_empty());
+
+    let mut o = NoColor::new(Vec::new());
+    no_files_found(&mut o, IfNoFilesFound::Ignore, &paths)?;
+    assert!(o.get_ref().is_empty());
+
+    no_files_found(&mut o, IfNoFilesFound::Warn, &paths)?;
+    let output = String::from_utf8(o.into_inner())?;
+    assert!(output.contains("No files were found with the provided path: missing/*.txt"));
+
+    let mut o = NoColor::new(Vec::new());
+    let e = no_files_found(&mut o, IfNoFilesFound::Error, &paths).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "No files were found with the provided path: missing/*.txt"
+    );
+
+    let with = [
+        (String::from("path"), RString::from("out")),
+        (String::from("if-no-files-found"), RString::from("fail")),
+    ]
+    .into_iter()
+    .collect::<BTreeMap<_, _>>();
+
+    assert!(ScheduleArtifact::new(ArtifactOperation::Upload, None, &with, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn download_pattern() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ws = dir.path().join("ws");
+    let artifacts = dir.path().join("artifacts");
+    write(&ws.join("out.txt"), "out")?;
+
+    for name in ["build-linux", "build-windows", "docs"] {
+        upload_artifact(&artifacts, name, &ws, &["out.txt"])?;
+    }
+
+    // An artifact without the expires file is incomplete and is ignored.
+    write(
+        &artifacts
+            .join("build-partial")
+            .join(FILES_DIR)
+            .join("out.txt"),
+        "",
+    )?;
+
+    let base = dir.path().join("download");
+
+    let all = downloads(&artifacts, &base, None, None, false)?;
+    assert_eq!(
+        all,
+        [
+            (String::from("build-linux"), base.join("build-linux")),
+            (String::from("build-windows"), base.join("build-windows")),
+            (String::from("docs"), base.join("docs")),
+        ]
+    );
+
+    let builds = downloads(&artifacts, &base, None, Some("build-*"), false)?;
+    assert_eq!(
+        builds,
+        [
+            (String::from("build-linux"), base.join("build-linux")),
+            (String::from("build-windows"), base.join("build-windows")),
+        ]
+    );
+
+    let merged = downloads(&artifacts, &base, None, Some("build-*"), true)?;
+    assert_eq!(
+        merged,
+        [
+            (String::from("build-linux"), base.clone()),
+            (String::from("build-windows"), base.clone()),
+        ]
+    );
+
+    let named = downloads(&artifacts, &base, Some("docs"), Some("build-*"), true)?;
+    assert_eq!(named, [(String::from("docs"), base.clone())]);
+
+    let e = downloads(&artifacts, &base, Some("build-partial"), None, false).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "Unable to download artifact: Artifact not found for name: build-partial"
+    );
+
+    assert!(downloads(&dir.path().join("missing"), &base, None, None, false)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn merge_multiple_collisions() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let artifacts = dir.path().join("artifacts");
+
+    for (name, contents) in [("b", "from b"), ("a", "from a"), ("c", "from c")] {
+        let ws = dir.path().join(name);
+        write(&ws.join("dist/shared.txt"), contents)?;
+        write(&ws.join(format!("dist/{name}.txt")), name)?;
+        upload_artifact(&artifacts, name, &ws, &["dist"])?;
+    }
+
+    let base = dir.path().join("download");
+
+    for (name, target) in downloads(&artifacts, &base, None, None, true)? {
+        copy_dir(&artifacts.join(&name).join(FILES_DIR), &target)?;
+    }
+
+    assert_eq!(fs::read_to_string(base.join("shared.txt"))?, "from c");
+
+    for name in ["a", "b", "c"] {
+        assert_eq!(fs::read_to_string(base.join(format!("{name}.txt")))?, name);
+    }
+
+    Ok(())
+}
@@ -1,15 +1,16 @@
 use std::borrow::Cow;
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::env;
 use std::ffi::OsString;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
-use std::path::{Path, PathBuf};
+use std::path::{self, Path, PathBuf};
 use std::rc::Rc;
 use std::str;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result, anyhow, bail, ensure};
+use anyhow::{Context, Result, anyhow, bail};
 use relative_path::{Component, RelativePath};
 use termcolor::{ColorSpec, WriteColor};
 
@@ -18,12 +19,13 @@ use crate::config::Os;
 use crate::once::Once;
 use crate::process::{Command, OsArg};
 use crate::rstr::{RStr, RString};
-use crate::shell::Shell;
-use crate::workflows::{Matrix, Step};
+use crate::shell::{ScriptShell, Shell};
+use crate::workflows::{Container, Matrix, Step, Tree};
 
 use super::{
-    ActionConfig, Env, Run, RunKind, RunOn, Schedule, ScheduleBasicCommand, ScheduleUse, Scheduler,
-    Session, SessionConfig,
+    ActionConfig, Env, ResumeState, Run, RunKind, RunOn, Schedule, ScheduleBasicCommand,
+    ScheduleUse, Scheduler, Session, SessionConfig, StepOutput, StepWindow, cache,
+    parse_key_values,
 };
 
 const WINDOWS_BASH_MESSAGE: &str = r#"Bash is not installed by default on Windows!
@@ -41,6 +43,12 @@ pub(crate) struct Batch {
     os: Os,
     commands: Vec<Schedule>,
     matrix: Option<Matrix>,
+    timeout: Option<Duration>,
+    container: Option<Container>,
+    services: Vec<(String, Container)>,
+    steps: StepWindow,
+    state_file: Option<PathBuf>,
+    resume: Option<ResumeState>,
 }
 
 impl Batch {
@@ -55,9 +63,52 @@ impl Batch {
             os,
             commands,
             matrix,
+            timeout: None,
+            container: None,
+            services: Vec::new(),
+            steps: StepWindow::default(),
+            state_file: None,
+            resume: None,
         }
     }
 
+    /// Modify the maximum amount of time the batch is allowed to run for.
+    pub(super) fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Modify the container options used when running in a container.
+    pub(super) fn with_container(mut self, container: Option<Container>) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// Modify the service containers which run alongside the batch.
+    pub(super) fn with_services(mut self, services: Vec<(String, Container)>) -> Self {
+        self.services = services;
+        self
+    }
+
+    /// Only run the steps of the job which are in the given window.
+    pub(crate) fn with_step_window(mut self, steps: StepWindow) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Persist the state of the job to the given file before each step, so
+    /// that it can be resumed if it fails.
+    pub(crate) fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(path);
+        self
+    }
+
+    /// Resume the job from a previously persisted state.
+    pub(crate) fn with_resume(mut self, state: ResumeState) -> Self {
+        self.resume = Some(state);
+        self
+    }
+
     /// Construct a batch from a single use.
     pub(super) fn with_use(
         batch: &SessionConfig<'_, '_>,
@@ -77,6 +128,12 @@ impl Batch {
             os: batch.cx.os.clone(),
             commands: vec![u],
             matrix: None,
+            timeout: None,
+            container: None,
+            services: Vec::new(),
+            steps: StepWindow::default(),
+            state_file: None,
+            resume: None,
         })
     }
 
@@ -93,20 +150,29 @@ impl Batch {
                 command, args,
             ))],
             matrix: None,
+            timeout: None,
+            container: None,
+            services: Vec::new(),
+            steps: StepWindow::default(),
+            state_file: None,
+            resume: None,
         }
     }
 
     /// Commit a batch.
+    ///
+    /// Returns the outputs produced by the batch, if any.
     pub(crate) fn commit<O>(
         self,
         o: &mut O,
         c: &SessionConfig<'_, '_>,
         session: &mut Session,
-    ) -> Result<()>
+    ) -> Result<BTreeMap<String, RString>>
     where
         O: ?Sized + WriteColor,
     {
         let mut scheduler = Scheduler::new();
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
 
         let scripts_dir = Once::new(|| {
             let dir = c.cx.paths.cache.context("Missing cache directory")?;
@@ -114,13 +180,14 @@ impl Batch {
         });
 
         for (run_on, os) in self.runners(&c.run_on) {
-            match run_on {
+            match &run_on {
                 RunOn::Same => {
                     session.is_same = true;
                 }
                 RunOn::Wsl(dist) => {
-                    session.dists.insert(dist);
+                    session.dists.insert(*dist);
                 }
+                RunOn::Container(..) => {}
             }
 
             write!(o, "# In ")?;
@@ -137,6 +204,14 @@ impl Batch {
                 o.reset()?;
             }
 
+            if let RunOn::Container(image) = &run_on {
+                write!(o, " on ")?;
+
+                o.set_color(&c.colors.title)?;
+                write!(o, "{image} (container)")?;
+                o.reset()?;
+            }
+
             if let Some(matrix) = &self.matrix {
                 write!(o, " ")?;
 
@@ -147,6 +222,67 @@ impl Batch {
 
             writeln!(o)?;
 
+            // NB: Services are reachable through their id if the batch runs
+            // in a container, since they share a network.
+            let network = match &run_on {
+                RunOn::Container(..) if !self.services.is_empty() => {
+                    Some(session.create_network(c)?)
+                }
+                _ => None,
+            };
+
+            let mut services = Vec::with_capacity(self.services.len());
+            let mut tree = Tree::new();
+
+            for (id, service) in &self.services {
+                write!(o, "# Starting service {id} ")?;
+                o.set_color(&c.colors.title)?;
+                write!(o, "{}", service.image)?;
+                o.reset()?;
+                writeln!(o)?;
+
+                let running = session
+                    .start_service(c, id, service, network.as_deref())
+                    .with_context(|| anyhow!("Starting service {id}"))?;
+
+                tree.insert(["job", "services", id, "id"], &*running.name);
+
+                for (port, host) in &running.ports {
+                    tree.insert(["job", "services", id, "ports", port], host);
+                }
+
+                services.push(running);
+            }
+
+            if let Some(state) = &self.resume {
+                state.restore_steps(&mut tree);
+                scheduler.env_mut().extend(state.env.clone());
+                scheduler
+                    .paths_mut()
+                    .extend(state.paths.iter().map(OsString::from));
+            }
+
+            scheduler.set_tree(tree);
+            scheduler.set_step_window(self.steps.clone());
+            scheduler.set_state_file(self.state_file.as_deref());
+
+            // NB: The home directory of jobs which don't run on the host, like
+            // jobs in a container, is not accessible to builtin actions.
+            scheduler.set_home(match &run_on {
+                RunOn::Same => cache::home_dir().ok(),
+                _ => None,
+            });
+
+            let container = match &run_on {
+                RunOn::Container(image) => Some(session.start_container(
+                    c,
+                    image,
+                    self.container.as_ref(),
+                    network.as_deref(),
+                )?),
+                _ => None,
+            };
+
             for run in self.commands.iter() {
                 scheduler.push_back(run.clone());
             }
@@ -186,11 +322,18 @@ impl Batch {
 
                 let mut skipped = run.skipped.as_deref();
 
+                // NB: Docker actions are always run through podman on the host.
+                let run_on = match &run.run {
+                    RunKind::Docker { .. } => &RunOn::Same,
+                    _ => &run_on,
+                };
+
                 match run_on {
                     RunOn::Same => {
                         let skip;
 
-                        (skip, run_command, paths, script_file) = setup_same(c, path, &run)?;
+                        (skip, run_command, paths, script_file) =
+                            setup_same(c, path, &run, env_keys.map(String::as_str))?;
 
                         if skip && skipped.is_none() {
                             skipped = Some("incompatible with distro");
@@ -200,10 +343,8 @@ impl Batch {
                             run_command.env(key, value);
                         }
 
-                        if let Some(script) = &script_file
-                            && let ScriptFileKind::Inline { contents, .. } = &script.kind
-                        {
-                            script_source = Some((Cow::Borrowed(contents.as_ref()), c.shell));
+                        if let RunKind::Shell { script, shell } = &run.run {
+                            script_source = Some((Cow::Borrowed(script.as_ref()), shell.clone()));
                         }
                     }
                     RunOn::Wsl(dist) => {
@@ -215,9 +356,9 @@ impl Batch {
                         let wslenv;
 
                         (command, wslenv, script_file, script_source) =
-                            setup_wsl(&run, env_keys.map(String::as_str));
+                            setup_wsl(&run, env_keys.map(String::as_str))?;
 
-                        run_command = wsl.shell(path, dist);
+                        run_command = wsl.shell(path, *dist);
                         run_command.arg(&command.command);
                         run_command.args(&command.args);
 
@@ -232,6 +373,47 @@ impl Batch {
 
                         display_command = Some(command);
                     }
+                    RunOn::Container(..) => {
+                        let Some(container) = &container else {
+                            bail!("Container has not been started");
+                        };
+
+                        let mut command;
+
+                        (command, script_file, script_source) = setup_container(&run)?;
+
+                        run_command = container.podman().exec(&path::absolute(path)?);
+
+                        let mut seen = HashSet::new();
+
+                        for (key, value) in env {
+                            if seen.insert(key.clone()) {
+                                run_command.args(["--env", &key]);
+                            }
+
+                            run_command.env(&key, value.clone());
+                            command.env(key, value);
+                        }
+
+                        // NB: The container has its own PATH, which the paths
+                        // added by steps are prepended to.
+                        if !scheduler.paths().is_empty() {
+                            let mut path = String::from("PATH=");
+
+                            for p in scheduler.paths() {
+                                path.push_str(&p.to_string_lossy());
+                                path.push(':');
+                            }
+
+                            path.push_str(&container.path);
+                            run_command.args(["--env", &path]);
+                        }
+
+                        run_command.arg(&*container.name);
+                        run_command.arg(&command.command);
+                        run_command.args(&command.args);
+                        display_command = Some(command);
+                    }
                 };
 
                 let mut make_script = None;
@@ -252,9 +434,14 @@ impl Batch {
                                 None => RString::new(),
                             };
 
-                            let script_path = Rc::<Path>::from(
-                                scripts_dir.join(format!("kick-{id}{process_id}-{sequence}.{ext}")),
-                            );
+                            let mut name = format!("kick-{id}{process_id}-{sequence}");
+
+                            if let Some(ext) = ext {
+                                name.push('.');
+                                name.push_str(ext);
+                            }
+
+                            let script_path = Rc::<Path>::from(scripts_dir.join(name));
 
                             make_script = Some((script_path.clone(), contents));
                             script_path
@@ -266,19 +453,25 @@ impl Batch {
                         run_command.env(variable, script_path.clone());
                     }
 
-                    if script_file.argument {
-                        run_command.arg(script_path.clone());
+                    for arg in &script_file.args {
+                        if arg == "{0}" {
+                            run_command.arg(script_path.clone());
+                        } else {
+                            run_command.arg(arg.replace("{0}", &script_path.to_string_lossy()));
+                        }
                     }
                 }
 
                 // Note that we don't want to pass PATH to WSL, it will only
                 // confuse any processes running in there since those paths
                 // points to OS-specified binaries.
-                if !paths.is_empty() || !scheduler.paths().is_empty() {
+                if !matches!(run_on, RunOn::Container(..))
+                    && (!paths.is_empty() || !scheduler.paths().is_empty())
+                {
                     let current_path;
 
                     let current_path = match run_on {
-                        RunOn::Wsl(..) => None,
+                        RunOn::Wsl(..) | RunOn::Container(..) => None,
                         RunOn::Same => {
                             current_path = env::var_os("PATH");
                             current_path.as_ref().map(env::split_paths)
@@ -320,9 +513,9 @@ impl Batch {
                     display_env = &current_command.env;
                     display_env_remove = &current_command.env_remove;
 
-                    if let Some((ref script_source, this_shell)) = script_source {
+                    if let Some((script_source, _)) = &script_source {
                         display = script_source;
-                        shell = this_shell;
+                        shell = c.shell;
                         break 'display;
                     }
 
@@ -359,7 +552,7 @@ impl Batch {
 
                 if c.verbose >= 1 || run.name.is_none() {
                     match shell {
-                        Shell::Bash => {
+                        Shell::Bash | Shell::Sh => {
                             if c.verbose >= 2 {
                                 for (key, value) in display_env {
                                     let key = key.to_string_lossy();
@@ -382,9 +575,9 @@ impl Batch {
 
                             writeln!(o, "{display}")?;
                         }
-                        Shell::Powershell => {
+                        Shell::Powershell | Shell::Pwsh => {
                             if c.verbose >= 2 && !display_env.is_empty() {
-                                writeln!(o, "powershell -Command {{")?;
+                                writeln!(o, "{shell} -Command {{")?;
 
                                 for (key, value) in display_env {
                                     let key = key.to_string_lossy();
@@ -415,6 +608,29 @@ impl Batch {
                                 writeln!(o, "{display}")?;
                             }
                         }
+                        Shell::Cmd => {
+                            if c.verbose >= 2 {
+                                for (key, value) in display_env {
+                                    let key = key.to_string_lossy();
+
+                                    let value = if c.exposed {
+                                        value.to_exposed_lossy()
+                                    } else {
+                                        value.to_string_lossy()
+                                    };
+
+                                    let value = value.replace('%', "%%");
+                                    writeln!(o, "set \"{key}={value}\"")?;
+                                }
+
+                                for key in display_env_remove {
+                                    let key = key.to_string_lossy();
+                                    writeln!(o, "set \"{key}=\"")?;
+                                }
+                            }
+
+                            writeln!(o, "{display}")?;
+                        }
                     }
 
                     if c.verbose >= 2
@@ -427,6 +643,12 @@ impl Batch {
                     }
                 }
 
+                if skipped.is_some()
+                    && let Some(id) = &run.id
+                {
+                    scheduler.insert_step_result(&id.to_exposed(), "skipped", "skipped")?;
+                }
+
                 if skipped.is_none() && !c.dry_run {
                     truncate(run.files())?;
 
@@ -445,13 +667,95 @@ impl Batch {
                         session.remove_path(&p);
                     }
 
-                    let status = run_command.status()?;
+                    let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+
+                    let timeout = match (run.timeout, remaining) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+
+                    let mut output = StepOutput::new(
+                        scheduler
+                            .name(" / ", run.name.as_deref())
+                            .map(|name| name.to_string()),
+                    );
+
+                    let status = run_command
+                        .status_with_lines(timeout, |line| output.line(o, c, session, line))?;
+
+                    // NB: Summaries are collected even if the step failed.
+                    if let Some(summary_file) = &run.summary_file
+                        && let Ok(contents) = fs::read(summary_file)
+                        && !contents.is_empty()
+                    {
+                        session.push_summary(String::from_utf8_lossy(&contents).into_owned());
+                    }
+
+                    let error = match status {
+                        Some(status) if status.success() => None,
+                        Some(status) => Some(status.to_string()),
+                        None => {
+                            if let Some(container) = &container
+                                && let Err(error) = container.kill_processes()
+                            {
+                                tracing::warn!(
+                                    ?error,
+                                    "Failed to kill processes in container {}",
+                                    container.name
+                                );
+                            }
+
+                            if let (Some(timeout), Some(deadline)) = (self.timeout, deadline)
+                                && Instant::now() >= deadline
+                            {
+                                bail!("Job timed out after {}", display_duration(timeout));
+                            }
+
+                            let timeout = timeout.unwrap_or_default();
+                            Some(format!("timed out after {}", display_duration(timeout)))
+                        }
+                    };
+
+                    let outcome = if error.is_some() {
+                        "failure"
+                    } else {
+                        "success"
+                    };
+
+                    let conclusion = if error.is_none() || run.continue_on_error {
+                        "success"
+                    } else {
+                        "failure"
+                    };
+
+                    if let Some(id) = &run.id {
+                        scheduler.insert_step_result(&id.to_exposed(), outcome, conclusion)?;
+                    }
+
+                    if let Some(error) = error {
+                        if !run.continue_on_error {
+                            if self.state_file.is_some() {
+                                writeln!(
+                                    o,
+                                    "# Failed at step #{}, use `--resume` to continue from it",
+                                    scheduler.step_index()
+                                )?;
+                            }
+
+                            bail!("Step failed: {error}");
+                        }
 
-                    ensure!(status.success(), status);
+                        o.set_color(&c.colors.warn)?;
+                        writeln!(
+                            o,
+                            "# Step failed ({error}), continuing since continue-on-error is set"
+                        )?;
+                        o.reset()?;
+                    }
 
                     let mut new_env = Vec::new();
                     let mut new_paths = Vec::new();
-                    let mut new_outputs = Vec::new();
+                    let mut new_outputs = output.into_outputs();
 
                     if let Some(env_file) = &run.env_file
                         && let Ok(contents) = fs::read(env_file)
@@ -474,7 +778,7 @@ impl Batch {
                     if let Some(output_file) = &run.output_file
                         && let Ok(contents) = fs::read(output_file)
                     {
-                        new_outputs = parse_key_values(&contents)?;
+                        new_outputs.extend(parse_key_values(&contents)?);
                     }
 
                     tracing::debug!(?new_env, ?new_paths, ?new_outputs);
@@ -501,19 +805,44 @@ impl Batch {
                     purge_dirs(run.purge_dirs())?;
                 }
             }
+
+            scheduler.finish_steps()?;
+
+            if let Some(container) = &container {
+                session.remove_container(container)?;
+            }
+
+            for service in &services {
+                session.remove_container(service)?;
+            }
+
+            if let Some(network) = &network {
+                session.remove_network(network)?;
+            }
         }
 
-        Ok(())
+        Ok(scheduler.outputs().clone())
     }
 
     fn runners(&self, opts: &[(RunOn, Os)]) -> BTreeSet<(RunOn, Os)> {
         let mut set = BTreeSet::new();
         set.extend(opts.iter().cloned());
-        set.insert((self.run_on, self.os.clone()));
+        set.insert((self.run_on.clone(), self.os.clone()));
         set
     }
 }
 
+/// Display a duration in minutes or seconds, as appropriate.
+fn display_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+
+    if secs >= 60.0 {
+        format!("{} minutes", secs / 60.0)
+    } else {
+        format!("{secs} seconds")
+    }
+}
+
 /// Truncate the given collection of files and ensure they exist.
 fn truncate<I>(paths: I) -> Result<()>
 where
@@ -567,21 +896,6 @@ where
     Ok(())
 }
 
-fn parse_key_values(contents: &[u8]) -> Result<Vec<(String, String)>> {
-    process_lines(contents, |line| {
-        let (key, value) = line.split_once('=')?;
-
-        let key = key.trim();
-        let value = value.trim();
-
-        if key.is_empty() || value.is_empty() {
-            return None;
-        }
-
-        Some((key.to_owned(), value.to_owned()))
-    })
-}
-
 fn parse_lines(contents: &[u8]) -> Result<Vec<String>> {
     process_lines(contents, |line| {
         let line = line.trim();
@@ -625,7 +939,7 @@ where
 enum ScriptFileKind {
     Inline {
         contents: Box<RStr>,
-        ext: &'static str,
+        ext: Option<&'static str>,
     },
     Existing {
         path: Rc<Path>,
@@ -635,33 +949,65 @@ enum ScriptFileKind {
 #[derive(Debug)]
 struct ScriptFile {
     variable: Option<&'static str>,
-    argument: bool,
+    /// Arguments to pass to the command, where `{0}` is replaced with the path
+    /// to the script file.
+    args: Vec<String>,
     kind: ScriptFileKind,
 }
 
 impl ScriptFile {
     fn inline(
         variable: Option<&'static str>,
-        argument: bool,
+        args: Vec<String>,
         contents: Box<RStr>,
-        ext: &'static str,
+        ext: Option<&'static str>,
     ) -> Self {
         Self {
             variable,
-            argument,
+            args,
             kind: ScriptFileKind::Inline { contents, ext },
         }
     }
 
-    fn path(variable: Option<&'static str>, argument: bool, path: Rc<Path>) -> Self {
+    fn path(variable: Option<&'static str>, args: Vec<String>, path: Rc<Path>) -> Self {
         Self {
             variable,
-            argument,
+            args,
             kind: ScriptFileKind::Existing { path },
         }
     }
 }
 
+/// Split the command used to run a script into the program and its
+/// arguments.
+fn shell_command(shell: &ScriptShell) -> Result<(String, Vec<String>)> {
+    let mut args = shell.template()?.into_iter();
+
+    let Some(program) = args.next() else {
+        bail!("Missing command for shell `{shell}`");
+    };
+
+    Ok((program, args.collect()))
+}
+
+/// Format an argument for a command run through the WSL login shell, where
+/// `{0}` refers to the given variable.
+fn wsl_script_arg(arg: &str, variable: &str) -> String {
+    let mut out = String::new();
+
+    for (n, part) in arg.split("{0}").enumerate() {
+        if n > 0 {
+            out.push_str(&format!("\"${variable}\""));
+        }
+
+        if !part.is_empty() {
+            out.push_str(&Shell::Sh.escape(part));
+        }
+    }
+
+    out
+}
+
 fn as_same_dist_specific(c: &SessionConfig<'_, '_>, command: &str) -> Option<Vec<Distribution>> {
     if c.cx.os != Os::Linux {
         return None;
@@ -685,10 +1031,11 @@ fn as_same_dist_specific(c: &SessionConfig<'_, '_>, command: &str) -> Option<Vec
     }
 }
 
-fn setup_same<'a>(
+fn setup_same<'a, 'env>(
     c: &SessionConfig<'_, 'a>,
     path: &Path,
     run: &Run,
+    env: impl IntoIterator<Item = &'env str>,
 ) -> Result<(bool, Command, &'a [PathBuf], Option<ScriptFile>)> {
     let mut skip = false;
 
@@ -698,18 +1045,10 @@ fn setup_same<'a>(
                 skip = !dist.contains(&c.cx.dist);
             }
 
-            match shell {
-                Shell::Powershell => {
-                    let Some(powershell) = c.cx.system.powershell.first() else {
-                        bail!("PowerShell not available");
-                    };
+            let (program, args) = shell_command(shell)?;
 
-                    let mut c = powershell.command_in(path);
-                    c.arg("-Command");
-                    c.arg(script);
-                    Ok((skip, c, &[], None))
-                }
-                Shell::Bash => {
+            let (mut command, paths) = match program.as_str() {
+                "bash" => {
                     let Some(bash) = c.cx.system.bash.first() else {
                         if let Os::Windows = &c.cx.os {
                             tracing::warn!("{WINDOWS_BASH_MESSAGE}");
@@ -718,12 +1057,31 @@ fn setup_same<'a>(
                         bail!("Bash is not available");
                     };
 
-                    let mut c = bash.command_in(path);
-                    c.args(["-i"]);
-                    let script_file = ScriptFile::inline(None, true, script.clone(), "bash");
-                    Ok((skip, c, &bash.paths, Some(script_file)))
+                    (bash.command_in(path), &bash.paths[..])
                 }
+                "powershell" => {
+                    let Some(powershell) = c.cx.system.powershell.first() else {
+                        bail!("PowerShell not available");
+                    };
+
+                    (powershell.command_in(path), &[][..])
+                }
+                _ => {
+                    let mut command = Command::new(program);
+                    command.current_dir(path);
+                    (command, &[][..])
+                }
+            };
+
+            // NB: The default shell is interactive so that local profiles are
+            // loaded.
+            if *shell == ScriptShell::Default {
+                command.arg("-i");
             }
+
+            let script_file =
+                ScriptFile::inline(None, args, shell.contents(script), shell.extension());
+            Ok((skip, command, paths, Some(script_file)))
         }
         RunKind::Command { command, args } => {
             let mut c = Command::new(command);
@@ -741,18 +1099,49 @@ fn setup_same<'a>(
             c.current_dir(path);
             Ok((skip, c, &[], None))
         }
+        RunKind::Docker {
+            image,
+            entrypoint,
+            args,
+        } => {
+            let Some(podman) = c.cx.system.podman.first() else {
+                bail!("Podman is not available");
+            };
+
+            let cache_dir = c.cx.paths.cache.context("Missing cache directory")?;
+            let repo_dir = path::absolute(&c.path)?;
+
+            let mut command = podman.run([repo_dir.as_path(), cache_dir]);
+            command.arg("--workdir").arg(path::absolute(path)?);
+
+            let mut seen = HashSet::new();
+
+            for key in env {
+                if seen.insert(key) {
+                    command.args(["--env", key]);
+                }
+            }
+
+            if let Some(entrypoint) = entrypoint {
+                command.arg("--entrypoint").arg(entrypoint);
+            }
+
+            command.arg(image);
+            command.args(args.as_ref());
+            Ok((skip, command, &[], None))
+        }
     }
 }
 
 fn setup_wsl<'run, 'a>(
     run: &'run Run,
     env: impl IntoIterator<Item = &'a str>,
-) -> (
+) -> Result<(
     Command,
     String,
     Option<ScriptFile>,
-    Option<(Cow<'run, RStr>, Shell)>,
-) {
+    Option<(Cow<'run, RStr>, ScriptShell)>,
+)> {
     let mut seen = HashSet::new();
     let mut wslenv = String::new();
     let mut script_file = None;
@@ -761,26 +1150,26 @@ fn setup_wsl<'run, 'a>(
     let mut c;
 
     match &run.run {
-        RunKind::Shell { script, shell } => match shell {
-            Shell::Powershell => {
-                c = Command::new("powershell");
-                c.args(["-Command"]);
-                c.arg(script);
+        RunKind::Shell { script, shell } => {
+            let (program, args) = shell_command(shell)?;
+            c = Command::new(program);
 
-                script_source = Some((Cow::Borrowed(script.as_ref()), Shell::Powershell));
+            if *shell == ScriptShell::Default {
+                c.arg("-i");
             }
-            Shell::Bash => {
-                c = Command::new("bash");
-                c.args(["-i", "$KICK_SCRIPT_FILE"]);
-                script_file = Some(ScriptFile::inline(
-                    Some("KICK_SCRIPT_FILE"),
-                    false,
-                    script.clone(),
-                    "bash",
-                ));
-                script_source = Some((Cow::Borrowed(script.as_ref()), Shell::Bash));
+
+            for arg in args {
+                c.arg(wsl_script_arg(&arg, "KICK_SCRIPT_FILE"));
             }
-        },
+
+            script_file = Some(ScriptFile::inline(
+                Some("KICK_SCRIPT_FILE"),
+                Vec::new(),
+                shell.contents(script),
+                shell.extension(),
+            ));
+            script_source = Some((Cow::Borrowed(script.as_ref()), shell.clone()));
+        }
         RunKind::Command { command, args } => {
             c = Command::new(command);
             c.args(args.as_ref());
@@ -793,12 +1182,15 @@ fn setup_wsl<'run, 'a>(
             c.args(["-i", "-c", "exec node $KICK_SCRIPT_FILE"]);
             script_file = Some(ScriptFile::path(
                 Some("KICK_SCRIPT_FILE"),
-                false,
+                Vec::new(),
                 node_script_file.clone(),
             ));
 
             let source = RString::from("exec node $KICK_SCRIPT_FILE".to_owned());
-            script_source = Some((Cow::Owned(source), Shell::Bash));
+            script_source = Some((Cow::Owned(source), ScriptShell::Default));
+        }
+        RunKind::Docker { .. } => {
+            bail!("Docker actions can only be run on the host");
         }
     }
 
@@ -831,7 +1223,48 @@ fn setup_wsl<'run, 'a>(
         wslenv.push_str("/p");
     }
 
-    (c, wslenv, script_file, script_source)
+    Ok((c, wslenv, script_file, script_source))
+}
+
+#[allow(clippy::type_complexity)]
+fn setup_container(
+    run: &Run,
+) -> Result<(
+    Command,
+    Option<ScriptFile>,
+    Option<(Cow<'_, RStr>, ScriptShell)>,
+)> {
+    let mut script_file = None;
+    let mut script_source = None;
+
+    let mut c;
+
+    match &run.run {
+        RunKind::Shell { script, shell } => {
+            let (program, args) = shell_command(shell)?;
+            c = Command::new(program);
+            script_file = Some(ScriptFile::inline(
+                None,
+                args,
+                shell.contents(script),
+                shell.extension(),
+            ));
+            script_source = Some((Cow::Borrowed(script.as_ref()), shell.clone()));
+        }
+        RunKind::Command { command, args } => {
+            c = Command::new(command);
+            c.args(args.as_ref());
+        }
+        RunKind::Node { script_file, .. } => {
+            c = Command::new("node");
+            c.arg(script_file);
+        }
+        RunKind::Docker { .. } => {
+            bail!("Docker actions can only be run on the host");
+        }
+    }
+
+    Ok((c, script_file, script_source))
 }
 
 fn translate_path_to_windows(path: &str) -> Result<String> {
@@ -1,4 +1,7 @@
-use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow, bail};
 use clap::{Parser, ValueEnum};
 
 use crate::config::{Distribution, Os};
@@ -6,7 +9,7 @@ use crate::ctxt::Ctxt;
 use crate::model::Repo;
 use crate::shell::Shell;
 
-use super::{RunOn, SessionConfig};
+use super::{RunOn, SessionConfig, secrets};
 
 #[derive(Default, Debug, Parser)]
 pub(crate) struct BatchOptions {
@@ -56,6 +59,50 @@ pub(crate) struct BatchOptions {
     /// conditions specified.
     #[arg(long)]
     pub(super) matrix_filter: Vec<String>,
+    /// The event to simulate when running Github workflows, like `push`,
+    /// `pull_request` or `workflow_dispatch`.
+    ///
+    /// When specified, workflows whose `on` block would not have been triggered
+    /// by the event are skipped.
+    #[arg(long)]
+    pub(super) event: Option<String>,
+    /// A JSON file containing the payload of the simulated event.
+    #[arg(long, value_name = "PATH")]
+    pub(super) event_payload: Option<PathBuf>,
+    /// Inputs to pass to a `workflow_dispatch` event in key=value form.
+    ///
+    /// Specifying inputs without an `--event` implies `workflow_dispatch`.
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub(super) input: Vec<String>,
+    /// Secrets to make available in the `secrets` context in NAME=VALUE form.
+    ///
+    /// Only specifying NAME means that the value is taken from the environment
+    /// variable with the same name.
+    ///
+    /// Secrets are also read from files in the `secrets` directory of the kick
+    /// configuration directory, and from environment variables prefixed with
+    /// `--secret-env-prefix`.
+    #[arg(long, value_name = "NAME[=VALUE]")]
+    pub(super) secret: Vec<String>,
+    /// The prefix of environment variables which provide secrets.
+    ///
+    /// This defaults to KICK_SECRET_, so that the `KICK_SECRET_TOKEN`
+    /// environment variable provides `secrets.TOKEN`.
+    #[arg(long, value_name = "PREFIX")]
+    pub(super) secret_env_prefix: Option<String>,
+    /// Variables to make available in the `vars` context in KEY=VALUE form.
+    ///
+    /// These override any variables configured in the `[run.vars]` section of
+    /// Kick.toml.
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub(super) var: Vec<String>,
+    /// The maximum total size in megabytes of entries stored by the builtin
+    /// `actions/cache`, after which the least recently used entries are
+    /// removed.
+    ///
+    /// This defaults to 10240, or 10 GB.
+    #[arg(long, value_name = "MB")]
+    pub(super) cache_limit: Option<u64>,
 }
 
 impl BatchOptions {
@@ -70,6 +117,8 @@ impl BatchOptions {
         let shell = self.shell.unwrap_or_else(|| cx.os.shell());
 
         let mut c = SessionConfig::new(cx, repo_path, shell);
+        c.runners = cx.config.runners(repo);
+        c.vars = cx.config.run_vars(repo);
 
         for &run_on in &self.run_on {
             c.add_run_on(run_on.to_run_on(), run_on.to_os(&cx.os))?;
@@ -107,6 +156,56 @@ impl BatchOptions {
             c.keep = true;
         }
 
+        if let Some(path) = &self.event_payload {
+            let payload = fs::read(path).with_context(|| anyhow!("{}", path.display()))?;
+
+            c.event_payload = serde_json::from_slice(&payload)
+                .with_context(|| anyhow!("{}: Expected a JSON object", path.display()))?;
+        }
+
+        for input in &self.input {
+            let Some((key, value)) = input.split_once('=') else {
+                bail!("Input must be in the form key=value: {input}");
+            };
+
+            c.inputs.insert(key.to_owned(), value.to_owned());
+        }
+
+        if let Some(config) = cx.paths.config {
+            c.secrets.load_dir(config)?;
+        }
+
+        let prefix = self
+            .secret_env_prefix
+            .as_deref()
+            .unwrap_or(secrets::DEFAULT_ENV_PREFIX);
+
+        c.secrets.load_env(prefix)?;
+
+        for secret in &self.secret {
+            c.secrets.parse(secret)?;
+        }
+
+        for var in &self.var {
+            let Some((key, value)) = var.split_once('=') else {
+                bail!("Variable must be in the form key=value: {var}");
+            };
+
+            c.vars.insert(key.to_owned(), value.to_owned());
+        }
+
+        if let Some(limit) = self.cache_limit {
+            c.cache_limit = limit.saturating_mul(1024 * 1024);
+        }
+
+        c.event = self.event.clone();
+
+        if c.event.is_none() && !c.inputs.is_empty() {
+            c.event = Some(String::from("workflow_dispatch"));
+        }
+
+        c.load_github_context(repo);
+        c.prepare_event()?;
         Ok(c)
     }
 }
@@ -0,0 +1,604 @@
+#[cfg(test)]
+mod tests;
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io;
+use std::path::{self, Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, anyhow, bail};
+use relative_path::RelativePath;
+use sha2::{Digest, Sha256};
+use termcolor::WriteColor;
+
+use crate::glob::Glob;
+use crate::rstr::{RStr, RString};
+
+use super::SessionConfig;
+
+/// The directory in the kick cache where entries of `actions/cache` are
+/// stored.
+const CACHE_DIR: &str = "actions-cache";
+
+/// The default limit of the total size of all cache entries.
+pub(super) const DEFAULT_CACHE_LIMIT: u64 = 10 * 1024 * 1024 * 1024;
+
+/// The file in an entry which contains its key.
+const KEY_FILE: &str = "key";
+
+/// The file in an entry which contains the archived paths. Its modification
+/// time is used to determine when the entry was last used.
+const DATA_FILE: &str = "data.tar";
+
+/// Archive prefixes used for paths depending on where they are located.
+const WORKSPACE: &str = "workspace";
+const HOME: &str = "home";
+const ROOT: &str = "root";
+
+/// The operation performed by a cache step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum CacheOperation {
+    Restore,
+    Save,
+}
+
+impl CacheOperation {
+    /// Get the cache operations performed by the given action, which is either
+    /// `cache`, `cache/restore` or `cache/save` in the `actions` organization.
+    pub(super) fn from_action(repo: &RStr) -> Option<&'static [Self]> {
+        if repo.str_eq("cache") {
+            return Some(&[Self::Restore, Self::Save]);
+        }
+
+        if repo.str_eq("cache/restore") {
+            return Some(&[Self::Restore]);
+        }
+
+        if repo.str_eq("cache/save") {
+            return Some(&[Self::Save]);
+        }
+
+        None
+    }
+}
+
+/// A builtin emulation of `actions/cache`, which stores entries in the kick
+/// cache directory.
+///
+/// When emulating `actions/cache`, restoring happens when the step runs while
+/// saving is deferred until the end of the job like the post step of the real
+/// action.
+#[derive(Clone)]
+pub(crate) struct ScheduleCache {
+    operation: CacheOperation,
+    id: Option<Rc<RStr>>,
+    key: String,
+    restore_keys: Vec<String>,
+    paths: Vec<String>,
+    fail_on_cache_miss: bool,
+    lookup_only: bool,
+    skipped: Option<String>,
+    /// Set if the restore found an exact match for the key, in which case
+    /// saving is skipped.
+    hit: Rc<Cell<bool>>,
+}
+
+impl ScheduleCache {
+    /// Construct a new cache operation from the inputs of a step.
+    pub(super) fn new(
+        operation: CacheOperation,
+        id: Option<&Rc<RStr>>,
+        with: &BTreeMap<String, RString>,
+        skipped: Option<&str>,
+        hit: Rc<Cell<bool>>,
+    ) -> Result<Self> {
+        let get = |key: &str| with.get(key).map(|v| v.to_exposed().into_owned());
+
+        let Some(key) = get("key") else {
+            bail!("Missing required input `key`");
+        };
+
+        let Some(path) = get("path") else {
+            bail!("Missing required input `path`");
+        };
+
+        let paths = lines(&path);
+
+        let restore_keys = get("restore-keys")
+            .as_deref()
+            .map(lines)
+            .unwrap_or_default();
+
+        let flag = |key: &str| get(key).is_some_and(|v| v.trim() == "true");
+
+        Ok(Self {
+            operation,
+            id: id.cloned(),
+            key,
+            restore_keys,
+            paths,
+            fail_on_cache_miss: flag("fail-on-cache-miss"),
+            lookup_only: flag("lookup-only"),
+            skipped: skipped.map(str::to_owned),
+            hit,
+        })
+    }
+
+    /// The id of the step, under which outputs are stored.
+    pub(super) fn id(&self) -> Option<&RStr> {
+        self.id.as_deref()
+    }
+
+    /// The name of the operation.
+    pub(super) fn name(&self) -> &'static RStr {
+        match self.operation {
+            CacheOperation::Restore => RStr::new("restore cache (builtin)"),
+            CacheOperation::Save => RStr::new("save cache (builtin)"),
+        }
+    }
+
+    /// Perform the cache operation, returning the outputs it produced.
+    ///
+    /// Paths prefixed with `~/` are resolved against `home`, see
+    /// [`resolve_path`].
+    pub(super) fn run<O>(
+        &self,
+        o: &mut O,
+        c: &SessionConfig<'_, '_>,
+        name: Option<&RStr>,
+        home: Option<&Path>,
+    ) -> Result<Vec<(String, String)>>
+    where
+        O: ?Sized + WriteColor,
+    {
+        write_header(o, c, name, self.skipped.as_deref())?;
+
+        if self.skipped.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let cache_dir = c.cx.paths.cache.context("Missing cache directory")?;
+        let dir = cache_dir.join(CACHE_DIR);
+        let workspace = path::absolute(&c.path)?;
+
+        match self.operation {
+            CacheOperation::Restore => self.restore(o, c, &dir, &workspace, home),
+            CacheOperation::Save => {
+                self.save(o, c, &dir, &workspace, home)?;
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    fn restore<O>(
+        &self,
+        o: &mut O,
+        c: &SessionConfig<'_, '_>,
+        dir: &Path,
+        workspace: &Path,
+        home: Option<&Path>,
+    ) -> Result<Vec<(String, String)>>
+    where
+        O: ?Sized + WriteColor,
+    {
+        let entry = find_entry(dir, &self.key, &self.restore_keys)?;
+
+        let mut outputs = vec![(String::from("cache-primary-key"), self.key.clone())];
+
+        let Some(entry) = entry else {
+            if self.fail_on_cache_miss {
+                bail!(
+                    "Failed to restore cache entry, since fail-on-cache-miss is set. Input key: {}",
+                    self.key
+                );
+            }
+
+            let mut keys = vec![self.key.as_str()];
+            keys.extend(self.restore_keys.iter().map(String::as_str));
+            writeln!(o, "Cache not found for input keys: {}", keys.join(", "))?;
+            outputs.push((String::from("cache-hit"), String::new()));
+            return Ok(outputs);
+        };
+
+        let exact = entry.key == self.key;
+        self.hit.set(exact);
+
+        if self.lookup_only {
+            writeln!(o, "Cache found and can be restored from key: {}", entry.key)?;
+        } else if c.dry_run {
+            writeln!(o, "Would restore cache from key: {}", entry.key)?;
+        } else {
+            unpack(&entry.path.join(DATA_FILE), workspace, home)
+                .with_context(|| anyhow!("Restoring cache from key: {}", entry.key))?;
+            touch(&entry.path.join(DATA_FILE))?;
+            writeln!(o, "Cache restored from key: {}", entry.key)?;
+        }
+
+        outputs.push((String::from("cache-hit"), exact.to_string()));
+        outputs.push((String::from("cache-matched-key"), entry.key));
+        Ok(outputs)
+    }
+
+    fn save<O>(
+        &self,
+        o: &mut O,
+        c: &SessionConfig<'_, '_>,
+        dir: &Path,
+        workspace: &Path,
+        home: Option<&Path>,
+    ) -> Result<()>
+    where
+        O: ?Sized + WriteColor,
+    {
+        if self.hit.get() {
+            writeln!(
+                o,
+                "Cache hit occurred on the primary key {}, not saving cache.",
+                self.key
+            )?;
+            return Ok(());
+        }
+
+        let entry = dir.join(entry_name(&self.key));
+
+        if entry.join(DATA_FILE).is_file() {
+            writeln!(
+                o,
+                "Cache already exists for key {}, not saving cache.",
+                self.key
+            )?;
+            return Ok(());
+        }
+
+        let paths = resolve_paths(workspace, home, &self.paths)?;
+
+        if paths.is_empty() {
+            writeln!(o, "No paths to cache were found, not saving cache.")?;
+            return Ok(());
+        }
+
+        if c.dry_run {
+            writeln!(o, "Would save cache with key: {}", self.key)?;
+            return Ok(());
+        }
+
+        fs::create_dir_all(&entry).with_context(|| anyhow!("{}", entry.display()))?;
+
+        let data = entry.join(DATA_FILE);
+        let temp = entry.join(format!("{DATA_FILE}.{}", c.process_id));
+
+        let result = pack(&temp, workspace, home, &paths)
+            .and_then(|()| fs::write(entry.join(KEY_FILE), &self.key).map_err(Into::into))
+            .and_then(|()| fs::rename(&temp, &data).map_err(Into::into));
+
+        if let Err(error) = result {
+            _ = fs::remove_file(&temp);
+            return Err(error.context(anyhow!("Saving cache with key: {}", self.key)));
+        }
+
+        writeln!(o, "Cache saved with key: {}", self.key)?;
+        evict(dir, c.cache_limit)?;
+        Ok(())
+    }
+}
+
+/// Write the header of a builtin step which is run by kick itself.
+pub(super) fn write_header<O>(
+    o: &mut O,
+    c: &SessionConfig<'_, '_>,
+    name: Option<&RStr>,
+    skipped: Option<&str>,
+) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    let Some(name) = name else {
+        return Ok(());
+    };
+
+    write!(o, "# ")?;
+    o.set_color(&c.colors.title)?;
+    write!(o, "{name}")?;
+    o.reset()?;
+
+    if let Some(skipped) = skipped {
+        write!(o, " ")?;
+        o.set_color(&c.colors.skip_cond)?;
+        write!(o, "(skipped: {skipped})")?;
+        o.reset()?;
+    }
+
+    writeln!(o)?;
+    Ok(())
+}
+
+/// An entry in the cache.
+struct Entry {
+    path: PathBuf,
+    key: String,
+    size: u64,
+    used: SystemTime,
+}
+
+/// Find the entry which matches the given key, or the most recently used
+/// entry which matches the first restore key that has any matches.
+fn find_entry(dir: &Path, key: &str, restore_keys: &[String]) -> Result<Option<Entry>> {
+    let mut entries = entries(dir)?;
+
+    if let Some(index) = entries.iter().position(|e| e.key == key) {
+        return Ok(Some(entries.swap_remove(index)));
+    }
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.used));
+
+    for prefix in restore_keys {
+        if let Some(index) = entries.iter().position(|e| e.key.starts_with(prefix)) {
+            return Ok(Some(entries.swap_remove(index)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// List all complete entries in the cache.
+fn entries(dir: &Path) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(e).with_context(|| anyhow!("{}", dir.display())),
+    };
+
+    for e in read_dir {
+        let path = e.with_context(|| anyhow!("{}", dir.display()))?.path();
+
+        let (Ok(key), Ok(m)) = (
+            fs::read_to_string(path.join(KEY_FILE)),
+            fs::metadata(path.join(DATA_FILE)),
+        ) else {
+            continue;
+        };
+
+        entries.push(Entry {
+            path,
+            key,
+            size: m.len(),
+            used: m.modified()?,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Remove the least recently used entries until the total size of the cache
+/// is within the given limit.
+fn evict(dir: &Path, limit: u64) -> Result<()> {
+    let mut entries = entries(dir)?;
+    entries.sort_by_key(|e| e.used);
+
+    let mut total = entries.iter().map(|e| e.size).sum::<u64>();
+
+    for e in entries {
+        if total <= limit {
+            break;
+        }
+
+        tracing::debug!(key = e.key, size = e.size, "Evicting cache entry");
+        fs::remove_dir_all(&e.path).with_context(|| anyhow!("{}", e.path.display()))?;
+        total = total.saturating_sub(e.size);
+    }
+
+    Ok(())
+}
+
+/// Mark an entry as used.
+fn touch(path: &Path) -> Result<()> {
+    let f = File::options()
+        .write(true)
+        .open(path)
+        .with_context(|| anyhow!("{}", path.display()))?;
+    f.set_modified(SystemTime::now())?;
+    Ok(())
+}
+
+/// Get the name of the directory of the entry with the given key.
+fn entry_name(key: &str) -> String {
+    let mut name = String::with_capacity(32);
+
+    for b in &Sha256::digest(key.as_bytes())[..16] {
+        _ = write!(name, "{b:02x}");
+    }
+
+    name
+}
+
+/// Resolve the paths to cache, which are either relative to the workspace,
+/// relative to the home directory if prefixed with `~/`, or absolute.
+///
+/// Paths which are relative to the workspace may be glob patterns, and patterns
+/// prefixed with `!` exclude paths matched by earlier patterns.
+pub(super) fn resolve_paths(
+    workspace: &Path,
+    home: Option<&Path>,
+    patterns: &[String],
+) -> Result<BTreeSet<PathBuf>> {
+    let mut paths = BTreeSet::new();
+
+    for pattern in patterns {
+        let (negate, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern.trim()),
+            None => (false, pattern.as_str()),
+        };
+
+        let mut matched = Vec::new();
+
+        if is_glob(pattern) && !pattern.starts_with("~/") && !Path::new(pattern).is_absolute() {
+            let glob = Glob::new(workspace, pattern);
+
+            for path in glob.matcher() {
+                matched.push(path?.to_path(workspace));
+            }
+        } else {
+            matched.push(resolve_path(workspace, home, pattern)?);
+        }
+
+        for path in matched {
+            if negate {
+                paths.remove(&path);
+            } else if fs::symlink_metadata(&path).is_ok() {
+                paths.insert(path);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Resolve a single path which is either relative to the workspace, relative to
+/// the home directory if prefixed with `~/`, or absolute.
+///
+/// The `home` directory is `None` for jobs which don't run on the host, like
+/// jobs running in a container, since their home directory is not accessible
+/// from the host.
+pub(super) fn resolve_path(workspace: &Path, home: Option<&Path>, path: &str) -> Result<PathBuf> {
+    if let Some(rest) = path.strip_prefix("~/") {
+        let Some(home) = home else {
+            bail!(
+                "Paths relative to the home directory like `{path}` are only supported for jobs which run on the host"
+            );
+        };
+
+        return Ok(home.join(rest));
+    }
+
+    if Path::new(path).is_absolute() {
+        return Ok(PathBuf::from(path));
+    }
+
+    Ok(RelativePath::new(path).to_logical_path(workspace))
+}
+
+/// Test if the given path is a glob pattern.
+pub(super) fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Archive the given paths into a tarball.
+fn pack(
+    output: &Path,
+    workspace: &Path,
+    home: Option<&Path>,
+    paths: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    let f = File::create(output).with_context(|| anyhow!("{}", output.display()))?;
+    let mut builder = tar::Builder::new(io::BufWriter::new(f));
+    builder.follow_symlinks(false);
+
+    for path in paths {
+        let name = archive_name(workspace, home, path);
+
+        if path.is_dir() {
+            builder
+                .append_dir_all(&name, path)
+                .with_context(|| anyhow!("{}", path.display()))?;
+        } else {
+            builder
+                .append_path_with_name(path, &name)
+                .with_context(|| anyhow!("{}", path.display()))?;
+        }
+    }
+
+    builder.into_inner()?.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+/// Unpack an archive, restoring paths to where they were archived from.
+fn unpack(input: &Path, workspace: &Path, home: Option<&Path>) -> Result<()> {
+    let f = File::open(input).with_context(|| anyhow!("{}", input.display()))?;
+    let mut archive = tar::Archive::new(io::BufReader::new(f));
+    archive.set_preserve_mtime(true);
+    archive.set_preserve_permissions(true);
+    archive.set_overwrite(true);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+
+        let Some(path) = restore_path(workspace, home, &name) else {
+            bail!("Unsupported path in cache archive: {}", name.display());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| anyhow!("{}", parent.display()))?;
+        }
+
+        entry
+            .unpack(&path)
+            .with_context(|| anyhow!("{}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Get the name of a path in the archive.
+fn archive_name(workspace: &Path, home: Option<&Path>, path: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix(workspace) {
+        return Path::new(WORKSPACE).join(rest);
+    }
+
+    if let Some(rest) = home.and_then(|home| path.strip_prefix(home).ok()) {
+        return Path::new(HOME).join(rest);
+    }
+
+    let rest = path
+        .components()
+        .filter(|c| matches!(c, Component::Normal(..)))
+        .collect::<PathBuf>();
+
+    Path::new(ROOT).join(rest)
+}
+
+/// Get the path that a name in the archive should be restored to.
+fn restore_path(workspace: &Path, home: Option<&Path>, name: &Path) -> Option<PathBuf> {
+    let mut it = name.components();
+
+    let base = match it.next()?.as_os_str().to_str()? {
+        WORKSPACE => workspace.to_owned(),
+        HOME => home?.to_owned(),
+        ROOT => PathBuf::from("/"),
+        _ => return None,
+    };
+
+    let rest = it.as_path();
+
+    if !rest
+        .components()
+        .all(|c| matches!(c, Component::Normal(..)))
+    {
+        return None;
+    }
+
+    Some(base.join(rest))
+}
+
+/// Get the home directory of the current user.
+pub(super) fn home_dir() -> Result<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    home.map(PathBuf::from).context("Missing home directory")
+}
+
+/// Split a multiline input into non-empty lines.
+pub(super) fn lines(value: &str) -> Vec<String> {
+    value
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use super::*;
+
+/// Write a complete entry to the cache with the given size and last use.
+fn write_entry(dir: &Path, key: &str, size: usize, used: u64) -> Result<PathBuf> {
+    let path = dir.join(entry_name(key));
+    fs::create_dir_all(&path)?;
+    fs::write(path.join(KEY_FILE), key)?;
+    fs::write(path.join(DATA_FILE), vec![0u8; size])?;
+
+    let f = File::options().write(true).open(path.join(DATA_FILE))?;
+    f.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(used))?;
+    Ok(path)
+}
+
+fn find_key(dir: &Path, key: &str, restore_keys: &[&str]) -> Result<Option<String>> {
+    let restore_keys = restore_keys
+        .iter()
+        .map(|k| k.to_string())
+        .collect::<Vec<_>>();
+    Ok(find_entry(dir, key, &restore_keys)?.map(|e| e.key))
+}
+
+#[test]
+fn exact_key() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    write_entry(dir.path(), "linux-1", 1, 100)?;
+    write_entry(dir.path(), "linux-2", 1, 200)?;
+
+    assert_eq!(
+        find_key(dir.path(), "linux-1", &["linux-"])?.as_deref(),
+        Some("linux-1")
+    );
+    assert_eq!(find_key(dir.path(), "linux-3", &[])?, None);
+    Ok(())
+}
+
+#[test]
+fn restore_keys() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    write_entry(dir.path(), "linux-old", 1, 100)?;
+    write_entry(dir.path(), "linux-new", 1, 200)?;
+    write_entry(dir.path(), "windows-1", 1, 300)?;
+
+    // NB: Entries which are missing their data are incomplete.
+    let incomplete = dir.path().join(entry_name("linux-partial"));
+    fs::create_dir_all(&incomplete)?;
+    fs::write(incomplete.join(KEY_FILE), "linux-partial")?;
+
+    assert_eq!(
+        find_key(dir.path(), "linux-3", &["linux-", "windows-"])?.as_deref(),
+        Some("linux-new")
+    );
+    assert_eq!(
+        find_key(dir.path(), "linux-3", &["macos-", "windows-"])?.as_deref(),
+        Some("windows-1")
+    );
+    assert_eq!(find_key(dir.path(), "linux-3", &["macos-"])?, None);
+
+    touch(&dir.path().join(entry_name("linux-old")).join(DATA_FILE))?;
+
+    assert_eq!(
+        find_key(dir.path(), "linux-3", &["linux-"])?.as_deref(),
+        Some("linux-old")
+    );
+    Ok(())
+}
+
+#[test]
+fn eviction() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let a = write_entry(dir.path(), "a", 10, 100)?;
+    let b = write_entry(dir.path(), "b", 10, 300)?;
+    let c = write_entry(dir.path(), "c", 10, 200)?;
+
+    evict(dir.path(), 30)?;
+    assert!(a.is_dir() && b.is_dir() && c.is_dir());
+
+    evict(dir.path(), 25)?;
+    assert!(!a.exists());
+    assert!(b.is_dir() && c.is_dir());
+
+    evict(dir.path(), 0)?;
+    assert!(!b.exists() && !c.exists());
+    Ok(())
+}
+
+#[test]
+fn home_paths() -> Result<()> {
+    let workspace = Path::new("/workspace");
+    let home = Path::new("/home/user");
+
+    assert_eq!(
+        resolve_path(workspace, Some(home), "~/.cargo/registry")?,
+        home.join(".cargo/registry")
+    );
+    assert_eq!(
+        resolve_path(workspace, None, "target")?,
+        workspace.join("target")
+    );
+    assert!(resolve_path(workspace, None, "~/.cargo/registry").is_err());
+    Ok(())
+}
+
+#[test]
+fn pack_unpack() -> Result<()> {
+    let root = tempfile::tempdir()?;
+    let workspace = root.path().join("workspace");
+    let home = root.path().join("home");
+
+    fs::create_dir_all(workspace.join("target/debug"))?;
+    fs::create_dir_all(home.join(".cargo"))?;
+    fs::write(workspace.join("target/debug/out"), "workspace")?;
+    fs::write(home.join(".cargo/config"), "home")?;
+
+    let patterns = ["target".to_owned(), "~/.cargo/config".to_owned()];
+    let paths = resolve_paths(&workspace, Some(&home), &patterns)?;
+    assert_eq!(paths.len(), 2);
+
+    let archive = root.path().join(DATA_FILE);
+    pack(&archive, &workspace, Some(&home), &paths)?;
+
+    fs::remove_dir_all(&workspace)?;
+    fs::remove_dir_all(&home)?;
+
+    unpack(&archive, &workspace, Some(&home))?;
+    assert_eq!(
+        fs::read_to_string(workspace.join("target/debug/out"))?,
+        "workspace"
+    );
+    assert_eq!(fs::read_to_string(home.join(".cargo/config"))?, "home");
+    Ok(())
+}
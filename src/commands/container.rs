@@ -0,0 +1,276 @@
+use std::iter;
+use std::path;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+
+use crate::process::Command;
+use crate::system::Podman;
+use crate::workflows::Container;
+
+use super::SessionConfig;
+
+/// The `PATH` used if a container doesn't specify one.
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// How long to wait between health checks of a service.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many times a service is health checked before we give up on it.
+const HEALTH_ATTEMPTS: usize = 120;
+
+/// A container which has been started for a batch.
+pub(super) struct RunningContainer {
+    podman: Podman,
+    /// The name of the container.
+    pub(super) name: Box<str>,
+    /// The `PATH` configured inside of the container.
+    pub(super) path: Box<str>,
+    /// Ports published by the container, as pairs of container ports and the
+    /// host ports they are published on.
+    pub(super) ports: Vec<(String, String)>,
+}
+
+impl RunningContainer {
+    /// Start a container from the given image for a batch to run in.
+    ///
+    /// The repo and the kick cache are mounted at the same paths inside of the
+    /// container, so that any paths we construct are valid in both places.
+    ///
+    /// If this is a dry run the container is not actually started.
+    pub(super) fn start(
+        c: &SessionConfig<'_, '_>,
+        name: Box<str>,
+        image: &str,
+        container: Option<&Container>,
+        network: Option<&RunningNetwork>,
+    ) -> Result<Self> {
+        let podman = podman(c)?;
+
+        if c.dry_run {
+            return Ok(Self::dry_run(podman, name, container));
+        }
+
+        let cache_dir = c.cx.paths.cache.context("Missing cache directory")?;
+        let repo_dir = path::absolute(&c.path)?;
+
+        let mut command = podman.run([repo_dir.as_path(), cache_dir]);
+        command.arg("--workdir").arg(&repo_dir);
+        command.args(["--detach", "--name", &name, "--entrypoint", "tail"]);
+
+        if let Some(network) = network {
+            command.args(["--network", &network.name]);
+        }
+
+        if let Some(container) = container {
+            configure(&mut command, container);
+        }
+
+        command.args([image, "-f", "/dev/null"]);
+        Self::launch(podman, command, name, image, container)
+    }
+
+    /// Start a service container, which runs alongside a batch.
+    ///
+    /// If the service is configured with a `--health-cmd`, this waits until
+    /// the service is healthy.
+    ///
+    /// If this is a dry run the service is not actually started.
+    pub(super) fn service(
+        c: &SessionConfig<'_, '_>,
+        name: Box<str>,
+        id: &str,
+        service: &Container,
+        network: Option<&RunningNetwork>,
+    ) -> Result<Self> {
+        let podman = podman(c)?;
+
+        if c.dry_run {
+            return Ok(Self::dry_run(podman, name, Some(service)));
+        }
+
+        let mut command = podman.run(iter::empty());
+        command.args(["--detach", "--name", &name]);
+
+        if let Some(network) = network {
+            command.args(["--network", &network.name, "--network-alias", id]);
+        }
+
+        configure(&mut command, service);
+
+        let image = service.image.to_exposed();
+        command.arg(image.as_ref());
+
+        let this = Self::launch(podman, command, name, &image, Some(service))?;
+
+        let has_health_cmd = service
+            .options
+            .iter()
+            .any(|o| o == "--health-cmd" || o.starts_with("--health-cmd="));
+
+        if has_health_cmd {
+            this.wait_healthy()
+                .with_context(|| anyhow!("Waiting for service {id}"))?;
+        }
+
+        Ok(this)
+    }
+
+    fn dry_run(podman: Podman, name: Box<str>, container: Option<&Container>) -> Self {
+        let ports = container
+            .map(|c| published_ports(&c.ports))
+            .unwrap_or_default();
+
+        Self {
+            podman,
+            name,
+            path: Box::from(DEFAULT_PATH),
+            ports,
+        }
+    }
+
+    fn launch(
+        podman: Podman,
+        mut command: Command,
+        name: Box<str>,
+        image: &str,
+        container: Option<&Container>,
+    ) -> Result<Self> {
+        let status = command.stdout(Stdio::null()).status()?;
+        ensure!(
+            status.success(),
+            "Failed to start container from {image}: {status}"
+        );
+
+        let path = podman
+            .path(&name)
+            .with_context(|| anyhow!("Inspecting container {name}"))?;
+
+        let ports = match container {
+            Some(container) if !container.ports.is_empty() => podman
+                .ports(&name)
+                .with_context(|| anyhow!("Getting ports of container {name}"))?,
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            podman,
+            name,
+            path: path.as_deref().unwrap_or(DEFAULT_PATH).into(),
+            ports,
+        })
+    }
+
+    /// Wait for the container to become healthy.
+    fn wait_healthy(&self) -> Result<()> {
+        for _ in 0..HEALTH_ATTEMPTS {
+            if self.podman.healthcheck(&self.name)? {
+                return Ok(());
+            }
+
+            thread::sleep(HEALTH_INTERVAL);
+        }
+
+        bail!("Container {} did not become healthy", self.name);
+    }
+
+    /// Access the podman installation used for the container.
+    pub(super) fn podman(&self) -> &Podman {
+        &self.podman
+    }
+
+    /// Kill every process running inside of the container except for its
+    /// init process.
+    ///
+    /// Killing `podman exec` on the host doesn't stop the command it executes,
+    /// so this is how commands which have timed out are stopped. Note that
+    /// this also kills any background processes started by earlier steps.
+    pub(super) fn kill_processes(&self) -> Result<()> {
+        tracing::trace!(name = ?self.name, "Killing processes in container");
+        self.podman.kill_all(&self.name)
+    }
+
+    /// Remove the container.
+    pub(super) fn remove(&self) -> Result<()> {
+        tracing::trace!(name = ?self.name, "Removing container");
+        self.podman.remove(&self.name)
+    }
+}
+
+/// A network which connects a batch container to its services.
+pub(super) struct RunningNetwork {
+    podman: Podman,
+    /// The name of the network.
+    pub(super) name: Box<str>,
+}
+
+impl RunningNetwork {
+    /// Create a new network.
+    ///
+    /// If this is a dry run the network is not actually created.
+    pub(super) fn create(c: &SessionConfig<'_, '_>, name: Box<str>) -> Result<Self> {
+        let podman = podman(c)?;
+
+        if !c.dry_run {
+            podman
+                .create_network(&name)
+                .with_context(|| anyhow!("Creating network {name}"))?;
+        }
+
+        Ok(Self { podman, name })
+    }
+
+    /// Remove the network.
+    pub(super) fn remove(&self) -> Result<()> {
+        tracing::trace!(name = ?self.name, "Removing network");
+        self.podman.remove_network(&self.name)
+    }
+}
+
+fn podman(c: &SessionConfig<'_, '_>) -> Result<Podman> {
+    let Some(podman) = c.cx.system.podman.first() else {
+        bail!("Podman is not available");
+    };
+
+    Ok(podman.clone())
+}
+
+/// Add the environment, published ports and options of a container to a
+/// command.
+fn configure(command: &mut Command, container: &Container) {
+    for (key, value) in &container.env {
+        command.args(["--env", key]);
+        command.env(key, value);
+    }
+
+    for port in &container.ports {
+        command.args(["--publish", port]);
+    }
+
+    command.args(&container.options);
+}
+
+/// Guess which ports will be published from their specifications, which is
+/// used during dry runs when the container isn't started.
+///
+/// A specification is either `<container>`, `<host>:<container>` or
+/// `<ip>:<host>:<container>`, optionally followed by a `/<protocol>`.
+fn published_ports(ports: &[String]) -> Vec<(String, String)> {
+    let mut output = Vec::new();
+
+    for port in ports {
+        let (port, _) = port.split_once('/').unwrap_or((port, ""));
+        let mut it = port.rsplit(':');
+
+        let Some(container) = it.next() else {
+            continue;
+        };
+
+        let host = it.next().unwrap_or(container);
+        output.push((container.to_owned(), host.to_owned()));
+    }
+
+    output
+}
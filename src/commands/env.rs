@@ -1,3 +1,6 @@
+#[cfg(test)]
+mod tests;
+
 use std::collections::BTreeMap;
 use std::path::Path;
 use std::rc::Rc;
@@ -5,11 +8,26 @@ use std::rc::Rc;
 use anyhow::{Context, Result};
 
 use crate::process::OsArg;
-use crate::rstr::{RStr, RString};
+use crate::rstr::RString;
 use crate::workflows::{Eval, Tree};
 
 use super::{ActionConfig, ActionRunner, Run, SessionConfig};
 
+/// Values of the `github` context which are also provided as `GITHUB_*`
+/// environment variables.
+const GITHUB_ENV: [&str; 10] = [
+    "actor",
+    "event_name",
+    "ref",
+    "ref_name",
+    "ref_type",
+    "repository",
+    "repository_owner",
+    "server_url",
+    "sha",
+    "workspace",
+];
+
 #[derive(Clone)]
 pub(super) struct Env {
     pub(super) env: Rc<BTreeMap<String, RString>>,
@@ -18,6 +36,7 @@ pub(super) struct Env {
     env_file: Rc<Path>,
     path_file: Rc<Path>,
     output_file: Rc<Path>,
+    summary_file: Rc<Path>,
     tools_path: Rc<Path>,
     temp_path: Rc<Path>,
 }
@@ -36,6 +55,7 @@ impl Env {
         let env_file = Rc::<Path>::from(state_dir.join("env"));
         let output_file = Rc::<Path>::from(state_dir.join("output"));
         let path_file = Rc::<Path>::from(state_dir.join("path"));
+        let summary_file = Rc::<Path>::from(state_dir.join("step_summary"));
         let temp_path = Rc::<Path>::from(state_dir.join("temp"));
 
         let tools_path;
@@ -59,6 +79,7 @@ impl Env {
         file_env.insert("GITHUB_ENV", env_file.clone());
         file_env.insert("GITHUB_PATH", path_file.clone());
         file_env.insert("GITHUB_OUTPUT", output_file.clone());
+        file_env.insert("GITHUB_STEP_SUMMARY", summary_file.clone());
         file_env.insert("RUNNER_TOOL_CACHE", tools_path.clone());
         file_env.insert("RUNNER_TEMP", temp_path.clone());
 
@@ -74,11 +95,13 @@ impl Env {
 
         tree.insert(["runner", "os"], runner_os.as_tree_value());
 
-        let github_tree = [(String::from("server"), RStr::new(batch.github_server()))]
-            .into_iter()
-            .chain(batch.github_token().map(|t| (String::from("token"), t)));
+        env.extend(github_env(&batch.github_context()));
+
+        tree.extend(&batch.github_tree());
 
-        tree.insert_prefix(["github"], github_tree);
+        if let Some(path) = &batch.event_path {
+            file_env.insert("GITHUB_EVENT_PATH", Rc::<Path>::from(path.as_path()));
+        }
 
         env.insert(
             String::from("GITHUB_SERVER"),
@@ -131,6 +154,7 @@ impl Env {
             env_file,
             path_file,
             output_file,
+            summary_file,
             tools_path,
             temp_path,
         })
@@ -195,7 +219,17 @@ impl Env {
             .with_env_file(Some(self.env_file.clone()))
             .with_path_file(Some(self.path_file.clone()))
             .with_output_file(Some(self.output_file.clone()))
+            .with_summary_file(Some(self.summary_file.clone()))
             .with_tools_path(self.tools_path.clone())
             .with_temp_path(self.temp_path.clone())
     }
 }
+
+/// Construct the `GITHUB_*` environment variables from the `github` context,
+/// like `GITHUB_REF_NAME` from `github.ref_name`.
+fn github_env(github: &BTreeMap<String, RString>) -> impl Iterator<Item = (String, RString)> + '_ {
+    GITHUB_ENV.into_iter().filter_map(|key| {
+        let value = github.get(key)?;
+        Some((format!("GITHUB_{}", key.to_uppercase()), value.clone()))
+    })
+}
@@ -0,0 +1,30 @@
+use super::*;
+
+#[test]
+fn github_env_mapping() {
+    let github = [
+        ("ref", "refs/heads/main"),
+        ("ref_name", "main"),
+        ("repository", "udoprog/kick"),
+        ("server", "https://github.com"),
+        ("token", "kick-env-token"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_owned(), RString::from(value)))
+    .collect::<BTreeMap<_, _>>();
+
+    let env = github_env(&github)
+        .map(|(key, value)| (key, value.to_exposed().into_owned()))
+        .collect::<Vec<_>>();
+
+    // NB: Only a subset of the context is exported, and missing values are
+    // left out.
+    assert_eq!(
+        env,
+        [
+            ("GITHUB_REF".to_owned(), "refs/heads/main".to_owned()),
+            ("GITHUB_REF_NAME".to_owned(), "main".to_owned()),
+            ("GITHUB_REPOSITORY".to_owned(), "udoprog/kick".to_owned()),
+        ]
+    );
+}
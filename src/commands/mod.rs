@@ -10,18 +10,31 @@ pub(crate) use self::action_runners::ActionRunners;
 mod actions;
 pub(crate) use self::actions::{Actions, StringObjectId};
 
+mod artifacts;
+pub(crate) use self::artifacts::collect_artifacts;
+use self::artifacts::{ArtifactOperation, ScheduleArtifact};
+
 mod batch;
 pub(crate) use self::batch::Batch;
 
+mod secrets;
+use self::secrets::Secrets;
+
 mod session_config;
 pub(crate) use self::session_config::SessionConfig;
 
 mod batch_options;
 pub(crate) use self::batch_options::BatchOptions;
 
+mod cache;
+use self::cache::{CacheOperation, ScheduleCache};
+
 mod colors;
 pub(crate) use self::colors::Colors;
 
+mod container;
+use self::container::{RunningContainer, RunningNetwork};
+
 mod env;
 use self::env::Env;
 
@@ -31,6 +44,10 @@ pub(crate) use self::r#prepare::Session;
 mod remediations;
 pub(crate) use self::remediations::Remediations;
 
+mod resume;
+use self::resume::StepTracker;
+pub(crate) use self::resume::{ResumeState, StepSelector, StepWindow};
+
 mod run_on;
 use self::run_on::RunOn;
 
@@ -39,12 +56,19 @@ use self::run::{Run, RunKind};
 
 mod schedule;
 use self::schedule::{
-    Schedule, ScheduleBasicCommand, ScheduleGroup, ScheduleNodeAction, ScheduleOutputs,
-    ScheduleUse, build_steps,
+    Schedule, ScheduleBasicCommand, ScheduleDockerAction, ScheduleGroup, ScheduleNodeAction,
+    ScheduleOutputs, ScheduleStaticSetup, ScheduleUse, build_steps,
 };
 
 mod scheduler;
 use self::scheduler::Scheduler;
 
+mod summary;
+pub(crate) use self::summary::{write_summaries, write_summary_file};
+
+mod workflow_commands;
+pub(crate) use self::workflow_commands::write_annotations;
+use self::workflow_commands::{Annotation, StepOutput, parse_key_values};
+
 mod workflows;
 use self::workflows::LoadedWorkflows;
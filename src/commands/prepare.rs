@@ -1,6 +1,7 @@
 use std::collections::BTreeSet;
 use std::path::Path;
 use std::process::Stdio;
+use std::rc::Rc;
 use std::str;
 
 use anyhow::{Result, bail};
@@ -8,9 +9,12 @@ use semver::Version;
 
 use crate::config::{Distribution, Os};
 use crate::process::Command;
-use crate::workflows::Eval;
+use crate::workflows::{Container, Eval};
 
-use super::{ActionRunners, Actions, Remediations, SessionConfig};
+use super::{
+    ActionRunners, Actions, Annotation, Remediations, RunningContainer, RunningNetwork,
+    SessionConfig,
+};
 
 const CURL: &str = "curl --proto '=https' --tlsv1.2 -sSf";
 const DEBIAN_WANTED: &[&str] = &["gcc", "pkg-config", "libssl-dev"];
@@ -34,6 +38,14 @@ pub(crate) struct Session {
     runners: ActionRunners,
     /// Files that should be removed at the end of the session.
     remove_paths: Vec<Box<Path>>,
+    /// Containers that should be removed at the end of the session.
+    containers: Vec<Rc<RunningContainer>>,
+    /// Networks that should be removed at the end of the session.
+    networks: Vec<Rc<RunningNetwork>>,
+    /// Annotations emitted by steps.
+    annotations: Vec<Annotation>,
+    /// Markdown written by steps to `GITHUB_STEP_SUMMARY`.
+    summaries: Vec<String>,
     /// Unique sequence number.
     sequence: u32,
     /// Keep temporary files.
@@ -43,6 +55,12 @@ pub(crate) struct Session {
 impl Session {
     /// Construct a new preparation.
     pub(crate) fn new(c: &SessionConfig<'_, '_>) -> Self {
+        let remove_paths = c
+            .event_path
+            .iter()
+            .map(|p| Box::from(p.as_path()))
+            .collect();
+
         Self {
             dists: BTreeSet::new(),
             is_same: false,
@@ -51,7 +69,11 @@ impl Session {
             is_same_prepare: false,
             actions: Actions::default(),
             runners: ActionRunners::default(),
-            remove_paths: Vec::new(),
+            remove_paths,
+            containers: Vec::new(),
+            networks: Vec::new(),
+            annotations: Vec::new(),
+            summaries: Vec::new(),
             sequence: 0,
             keep: c.keep,
         }
@@ -64,11 +86,120 @@ impl Session {
         sequence
     }
 
+    /// Record an annotation emitted by a step.
+    pub(super) fn push_annotation(&mut self, annotation: Annotation) {
+        self.annotations.push(annotation);
+    }
+
+    /// Get all annotations emitted by steps.
+    pub(crate) fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Record the summary written by a step.
+    pub(crate) fn push_summary(&mut self, summary: String) {
+        self.summaries.push(summary);
+    }
+
+    /// Get all summaries written by steps.
+    pub(crate) fn summaries(&self) -> &[String] {
+        &self.summaries
+    }
+
     /// Mark a file that should be removed.
     pub(super) fn remove_path(&mut self, path: impl AsRef<Path>) {
         self.remove_paths.push(Box::from(path.as_ref()));
     }
 
+    /// Start a container to run a batch in.
+    ///
+    /// The container is removed at the latest when the session ends.
+    pub(super) fn start_container(
+        &mut self,
+        c: &SessionConfig<'_, '_>,
+        image: &str,
+        container: Option<&Container>,
+        network: Option<&RunningNetwork>,
+    ) -> Result<Rc<RunningContainer>> {
+        let name = self.podman_name(c);
+        let container = RunningContainer::start(c, name, image, container, network)?;
+        Ok(self.register_container(c, container))
+    }
+
+    /// Start a service container which runs alongside a batch.
+    ///
+    /// The service is removed at the latest when the session ends.
+    pub(super) fn start_service(
+        &mut self,
+        c: &SessionConfig<'_, '_>,
+        id: &str,
+        service: &Container,
+        network: Option<&RunningNetwork>,
+    ) -> Result<Rc<RunningContainer>> {
+        let name = self.podman_name(c);
+        let container = RunningContainer::service(c, name, id, service, network)?;
+        Ok(self.register_container(c, container))
+    }
+
+    /// Remove a container started with [`Session::start_container`] or
+    /// [`Session::start_service`].
+    pub(super) fn remove_container(&mut self, container: &RunningContainer) -> Result<()> {
+        let Some(index) = self
+            .containers
+            .iter()
+            .position(|c| c.name == container.name)
+        else {
+            return Ok(());
+        };
+
+        self.containers.swap_remove(index).remove()
+    }
+
+    /// Create a network which connects a batch container to its services.
+    ///
+    /// The network is removed at the latest when the session ends.
+    pub(super) fn create_network(
+        &mut self,
+        c: &SessionConfig<'_, '_>,
+    ) -> Result<Rc<RunningNetwork>> {
+        let name = self.podman_name(c);
+        let network = Rc::new(RunningNetwork::create(c, name)?);
+
+        if !c.dry_run {
+            self.networks.push(network.clone());
+        }
+
+        Ok(network)
+    }
+
+    /// Remove a network created with [`Session::create_network`].
+    pub(super) fn remove_network(&mut self, network: &RunningNetwork) -> Result<()> {
+        let Some(index) = self.networks.iter().position(|n| n.name == network.name) else {
+            return Ok(());
+        };
+
+        self.networks.swap_remove(index).remove()
+    }
+
+    fn register_container(
+        &mut self,
+        c: &SessionConfig<'_, '_>,
+        container: RunningContainer,
+    ) -> Rc<RunningContainer> {
+        let container = Rc::new(container);
+
+        if !c.dry_run {
+            self.containers.push(container.clone());
+        }
+
+        container
+    }
+
+    /// Generate a unique name for a podman resource.
+    fn podman_name(&mut self, c: &SessionConfig<'_, '_>) -> Box<str> {
+        format!("kick-{}-{}", c.process_id, self.sequence()).into()
+    }
+
     /// Access actions to prepare.
     pub(super) fn actions_mut(&mut self) -> &mut Actions {
         &mut self.actions
@@ -97,7 +228,7 @@ impl Session {
         }
 
         self.actions
-            .synchronize(&mut self.runners, config.cx, eval)?;
+            .synchronize(&mut self.runners, config.cx, &config.path, eval)?;
 
         while let Some(version) = self.actions.found_node_versions.pop_first() {
             self.prepare_node_version(&version, config, &mut suggestions)?;
@@ -306,8 +437,20 @@ impl Session {
         Ok(())
     }
 
-    /// Clean up any remaining temporary files.
+    /// Clean up any remaining containers, networks and temporary files.
     fn cleanup(&mut self) {
+        for container in self.containers.drain(..) {
+            if let Err(error) = container.remove() {
+                tracing::warn!(name = ?container.name, ?error, "Failed to remove container");
+            }
+        }
+
+        for network in self.networks.drain(..) {
+            if let Err(error) = network.remove() {
+                tracing::warn!(name = ?network.name, ?error, "Failed to remove network");
+            }
+        }
+
         if self.keep {
             return;
         }
@@ -0,0 +1,263 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::workflows::{Step, Tree};
+
+use super::SessionConfig;
+
+/// The directory in the kick cache where the state of jobs is stored.
+const RESUME_DIR: &str = "resume";
+
+/// A step in a job, selected either by its 1-based index or by its id or name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StepSelector {
+    Index(usize),
+    Id(String),
+}
+
+impl StepSelector {
+    /// Parse a step selector, where a number is interpreted as an index.
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+            let index = value.parse::<usize>()?;
+
+            if index == 0 {
+                bail!("Step indexes start at 1");
+            }
+
+            return Ok(Self::Index(index));
+        }
+
+        Ok(Self::Id(value.to_owned()))
+    }
+
+    fn is_match(&self, index: usize, step: Option<&Step>) -> bool {
+        match self {
+            Self::Index(n) => *n == index,
+            Self::Id(expected) => step.is_some_and(|step| {
+                step.id.as_deref().is_some_and(|id| id.str_eq(expected))
+                    || step.name.as_deref() == Some(expected.as_str())
+            }),
+        }
+    }
+}
+
+impl fmt::Display for StepSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{index}"),
+            Self::Id(id) => write!(f, "`{id}`"),
+        }
+    }
+}
+
+/// A window of steps in a job to run.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StepWindow {
+    from: Option<StepSelector>,
+    until: Option<StepSelector>,
+}
+
+impl StepWindow {
+    /// Construct a new step window, where both ends are inclusive.
+    pub(crate) fn new(from: Option<StepSelector>, until: Option<StepSelector>) -> Self {
+        Self { from, until }
+    }
+
+    /// Test if the window includes every step.
+    pub(crate) fn is_full(&self) -> bool {
+        self.from.is_none() && self.until.is_none()
+    }
+
+    /// Return a copy of the window which starts from the given step.
+    pub(crate) fn with_from(&self, from: StepSelector) -> Self {
+        Self {
+            from: Some(from),
+            until: self.until.clone(),
+        }
+    }
+}
+
+/// Tracks which steps of a job are in a window as they are being run.
+#[derive(Default)]
+pub(super) struct StepTracker {
+    window: StepWindow,
+    /// The index of the current step.
+    index: usize,
+    started: bool,
+    ended: bool,
+    /// Set if the end of the window was found before its start.
+    inverted: bool,
+}
+
+impl StepTracker {
+    pub(super) fn new(window: StepWindow) -> Self {
+        Self {
+            started: window.from.is_none(),
+            window,
+            index: 0,
+            ended: false,
+            inverted: false,
+        }
+    }
+
+    /// The 1-based index of the current step.
+    pub(super) fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Advance to the next step, returning `true` if it is in the window.
+    pub(super) fn next(&mut self, step: Option<&Step>) -> bool {
+        self.index += 1;
+
+        if self.ended {
+            return false;
+        }
+
+        let is_until = self
+            .window
+            .until
+            .as_ref()
+            .is_some_and(|until| until.is_match(self.index, step));
+
+        if !self.started {
+            self.started = self
+                .window
+                .from
+                .as_ref()
+                .is_some_and(|from| from.is_match(self.index, step));
+
+            if !self.started {
+                if is_until {
+                    self.inverted = true;
+                    self.ended = true;
+                }
+
+                return false;
+            }
+        }
+
+        if is_until {
+            self.ended = true;
+        }
+
+        true
+    }
+
+    /// Check that the steps selected by the window were found.
+    pub(super) fn finish(&self) -> Result<()> {
+        if let (Some(from), Some(until)) = (&self.window.from, &self.window.until)
+            && self.inverted
+        {
+            bail!("Step {until} comes before step {from} in the job");
+        }
+
+        if let Some(from) = &self.window.from
+            && !self.started
+        {
+            bail!("Step {from} was not found in the job");
+        }
+
+        if let Some(until) = &self.window.until
+            && !self.ended
+        {
+            bail!("Step {until} was not found in the job");
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of a job persisted before each of its steps, which is used to
+/// resume a failed job from the step that failed.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct ResumeState {
+    /// The 1-based index of the step to resume from.
+    pub(super) step: usize,
+    /// Environment variables added through `GITHUB_ENV`.
+    pub(super) env: BTreeMap<String, String>,
+    /// Paths added through `GITHUB_PATH`.
+    pub(super) paths: Vec<String>,
+    /// Values of the `steps` context.
+    pub(super) steps: Vec<(Vec<String>, String)>,
+}
+
+impl ResumeState {
+    /// Get the path of the file that the state of a job identified by the
+    /// given parts is stored in.
+    pub(crate) fn path(c: &SessionConfig<'_, '_>, parts: &[&str]) -> Result<PathBuf> {
+        let cache_dir = c.cx.paths.cache.context("Missing cache directory")?;
+
+        let mut hasher = Sha256::new();
+
+        for part in parts {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+
+        let mut name = String::with_capacity(37);
+
+        for b in &hasher.finalize()[..16] {
+            _ = write!(name, "{b:02x}");
+        }
+
+        name.push_str(".json");
+        Ok(cache_dir.join("state").join(RESUME_DIR).join(name))
+    }
+
+    /// The 1-based index of the step to resume from.
+    pub(crate) fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Load the state from the given path, if it exists.
+    pub(crate) fn load(path: &Path) -> Result<Option<Self>> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| anyhow!("{}", path.display())),
+        };
+
+        let state =
+            serde_json::from_slice(&bytes).with_context(|| anyhow!("{}", path.display()))?;
+        Ok(Some(state))
+    }
+
+    /// Save the state to the given path.
+    pub(super) fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| anyhow!("{}", parent.display()))?;
+        }
+
+        fs::write(path, serde_json::to_vec(self)?).with_context(|| anyhow!("{}", path.display()))
+    }
+
+    /// Extract the `steps` context from a tree.
+    pub(super) fn set_steps(&mut self, tree: &Tree) {
+        self.steps = tree
+            .values_under(["steps"])
+            .into_iter()
+            .map(|(key, value)| (key, value.to_exposed().into_owned()))
+            .collect();
+    }
+
+    /// Restore the `steps` context into a tree.
+    pub(super) fn restore_steps(&self, tree: &mut Tree) {
+        for (key, value) in &self.steps {
+            tree.insert(
+                ["steps"].into_iter().chain(key.iter().map(String::as_str)),
+                value,
+            );
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::commands::Scheduler;
+use crate::rstr::RString;
+
+use super::*;
+
+fn step(id: Option<&str>, name: Option<&str>) -> Step {
+    Step {
+        id: id.map(|id| RString::from(id).into_rc()),
+        name: name.map(str::to_owned),
+        ..Step::default()
+    }
+}
+
+/// The steps of a job used in tests, where the second step has no id.
+fn steps() -> Vec<Step> {
+    vec![
+        step(Some("checkout"), Some("Checkout")),
+        step(None, Some("Install toolchain")),
+        step(Some("build"), None),
+        step(Some("test"), Some("Run tests")),
+    ]
+}
+
+fn window(from: Option<&str>, until: Option<&str>) -> Result<StepWindow> {
+    Ok(StepWindow::new(
+        from.map(StepSelector::parse).transpose()?,
+        until.map(StepSelector::parse).transpose()?,
+    ))
+}
+
+/// Track the steps of a job, returning the 1-based indexes of the steps which
+/// are in the window along with the tracker.
+fn track(window: StepWindow) -> (Vec<usize>, StepTracker) {
+    let mut tracker = StepTracker::new(window);
+    let mut selected = Vec::new();
+
+    for step in steps() {
+        if tracker.next(Some(&step)) {
+            selected.push(tracker.index());
+        }
+    }
+
+    (selected, tracker)
+}
+
+fn select(from: Option<&str>, until: Option<&str>) -> Result<Vec<usize>> {
+    let (selected, tracker) = track(window(from, until)?);
+    tracker.finish()?;
+    Ok(selected)
+}
+
+#[test]
+fn parse_selector() -> Result<()> {
+    assert_eq!(StepSelector::parse("3")?, StepSelector::Index(3));
+    assert_eq!(
+        StepSelector::parse("build")?,
+        StepSelector::Id(String::from("build"))
+    );
+    assert_eq!(
+        StepSelector::parse("3rd")?,
+        StepSelector::Id(String::from("3rd"))
+    );
+    assert!(StepSelector::parse("0").is_err());
+    Ok(())
+}
+
+#[test]
+fn select_steps() -> Result<()> {
+    assert!(window(None, None)?.is_full());
+    assert_eq!(select(None, None)?, [1, 2, 3, 4]);
+
+    assert_eq!(select(Some("2"), None)?, [2, 3, 4]);
+    assert_eq!(select(None, Some("2"))?, [1, 2]);
+    assert_eq!(select(Some("2"), Some("3"))?, [2, 3]);
+
+    assert_eq!(select(Some("build"), None)?, [3, 4]);
+    assert_eq!(select(None, Some("build"))?, [1, 2, 3]);
+    assert_eq!(select(Some("checkout"), Some("build"))?, [1, 2, 3]);
+
+    assert_eq!(select(Some("Install toolchain"), None)?, [2, 3, 4]);
+    assert_eq!(select(Some("Checkout"), Some("Run tests"))?, [1, 2, 3, 4]);
+
+    assert_eq!(select(Some("build"), Some("build"))?, [3]);
+    assert_eq!(select(Some("3"), Some("build"))?, [3]);
+    Ok(())
+}
+
+#[test]
+fn resume_window() -> Result<()> {
+    let window = window(None, Some("test"))?.with_from(StepSelector::Index(3));
+    let (selected, tracker) = track(window);
+    tracker.finish()?;
+    assert_eq!(selected, [3, 4]);
+    Ok(())
+}
+
+#[test]
+fn unknown_steps() -> Result<()> {
+    let (selected, tracker) = track(window(Some("deploy"), None)?);
+    assert!(selected.is_empty());
+    assert_eq!(
+        tracker.finish().unwrap_err().to_string(),
+        "Step `deploy` was not found in the job"
+    );
+
+    let (selected, tracker) = track(window(None, Some("5"))?);
+    assert_eq!(selected, [1, 2, 3, 4]);
+    assert_eq!(
+        tracker.finish().unwrap_err().to_string(),
+        "Step #5 was not found in the job"
+    );
+
+    let mut scheduler = Scheduler::new();
+    scheduler.set_step_window(window(Some("deploy"), None)?);
+    assert_eq!(
+        scheduler.finish_steps().unwrap_err().to_string(),
+        "Step `deploy` was not found in the job"
+    );
+    Ok(())
+}
+
+#[test]
+fn inverted_window() -> Result<()> {
+    let (selected, tracker) = track(window(Some("test"), Some("build"))?);
+    assert!(selected.is_empty());
+    assert_eq!(
+        tracker.finish().unwrap_err().to_string(),
+        "Step `build` comes before step `test` in the job"
+    );
+
+    let (selected, tracker) = track(window(Some("3"), Some("1"))?);
+    assert!(selected.is_empty());
+    assert_eq!(
+        tracker.finish().unwrap_err().to_string(),
+        "Step #1 comes before step #3 in the job"
+    );
+    Ok(())
+}
+
+#[test]
+fn state_round_trip() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("state").join("job.json");
+    assert!(ResumeState::load(&path)?.is_none());
+
+    let mut tree = Tree::new();
+    tree.insert(["steps", "build", "outcome"], "success");
+    tree.insert(["steps", "build", "outputs", "version"], "1.2.3");
+    tree.insert(["matrix", "os"], "linux");
+
+    let mut state = ResumeState {
+        step: 3,
+        env: [(String::from("RUSTFLAGS"), String::from("-Dwarnings"))]
+            .into_iter()
+            .collect(),
+        paths: vec![String::from("/opt/bin")],
+        ..ResumeState::default()
+    };
+
+    state.set_steps(&tree);
+    state.save(&path)?;
+
+    let Some(loaded) = ResumeState::load(&path)? else {
+        panic!("Expected state to be saved");
+    };
+
+    assert_eq!(loaded.step(), 3);
+    assert_eq!(loaded.env, state.env);
+    assert_eq!(loaded.paths, state.paths);
+    assert_eq!(loaded.steps, state.steps);
+
+    let mut restored = Tree::new();
+    loaded.restore_steps(&mut restored);
+
+    assert_eq!(
+        restored.values_under(["steps"]),
+        tree.values_under(["steps"])
+    );
+    assert!(restored.values_under(["matrix"]).is_empty());
+    Ok(())
+}
+
+#[test]
+fn state_invalid() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("job.json");
+    fs::write(&path, "not json")?;
+    assert!(ResumeState::load(&path).is_err());
+    Ok(())
+}
@@ -2,15 +2,16 @@ use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 use std::rc::Rc;
 use std::str;
+use std::time::Duration;
 
 use crate::process::OsArg;
 use crate::rstr::{RStr, RString};
-use crate::shell::Shell;
+use crate::shell::ScriptShell;
 
 pub(super) enum RunKind {
     Shell {
         script: Box<RStr>,
-        shell: Shell,
+        shell: ScriptShell,
     },
     Command {
         command: OsArg,
@@ -20,6 +21,11 @@ pub(super) enum RunKind {
         node_version: u64,
         script_file: Rc<Path>,
     },
+    Docker {
+        image: Rc<str>,
+        entrypoint: Option<Rc<str>>,
+        args: Box<[RString]>,
+    },
 }
 
 /// A run configuration.
@@ -36,12 +42,18 @@ pub(crate) struct Run {
     pub(super) path_file: Option<Rc<Path>>,
     // If an output file is supported, this is the path to the file to set up.
     pub(super) output_file: Option<Rc<Path>>,
+    // If a step summary is supported, this is the path to the file to set up.
+    pub(super) summary_file: Option<Rc<Path>>,
     // The directory where to store tools, if possible.
     pub(super) tools_path: Option<Rc<Path>>,
     // The directory where temporary data is stored.
     pub(super) temp_path: Option<Rc<Path>>,
     // Environment variables which are files.
     pub(super) env_is_file: HashSet<String>,
+    // Whether the run is allowed to fail without failing the batch.
+    pub(super) continue_on_error: bool,
+    // The maximum amount of time the run is allowed to take.
+    pub(super) timeout: Option<Duration>,
 }
 
 impl Run {
@@ -58,7 +70,7 @@ impl Run {
     }
 
     /// Setup a script to run.
-    pub(super) fn script(script: impl Into<Box<RStr>>, shell: Shell) -> Self {
+    pub(super) fn script(script: impl Into<Box<RStr>>, shell: ScriptShell) -> Self {
         Self::with_run(RunKind::Shell {
             script: script.into(),
             shell,
@@ -73,6 +85,15 @@ impl Run {
         })
     }
 
+    /// Setup a docker image to run.
+    pub(super) fn docker(image: Rc<str>, entrypoint: Option<Rc<str>>, args: Vec<RString>) -> Self {
+        Self::with_run(RunKind::Docker {
+            image,
+            entrypoint,
+            args: args.into(),
+        })
+    }
+
     pub(super) fn with_run(run: RunKind) -> Self {
         Self {
             run,
@@ -84,9 +105,12 @@ impl Run {
             env_file: None,
             path_file: None,
             output_file: None,
+            summary_file: None,
             tools_path: None,
             temp_path: None,
             env_is_file: HashSet::new(),
+            continue_on_error: false,
+            timeout: None,
         }
     }
 
@@ -128,6 +152,20 @@ impl Run {
         self
     }
 
+    /// Modify whether the run command is allowed to fail.
+    #[inline]
+    pub(super) fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Modify the timeout of the run command.
+    #[inline]
+    pub(super) fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
     /// Modify the environment file of the run command.
     #[inline]
     pub(super) fn with_env_file(mut self, env_file: Option<Rc<Path>>) -> Self {
@@ -149,6 +187,13 @@ impl Run {
         self
     }
 
+    /// Modify the step summary file of the run command.
+    #[inline]
+    pub(super) fn with_summary_file(mut self, summary_file: Option<Rc<Path>>) -> Self {
+        self.summary_file = summary_file;
+        self
+    }
+
     /// Modify the tools path.
     #[inline]
     pub(super) fn with_tools_path(mut self, tools_path: Rc<Path>) -> Self {
@@ -184,6 +229,7 @@ impl Run {
             .iter()
             .chain(self.path_file.as_slice())
             .chain(self.output_file.as_slice())
+            .chain(self.summary_file.as_slice())
             .map(Rc::as_ref)
     }
 
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::config::{Distribution, Os};
 
 use anyhow::{Result, bail};
@@ -5,13 +7,15 @@ use anyhow::{Result, bail};
 use super::SessionConfig;
 
 /// A run on configuration.
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) enum RunOn {
     /// Run on the same system (default).
     #[default]
     Same,
     /// Run over WSL with the specified distribution.
     Wsl(Distribution),
+    /// Run inside of a podman container using the specified image.
+    Container(Rc<str>),
 }
 
 impl RunOn {
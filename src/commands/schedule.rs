@@ -1,17 +1,22 @@
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::BTreeMap;
 use std::path::Path;
 use std::rc::Rc;
+use std::time::Duration;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result};
 
 use crate::config::Os;
 use crate::process::OsArg;
 use crate::rstr::{RStr, RString};
-use crate::shell::Shell;
-use crate::workflows::{Eval, Step, Tree};
+use crate::shell::ScriptShell;
+use crate::workflows::{self, Eval, Step, Tree};
 
-use super::{ActionConfig, ActionRunner, ActionRunners, Env, Run, Session, SessionConfig};
+use super::{
+    ActionConfig, ActionRunner, ActionRunners, ArtifactOperation, CacheOperation, Env, Run,
+    ScheduleArtifact, ScheduleCache, Session, SessionConfig,
+};
 
 /// Schedule outputs.
 #[derive(Clone)]
@@ -26,6 +31,7 @@ pub(super) struct ScheduleGroup {
     pub(super) id: Option<Rc<RStr>>,
     pub(super) steps: Rc<[Schedule]>,
     pub(super) outputs: Option<ScheduleOutputs>,
+    pub(super) tree: Option<Rc<Tree>>,
 }
 
 impl ScheduleGroup {
@@ -36,6 +42,7 @@ impl ScheduleGroup {
             id,
             steps,
             outputs: None,
+            tree: None,
         }
     }
 
@@ -44,6 +51,12 @@ impl ScheduleGroup {
         self.outputs = Some(outputs);
         self
     }
+
+    /// Modify the tree that the group starts out with.
+    pub(super) fn with_tree(mut self, tree: Rc<Tree>) -> Self {
+        self.tree = Some(tree);
+        self
+    }
 }
 
 /// A scheduled action.
@@ -53,8 +66,11 @@ pub(super) enum Schedule {
     BasicCommand(ScheduleBasicCommand),
     StaticSetup(ScheduleStaticSetup),
     NodeAction(ScheduleNodeAction),
+    DockerAction(ScheduleDockerAction),
     Run(ScheduleRun),
     Use(ScheduleUse),
+    Cache(ScheduleCache),
+    Artifact(ScheduleArtifact),
 }
 
 impl Schedule {
@@ -62,12 +78,25 @@ impl Schedule {
     pub(super) fn prepare(&self, session: &mut Session) -> Result<()> {
         match self {
             Schedule::Use(u) => {
-                session.actions_mut().insert_action(u.uses())?;
+                // NB: Builtin actions don't need to be synchronized.
+                if !is_builtin_action(u.uses()) {
+                    session.actions_mut().insert_action(u.uses())?;
+                }
+
                 Ok(())
             }
             _ => Ok(()),
         }
     }
+
+    /// Get the workflow step the schedule was constructed from, if any.
+    pub(super) fn step(&self) -> Option<&Step> {
+        match self {
+            Schedule::Run(run) => Some(&run.step),
+            Schedule::Use(u) => Some(&u.step),
+            _ => None,
+        }
+    }
 }
 
 /// Add jobs from a workflows, matrix, and associated steps.
@@ -78,7 +107,7 @@ pub(super) fn build_steps(
     name: Option<&RStr>,
     steps: &[Rc<Step>],
     runner: Option<&ActionRunner>,
-) -> Result<Schedule> {
+) -> Result<ScheduleGroup> {
     let env = Env::new(batch, runner, c)?;
 
     let mut group = Vec::new();
@@ -110,11 +139,11 @@ pub(super) fn build_steps(
         }
     }
 
-    Ok(Schedule::Group(ScheduleGroup::new(
+    Ok(ScheduleGroup::new(
         name.map(RStr::as_rc),
         id.cloned(),
         group.into(),
-    )))
+    ))
 }
 
 #[derive(Clone)]
@@ -147,6 +176,8 @@ pub(crate) struct ScheduleNodeAction {
     skipped: Option<String>,
     env: Env,
     condition: Option<String>,
+    continue_on_error: bool,
+    timeout: Option<Duration>,
 }
 
 impl ScheduleNodeAction {
@@ -163,9 +194,23 @@ impl ScheduleNodeAction {
             skipped: skipped.map(str::to_owned),
             env,
             condition,
+            continue_on_error: false,
+            timeout: None,
         }
     }
 
+    /// Modify whether the action is allowed to fail.
+    pub(crate) fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Modify the timeout of the action.
+    pub(crate) fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
     pub(super) fn build(self) -> Result<Run> {
         let skipped = 'skipped: {
             let Some(condition) = self.condition else {
@@ -183,15 +228,111 @@ impl ScheduleNodeAction {
 
         let run = Run::node(self.node_version, self.path)
             .with_skipped(self.skipped.or(skipped))
-            .with_env(self.env.build_os_env());
+            .with_env(self.env.build_os_env())
+            .with_continue_on_error(self.continue_on_error)
+            .with_timeout(self.timeout);
 
         Ok(self.env.decorate(run))
     }
 }
 
+#[derive(Clone)]
+pub(crate) struct ScheduleDockerAction {
+    image: Rc<str>,
+    entrypoint: Option<Rc<str>>,
+    args: Rc<[String]>,
+    action_env: Rc<BTreeMap<String, String>>,
+    skipped: Option<String>,
+    env: Env,
+    condition: Option<String>,
+    id: Option<Rc<RStr>>,
+    name: Option<Rc<RStr>>,
+    continue_on_error: bool,
+    timeout: Option<Duration>,
+}
+
+impl ScheduleDockerAction {
+    pub(crate) fn new(
+        image: Rc<str>,
+        entrypoint: Option<Rc<str>>,
+        args: Rc<[String]>,
+        action_env: Rc<BTreeMap<String, String>>,
+        skipped: Option<&str>,
+        env: Env,
+        condition: Option<String>,
+    ) -> Self {
+        Self {
+            image,
+            entrypoint,
+            args,
+            action_env,
+            skipped: skipped.map(str::to_owned),
+            env,
+            condition,
+            id: None,
+            name: None,
+            continue_on_error: false,
+            timeout: None,
+        }
+    }
+
+    /// Modify the id of the action, under which its outputs are stored.
+    pub(crate) fn with_id(mut self, id: Option<Rc<RStr>>) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Modify the name of the action.
+    pub(crate) fn with_name(mut self, name: Rc<RStr>) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Modify whether the action is allowed to fail.
+    pub(crate) fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Modify the timeout of the action.
+    pub(crate) fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub(super) fn build(self) -> Result<Run> {
+        let env = self.env.extend_with(&Tree::new(), &self.action_env)?;
+        let eval = Eval::new(&env.tree);
+
+        let mut skipped = None;
+
+        if let Some(condition) = &self.condition
+            && !eval.test(condition)?
+        {
+            skipped = Some(condition.clone());
+        }
+
+        let args = self
+            .args
+            .iter()
+            .map(|arg| Ok(eval.eval(arg)?.into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let run = Run::docker(self.image, self.entrypoint, args)
+            .with_id(self.id)
+            .with_name(self.name.as_deref())
+            .with_skipped(self.skipped.or(skipped))
+            .with_env(env.build_os_env())
+            .with_continue_on_error(self.continue_on_error)
+            .with_timeout(self.timeout);
+
+        Ok(env.decorate(run))
+    }
+}
+
 #[derive(Clone)]
 pub(super) struct ScheduleStaticSetup {
-    command: &'static str,
+    command: OsArg,
     name: &'static str,
     args: Vec<RString>,
     skipped: Option<String>,
@@ -199,13 +340,13 @@ pub(super) struct ScheduleStaticSetup {
 
 impl ScheduleStaticSetup {
     pub(super) fn new(
-        command: &'static str,
+        command: impl Into<OsArg>,
         name: &'static str,
         args: Vec<RString>,
         skipped: Option<String>,
     ) -> Self {
         Self {
-            command,
+            command: command.into(),
             name,
             args,
             skipped,
@@ -249,6 +390,7 @@ impl ScheduleRun {
         let shell = to_shell(shell.as_deref())?;
 
         let name = self.step.name.as_ref().map(|v| eval.eval(v)).transpose()?;
+        let (continue_on_error, timeout) = step_limits(&self.step, eval)?;
 
         let working_directory = self
             .step
@@ -262,26 +404,48 @@ impl ScheduleRun {
             .with_name(name.as_deref())
             .with_env(env.build_os_env())
             .with_skipped(skipped.clone())
-            .with_working_directory(working_directory);
+            .with_working_directory(working_directory)
+            .with_continue_on_error(continue_on_error)
+            .with_timeout(timeout);
 
         Ok(env.decorate(run))
     }
 }
 
-fn to_shell(shell: Option<&RStr>) -> Result<Shell> {
+/// Evaluate the `continue-on-error` and `timeout-minutes` settings of a step.
+fn step_limits(step: &Step, eval: &Eval) -> Result<(bool, Option<Duration>)> {
+    let continue_on_error = step
+        .continue_on_error
+        .as_deref()
+        .map(|source| workflows::eval_bool(eval, source))
+        .transpose()
+        .context("continue-on-error")?
+        .unwrap_or_default();
+
+    let timeout = step
+        .timeout_minutes
+        .as_deref()
+        .map(|source| workflows::eval_minutes(eval, source))
+        .transpose()
+        .context("timeout-minutes")?;
+
+    Ok((continue_on_error, timeout))
+}
+
+fn to_shell(shell: Option<&RStr>) -> Result<ScriptShell> {
     let Some(shell) = shell else {
-        return Ok(Shell::Bash);
+        return Ok(ScriptShell::Default);
     };
 
-    match shell.to_exposed().as_ref() {
-        "bash" => Ok(Shell::Bash),
-        "powershell" => Ok(Shell::Powershell),
-        _ => bail!("Unsupported shell: {shell}"),
-    }
+    ScriptShell::parse(shell.to_exposed().as_ref())
 }
 
 /// Check if a use should be skipped.
 fn should_skip_use(uses: &str) -> bool {
+    if uses.starts_with("./") {
+        return false;
+    }
+
     let Some((head, _)) = uses.split_once('@') else {
         return true;
     };
@@ -341,7 +505,16 @@ impl ScheduleUse {
             .map(|(k, v)| Ok((k.clone(), eval.eval(v)?.into_owned())))
             .collect::<Result<BTreeMap<_, _>>>()?;
 
-        if builtin_action(&self.uses, id, &with, skipped.as_deref(), &mut main)? {
+        let (continue_on_error, timeout) = step_limits(&self.step, eval)?;
+
+        if builtin_action(
+            &self.uses,
+            id,
+            &with,
+            skipped.as_deref(),
+            &mut main,
+            &mut post,
+        )? {
             return Ok(RunGroup { main, pre, post });
         }
 
@@ -352,11 +525,13 @@ impl ScheduleUse {
                 .repo_from_name()
                 .with_id(id)
                 .with_skipped(skipped.as_ref())
-                .with_inputs(with);
+                .with_inputs(with)
+                .with_continue_on_error(continue_on_error)
+                .with_timeout(timeout);
 
             let steps = runners.build(batch, &c)?;
 
-            main.push(steps.main);
+            main.extend(steps.main);
             pre.extend(steps.pre);
             post.extend(steps.post);
         }
@@ -377,12 +552,31 @@ struct RustToolchain<'a> {
     targets: Option<&'a RStr>,
 }
 
+/// Test if the given use refers to an action which is implemented as a
+/// builtin.
+fn is_builtin_action(uses: &RStr) -> bool {
+    let Some((head, _)) = uses.split_once('@') else {
+        return false;
+    };
+
+    let Some((user, repo)) = head.split_once('/') else {
+        return false;
+    };
+
+    (user.str_eq("dtolnay") && repo.str_eq("rust-toolchain"))
+        || (user.str_eq("actions-rs") && repo.str_eq("toolchain"))
+        || (user.str_eq("actions")
+            && (CacheOperation::from_action(repo).is_some()
+                || ArtifactOperation::from_action(repo).is_some()))
+}
+
 fn builtin_action(
     uses: &RStr,
     id: Option<&Rc<RStr>>,
     with: &BTreeMap<String, RString>,
     skipped: Option<&str>,
     main: &mut Vec<Schedule>,
+    post: &mut Vec<Schedule>,
 ) -> Result<bool> {
     let Some((head, version)) = uses.split_once('@') else {
         return Ok(false);
@@ -442,6 +636,37 @@ fn builtin_action(
         return Ok(true);
     }
 
+    if user.str_eq("actions")
+        && let Some(operations) = CacheOperation::from_action(repo)
+    {
+        // NB: The restore and the save share whether there was an exact match,
+        // since the save is skipped if so.
+        let hit = Rc::new(Cell::new(false));
+
+        for &operation in operations {
+            let cache = ScheduleCache::new(operation, id, with, skipped, hit.clone())?;
+
+            // NB: Only `actions/cache` defers saving to the end of the job,
+            // `actions/cache/save` saves when the step runs.
+            match operation {
+                CacheOperation::Save if operations.len() > 1 => {
+                    post.push(Schedule::Cache(cache));
+                }
+                _ => main.push(Schedule::Cache(cache)),
+            }
+        }
+
+        return Ok(true);
+    }
+
+    if user.str_eq("actions")
+        && let Some(operation) = ArtifactOperation::from_action(repo)
+    {
+        let artifact = ScheduleArtifact::new(operation, id, with, skipped)?;
+        main.push(Schedule::Artifact(artifact));
+        return Ok(true);
+    }
+
     Ok(false)
 }
 
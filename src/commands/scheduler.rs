@@ -1,5 +1,6 @@
 use std::collections::{BTreeMap, VecDeque};
 use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use anyhow::{Context, Result, bail};
@@ -9,7 +10,9 @@ use crate::config::Os;
 use crate::rstr::{RStr, RString};
 use crate::workflows::{Eval, Tree};
 
-use super::{Run, Schedule, ScheduleOutputs, Session, SessionConfig};
+use super::{
+    ResumeState, Run, Schedule, ScheduleOutputs, Session, SessionConfig, StepTracker, StepWindow,
+};
 
 struct StackEntry {
     name: Option<Rc<RStr>>,
@@ -30,6 +33,20 @@ pub(super) struct Scheduler {
     env: BTreeMap<String, String>,
     /// Current paths configured.
     paths: Vec<OsString>,
+    /// Outputs produced by groups which are not nested in another group.
+    outputs: BTreeMap<String, RString>,
+    /// Tree which groups that are not nested in another group are extended
+    /// with.
+    tree: Tree,
+    /// Tracks the steps of the job being run.
+    steps: StepTracker,
+    /// The number of schedules which were expanded from the current step of
+    /// the job, and have not been scheduled yet.
+    expanded: usize,
+    /// The file that the state of the job is persisted to before each step.
+    state_file: Option<PathBuf>,
+    /// The home directory of the job, if it is accessible from the host.
+    home: Option<PathBuf>,
 }
 
 impl Scheduler {
@@ -39,6 +56,12 @@ impl Scheduler {
             stack: Vec::new(),
             env: BTreeMap::new(),
             paths: Vec::new(),
+            outputs: BTreeMap::new(),
+            tree: Tree::new(),
+            steps: StepTracker::default(),
+            expanded: 0,
+            state_file: None,
+            home: None,
         }
     }
 
@@ -94,8 +117,55 @@ impl Scheduler {
         &self.paths
     }
 
+    /// Outputs produced by groups which are not nested in another group.
+    pub(super) fn outputs(&self) -> &BTreeMap<String, RString> {
+        &self.outputs
+    }
+
     pub(super) fn tree(&self) -> &Tree {
-        self.stack.last().map(|e| &e.tree).unwrap_or_default()
+        self.stack.last().map(|e| &e.tree).unwrap_or(&self.tree)
+    }
+
+    /// Set the tree which groups that are not nested in another group are
+    /// extended with.
+    pub(super) fn set_tree(&mut self, tree: Tree) {
+        self.tree = tree;
+    }
+
+    /// Start tracking the steps of a job, only running the steps in the given
+    /// window.
+    pub(super) fn set_step_window(&mut self, window: StepWindow) {
+        self.steps = StepTracker::new(window);
+        self.expanded = 0;
+    }
+
+    /// Set the file that the state of the job is persisted to before each
+    /// step.
+    pub(super) fn set_state_file(&mut self, path: Option<&Path>) {
+        self.state_file = path.map(Path::to_owned);
+    }
+
+    /// Set the home directory of the job, which paths prefixed with `~/` in
+    /// builtin actions like `actions/cache` are resolved against.
+    pub(super) fn set_home(&mut self, home: Option<PathBuf>) {
+        self.home = home;
+    }
+
+    /// The 1-based index of the step of the job currently being run.
+    pub(super) fn step_index(&self) -> usize {
+        self.steps.index()
+    }
+
+    /// Check that the steps selected were found, and remove the persisted
+    /// state since the job completed.
+    pub(super) fn finish_steps(&self) -> Result<()> {
+        self.steps.finish()?;
+
+        if let Some(path) = &self.state_file {
+            _ = std::fs::remove_file(path);
+        }
+
+        Ok(())
     }
 
     pub(super) fn env_mut(&mut self) -> &mut BTreeMap<String, String> {
@@ -110,18 +180,25 @@ impl Scheduler {
         Some(&mut self.stack.last_mut()?.tree)
     }
 
-    fn next_schedule(&mut self) -> Result<Option<Schedule>> {
+    /// Get the next schedule, and whether it starts a new step of the job.
+    fn next_schedule(&mut self) -> Result<Option<(Schedule, bool)>> {
         loop {
+            let depth = self.stack.len();
+
             let Some(e) = self.stack.last_mut() else {
-                return Ok(self.queue.pop_front());
+                return Ok(self.queue.pop_front().map(|item| (item, false)));
             };
 
             if let Some(item) = e.pre.pop_front() {
-                return Ok(Some(item));
+                return Ok(Some((item, false)));
             }
 
             if let Some(item) = e.main.pop_front() {
-                return Ok(Some(item));
+                // NB: Schedules in the group of the job are its steps, unless
+                // they were expanded from one of its steps.
+                let is_step = depth == 1 && self.expanded == 0;
+                self.expanded = self.expanded.saturating_sub(usize::from(depth == 1));
+                return Ok(Some((item, is_step)));
             }
 
             if let Some(item) = e.post.pop_front() {
@@ -135,9 +212,6 @@ impl Scheduler {
             if let Some(o) = e.outputs {
                 let raw_env = BTreeMap::new();
                 let env = o.env.extend_with(&e.tree, &raw_env)?;
-
-                let id = e.id.context("Missing id to store outputs")?;
-                let id = id.to_exposed();
                 let eval = Eval::new(&env.tree);
 
                 let mut values = BTreeMap::new();
@@ -146,7 +220,13 @@ impl Scheduler {
                     values.insert(key.clone(), eval.eval(&value)?.into_owned());
                 }
 
-                let tree = self.tree_mut().context("Missing scheduler tree")?;
+                let Some(tree) = self.tree_mut() else {
+                    self.outputs.extend(values);
+                    continue;
+                };
+
+                let id = e.id.context("Missing id to store outputs")?;
+                let id = id.to_exposed();
                 tree.insert_prefix(["steps", id.as_ref(), "outputs"], values);
             }
         }
@@ -162,7 +242,11 @@ impl Scheduler {
     where
         O: ?Sized + WriteColor,
     {
-        while let Some(schedule) = self.next_schedule()? {
+        while let Some((schedule, is_step)) = self.next_schedule()? {
+            if is_step && !self.begin_step(o, config, &schedule)? {
+                continue;
+            }
+
             schedule.prepare(session)?;
 
             // This will take care to synchronize any actions which are needed
@@ -180,9 +264,15 @@ impl Scheduler {
 
             match schedule {
                 Schedule::Group(g) => {
+                    let mut tree = g.tree.as_deref().cloned().unwrap_or_default();
+
+                    if self.stack.is_empty() {
+                        tree.extend(&self.tree);
+                    }
+
                     self.stack.push(StackEntry {
                         name: g.name,
-                        tree: Tree::new(),
+                        tree,
                         id: g.id,
                         main: g.steps.iter().cloned().collect(),
                         pre: VecDeque::new(),
@@ -202,13 +292,41 @@ impl Scheduler {
                     let run = node.build()?;
                     return Ok(Some(run));
                 }
+                Schedule::DockerAction(docker) => {
+                    let run = docker.build()?;
+                    return Ok(Some(run));
+                }
                 Schedule::Run(run) => {
                     let run = run.build(self.tree())?;
                     return Ok(Some(run));
                 }
+                Schedule::Cache(cache) => {
+                    let name = self.name(" / ", Some(cache.name()));
+                    let outputs = cache.run(o, config, name.as_deref(), self.home.as_deref())?;
+
+                    if let Some(id) = cache.id()
+                        && !outputs.is_empty()
+                    {
+                        self.insert_new_outputs(&id.to_exposed(), &outputs)?;
+                    }
+                }
+                Schedule::Artifact(artifact) => {
+                    let name = self.name(" / ", Some(artifact.name()));
+                    let outputs = artifact.run(o, config, name.as_deref(), self.home.as_deref())?;
+
+                    if let Some(id) = artifact.id()
+                        && !outputs.is_empty()
+                    {
+                        self.insert_new_outputs(&id.to_exposed(), &outputs)?;
+                    }
+                }
                 Schedule::Use(u) => {
                     let group = u.build(config, self.tree(), session.runners(), os)?;
 
+                    if self.stack.len() == 1 {
+                        self.expanded += group.main.len();
+                    }
+
                     let e = self
                         .stack
                         .last_mut()
@@ -232,6 +350,73 @@ impl Scheduler {
         Ok(None)
     }
 
+    /// Begin a new step of the job, returning `false` if it should be skipped.
+    ///
+    /// This persists the state of the job if a state file is configured, so
+    /// that the job can be resumed from the step.
+    fn begin_step<O>(
+        &mut self,
+        o: &mut O,
+        config: &SessionConfig<'_, '_>,
+        schedule: &Schedule,
+    ) -> Result<bool>
+    where
+        O: ?Sized + WriteColor,
+    {
+        let step = schedule.step();
+
+        if !self.steps.next(step) {
+            o.set_color(&config.colors.skip_cond)?;
+            write!(o, "# Skipping step #{}", self.steps.index())?;
+
+            if let Some(step) = step {
+                if let Some(name) = &step.name {
+                    write!(o, " ({name})")?;
+                } else if let Some(id) = &step.id {
+                    write!(o, " ({id})")?;
+                }
+            }
+
+            write!(o, ": not in the selected steps")?;
+            o.reset()?;
+            writeln!(o)?;
+            return Ok(false);
+        }
+
+        if let Some(path) = &self.state_file
+            && !config.dry_run
+        {
+            let mut state = ResumeState {
+                step: self.steps.index(),
+                env: self.env.clone(),
+                paths: self
+                    .paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect(),
+                ..ResumeState::default()
+            };
+
+            state.set_steps(self.tree());
+            state.save(path)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Insert the outcome and conclusion of the step with the given id.
+    pub(super) fn insert_step_result(
+        &mut self,
+        id: &str,
+        outcome: &str,
+        conclusion: &str,
+    ) -> Result<()> {
+        let tree = self.tree_mut().context("Missing scheduler tree")?;
+        tree.insert(["steps", id, "outcome"], outcome);
+        tree.insert(["steps", id, "conclusion"], conclusion);
+        Ok(())
+    }
+
     /// Insert new outputs with an associated id.
     pub(super) fn insert_new_outputs<'a>(
         &mut self,
@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::rstr::{self, RString};
+
+/// The directory in the kick configuration directory which holds secrets.
+const SECRETS_DIR: &str = "secrets";
+
+/// The default prefix of environment variables which provide secrets.
+pub(super) const DEFAULT_ENV_PREFIX: &str = "KICK_SECRET_";
+
+/// Provides values for the `secrets` context.
+///
+/// Secrets are read from the following sources, where later sources take
+/// precedence:
+/// * Files in the `secrets` directory of the kick configuration directory,
+///   where the name of the file is the name of the secret.
+/// * Environment variables with the given prefix, like `KICK_SECRET_<NAME>`.
+/// * Secrets specified on the command line.
+#[derive(Default)]
+pub(super) struct Secrets {
+    values: BTreeMap<String, RString>,
+}
+
+impl Secrets {
+    /// Load secrets from the given configuration directory.
+    ///
+    /// This refuses to load secrets from a directory which users other than
+    /// its owner can access.
+    pub(super) fn load_dir(&mut self, config: &Path) -> Result<()> {
+        let dir = config.join(SECRETS_DIR);
+
+        let metadata = match fs::metadata(&dir) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| anyhow!("{}", dir.display())),
+        };
+
+        if crate::fs::is_shared(&metadata) {
+            bail!(
+                "{}: Secrets directory is accessible by other users, restrict it with `chmod 700 {}`",
+                dir.display(),
+                dir.display()
+            );
+        }
+
+        let entries = fs::read_dir(&dir).with_context(|| anyhow!("{}", dir.display()))?;
+
+        for e in entries {
+            let e = e.with_context(|| anyhow!("{}", dir.display()))?;
+            let path = e.path();
+
+            if !e.file_type()?.is_file() {
+                continue;
+            }
+
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                tracing::warn!("{}: Ignoring secret with non-utf8 name", path.display());
+                continue;
+            };
+
+            if let Some(secret) = crate::env::read_secret_string(&path)? {
+                self.insert(name, secret.as_secret())
+                    .with_context(|| anyhow!("{}", path.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load secrets from environment variables with the given prefix.
+    pub(super) fn load_env(&mut self, prefix: &str) -> Result<()> {
+        self.load_vars(prefix, env::vars_os())
+    }
+
+    fn load_vars<I>(&mut self, prefix: &str, vars: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (OsString, OsString)>,
+    {
+        for (key, value) in vars {
+            let (Some(key), Some(value)) = (key.to_str(), value.to_str()) else {
+                continue;
+            };
+
+            if let Some(name) = key.strip_prefix(prefix)
+                && !name.is_empty()
+            {
+                self.insert(name, value)
+                    .with_context(|| anyhow!("Environment variable {key}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse a secret specified on the command line.
+    ///
+    /// This is either in the form `NAME=VALUE`, or `NAME` in which case the
+    /// value is taken from the environment variable with the same name.
+    pub(super) fn parse(&mut self, secret: &str) -> Result<()> {
+        let (name, value) = match secret.split_once('=') {
+            Some((name, value)) => (name, value.to_owned()),
+            None => match env::var(secret) {
+                Ok(value) => (secret, value),
+                Err(..) => bail!("Missing environment variable for secret `{secret}`"),
+            },
+        };
+
+        self.insert(name, &value)
+    }
+
+    fn insert(&mut self, name: &str, value: &str) -> Result<()> {
+        let Some(secret) = RString::redacted(value) else {
+            bail!("Secret `{name}` may only contain ASCII characters");
+        };
+
+        // NB: Secrets are masked if steps happen to print them.
+        rstr::add_mask(value);
+        self.values.insert(name.to_owned(), secret);
+        Ok(())
+    }
+
+    /// Iterate over all secrets.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (String, RString)> + '_ {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+    }
+}
@@ -0,0 +1,114 @@
+use std::ffi::OsString;
+
+use super::*;
+
+// NB: Secrets are masked across the process, so values have to be unique to
+// not mask the output of other tests.
+
+fn exposed(secrets: &Secrets) -> Vec<(String, String)> {
+    secrets
+        .iter()
+        .map(|(name, value)| (name, value.to_exposed().into_owned()))
+        .collect()
+}
+
+fn vars(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+    vars.iter()
+        .map(|(key, value)| (OsString::from(key), OsString::from(value)))
+        .collect()
+}
+
+#[test]
+fn parse() -> Result<()> {
+    let mut secrets = Secrets::default();
+    secrets.parse("TOKEN=kick-secrets-parse=value")?;
+
+    assert_eq!(
+        exposed(&secrets),
+        [("TOKEN".to_owned(), "kick-secrets-parse=value".to_owned())]
+    );
+
+    let (_, value) = secrets.iter().next().unwrap();
+    assert_eq!(value.to_string(), "***");
+
+    let error = secrets
+        .parse("KICK_SECRETS_TEST_MISSING_VARIABLE")
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Missing environment variable for secret `KICK_SECRETS_TEST_MISSING_VARIABLE`"
+    );
+
+    let error = secrets.parse("UNICODE=kick-secrets-värde").unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Secret `UNICODE` may only contain ASCII characters"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn load_env() -> Result<()> {
+    let mut secrets = Secrets::default();
+
+    secrets.load_vars(
+        DEFAULT_ENV_PREFIX,
+        vars(&[
+            ("KICK_SECRET_TOKEN", "kick-secrets-env-token"),
+            ("KICK_SECRET_", "kick-secrets-env-empty"),
+            ("OTHER_TOKEN", "kick-secrets-env-other"),
+        ]),
+    )?;
+
+    assert_eq!(
+        exposed(&secrets),
+        [("TOKEN".to_owned(), "kick-secrets-env-token".to_owned())]
+    );
+
+    // Secrets specified later take precedence.
+    secrets.parse("TOKEN=kick-secrets-env-override")?;
+
+    assert_eq!(
+        exposed(&secrets),
+        [("TOKEN".to_owned(), "kick-secrets-env-override".to_owned())]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn load_dir() -> Result<()> {
+    let config = tempfile::tempdir()?;
+
+    let mut secrets = Secrets::default();
+    secrets.load_dir(config.path())?;
+    assert!(exposed(&secrets).is_empty());
+
+    let dir = config.path().join(SECRETS_DIR);
+    fs::create_dir(&dir)?;
+    fs::write(dir.join("TOKEN"), "# A comment\nkick-secrets-dir-token\n")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755))?;
+        let error = secrets.load_dir(config.path()).unwrap_err();
+        assert!(error.to_string().contains("chmod 700"), "{error}");
+        assert!(exposed(&secrets).is_empty());
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    secrets.load_dir(config.path())?;
+
+    assert_eq!(
+        exposed(&secrets),
+        [("TOKEN".to_owned(), "kick-secrets-dir-token".to_owned())]
+    );
+
+    Ok(())
+}
@@ -1,17 +1,22 @@
+#[cfg(test)]
+mod tests;
+
 use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::path::PathBuf;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::str;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 
 use crate::config::{Distribution, Os};
 use crate::ctxt::Ctxt;
 use crate::model::Repo;
 use crate::rstr::{RStr, RString};
 use crate::shell::Shell;
-use crate::workflows::WorkflowManifests;
+use crate::workflows::{Tree, WorkflowManifests};
 
-use super::{Colors, LoadedWorkflows, RunOn};
+use super::{Colors, LoadedWorkflows, RunOn, Secrets, artifacts, cache};
 
 const GITHUB_SERVER: &str = "https://github.com";
 
@@ -25,6 +30,7 @@ pub(crate) struct SessionConfig<'a, 'cx> {
     pub(super) env: BTreeMap<String, String>,
     pub(super) env_passthrough: BTreeSet<String>,
     pub(super) run_on: Vec<(RunOn, Os)>,
+    pub(super) runners: BTreeMap<String, String>,
     pub(super) verbose: u8,
     pub(super) dry_run: bool,
     pub(super) exposed: bool,
@@ -34,6 +40,26 @@ pub(crate) struct SessionConfig<'a, 'cx> {
     pub(super) keep: bool,
     pub(super) github_server: Option<String>,
     pub(super) github_token: Option<Box<RStr>>,
+    /// Values of the `github` context which are derived from the repo.
+    pub(super) github: BTreeMap<String, RString>,
+    /// The event to simulate, if one was explicitly specified.
+    pub(super) event: Option<String>,
+    /// The payload of the simulated event.
+    pub(super) event_payload: serde_json::Map<String, serde_json::Value>,
+    /// Inputs of the simulated event.
+    pub(super) inputs: BTreeMap<String, String>,
+    /// The file the event payload has been written to.
+    pub(super) event_path: Option<PathBuf>,
+    /// Values of the `secrets` context.
+    pub(super) secrets: Secrets,
+    /// Values of the `vars` context.
+    pub(super) vars: BTreeMap<String, String>,
+    /// The limit in bytes of the total size of entries stored by the builtin
+    /// `actions/cache`.
+    pub(super) cache_limit: u64,
+    /// The directory where artifacts of the run are stored, if it has been
+    /// overridden.
+    pub(super) artifacts_dir: Option<PathBuf>,
 }
 
 impl<'a, 'cx> SessionConfig<'a, 'cx> {
@@ -48,6 +74,7 @@ impl<'a, 'cx> SessionConfig<'a, 'cx> {
             env: BTreeMap::new(),
             env_passthrough: BTreeSet::new(),
             run_on: Vec::new(),
+            runners: BTreeMap::new(),
             verbose: 0,
             dry_run: false,
             exposed: false,
@@ -60,6 +87,15 @@ impl<'a, 'cx> SessionConfig<'a, 'cx> {
                 .github_auth()
                 .and_then(|t| RString::redacted(t.as_secret()))
                 .map(Box::<RStr>::from),
+            github: BTreeMap::new(),
+            event: None,
+            event_payload: serde_json::Map::new(),
+            inputs: BTreeMap::new(),
+            event_path: None,
+            secrets: Secrets::default(),
+            vars: BTreeMap::new(),
+            cache_limit: cache::DEFAULT_CACHE_LIMIT,
+            artifacts_dir: None,
         }
     }
 
@@ -73,6 +109,40 @@ impl<'a, 'cx> SessionConfig<'a, 'cx> {
         self.github_token.as_deref()
     }
 
+    /// Test if the session is a dry run.
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Test if temporary files should be kept around.
+    pub(crate) fn is_keep(&self) -> bool {
+        self.keep
+    }
+
+    /// Set the path that batches are run in.
+    pub(crate) fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
+    /// Get the directory where artifacts uploaded during the run are stored.
+    pub(crate) fn artifacts_dir(&self) -> Result<PathBuf> {
+        if let Some(dir) = &self.artifacts_dir {
+            return Ok(dir.clone());
+        }
+
+        let cache_dir = self.cx.paths.cache.context("Missing cache directory")?;
+
+        Ok(cache_dir
+            .join(artifacts::ARTIFACTS_DIR)
+            .join(format!("kick-{}", self.process_id)))
+    }
+
+    /// Set the directory where artifacts are stored, so that it can be shared
+    /// with the parent process.
+    pub(crate) fn set_artifacts_dir(&mut self, dir: &Path) {
+        self.artifacts_dir = Some(dir.to_owned());
+    }
+
     /// Set an environment variable.
     pub(crate) fn set_env(&mut self, key: impl AsRef<str>, env: impl AsRef<str>) {
         self.env
@@ -109,23 +179,343 @@ impl<'a, 'cx> SessionConfig<'a, 'cx> {
         Ok(())
     }
 
+    /// Load the `github` context from the git repository of the given repo.
+    ///
+    /// Values which can't be determined are left out.
+    pub(super) fn load_github_context(&mut self, repo: &Repo) {
+        let mut github = BTreeMap::new();
+
+        github.insert("event_name", self.event_name().to_owned());
+        github.insert("server_url", self.github_server().to_owned());
+
+        if let Some(path) = repo.repo() {
+            github.insert("repository", path.to_string());
+            github.insert("repository_owner", path.owner.to_owned());
+        }
+
+        if let Some(git) = self.cx.system.git.first() {
+            let dir = &self.path;
+
+            match git.rev_parse(dir, "HEAD") {
+                Ok(sha) => {
+                    github.insert("sha", sha);
+                }
+                Err(error) => {
+                    tracing::trace!(?error, "Failed to get HEAD commit");
+                }
+            }
+
+            let head = match git.symbolic_ref(dir) {
+                Ok(Some(head)) => Some((head, "branch")),
+                Ok(None) => git
+                    .tags_at(dir, "HEAD")
+                    .ok()
+                    .and_then(|tags| tags.into_iter().next())
+                    .map(|tag| (format!("refs/tags/{tag}"), "tag")),
+                Err(error) => {
+                    tracing::trace!(?error, "Failed to get HEAD reference");
+                    None
+                }
+            };
+
+            if let Some((head, ref_type)) = head {
+                github.extend(ref_context(head, ref_type));
+            }
+
+            let actor = ["github.user", "user.name"]
+                .into_iter()
+                .find_map(|key| git.config_get(dir, key).ok().flatten());
+
+            if let Some(actor) = actor {
+                github.insert("actor", actor);
+            }
+        }
+
+        self.github = github
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), RString::from(value)))
+            .collect();
+    }
+
+    /// Construct the values of the `github` context.
+    pub(super) fn github_context(&self) -> BTreeMap<String, RString> {
+        let mut github = self.github.clone();
+        github.insert(String::from("server"), RString::from(self.github_server()));
+
+        let workspace = std::path::absolute(&self.path).unwrap_or_else(|_| self.path.clone());
+
+        github.insert(
+            String::from("workspace"),
+            RString::from(workspace.to_string_lossy()),
+        );
+
+        if let Some(token) = self.github_token() {
+            github.insert(String::from("token"), token.to_owned());
+        }
+
+        if let Some(path) = &self.event_path {
+            github.insert(
+                String::from("event_path"),
+                RString::from(path.to_string_lossy()),
+            );
+        }
+
+        github
+    }
+
+    /// Construct a tree containing the `github` context, including the payload
+    /// of the simulated event.
+    pub(super) fn github_tree(&self) -> Tree {
+        let mut tree = Tree::new();
+        tree.insert_prefix(["github"], self.github_context());
+
+        let mut key = vec![String::from("github"), String::from("event")];
+
+        for (name, value) in &self.event_payload {
+            key.push(name.clone());
+            insert_json(&mut tree, &mut key, value);
+            key.pop();
+        }
+
+        tree
+    }
+
+    /// Get the name of the simulated event.
+    pub(super) fn event_name(&self) -> &str {
+        self.event.as_deref().unwrap_or("push")
+    }
+
+    /// Fill in the payload of the simulated event and write it to the file
+    /// exposed through `GITHUB_EVENT_PATH`.
+    pub(super) fn prepare_event(&mut self) -> Result<()> {
+        let mut payload = mem::take(&mut self.event_payload);
+        fill_event_payload(&mut payload, self.event_name(), &self.github, &self.inputs);
+        self.event_payload = payload;
+
+        let cache_dir = self.cx.paths.cache.context("Missing cache directory")?;
+        let state_dir = cache_dir.join("state");
+        fs::create_dir_all(&state_dir)
+            .with_context(|| anyhow!("Creating {}", state_dir.display()))?;
+
+        let path = state_dir.join(format!("kick-{}-event.json", self.process_id));
+        let payload = serde_json::to_vec_pretty(&self.event_payload)?;
+        fs::write(&path, payload).with_context(|| anyhow!("Writing {}", path.display()))?;
+        self.event_path = Some(path);
+        Ok(())
+    }
+
+    /// Get the git reference which the simulated event applies to.
+    fn event_ref(&self) -> Option<String> {
+        let payload = &self.event_payload;
+
+        if self.event_name() == "pull_request"
+            && let Some(base) = payload
+                .get("pull_request")
+                .and_then(|pr| pr.get("base"))
+                .and_then(|base| base.get("ref"))
+                .and_then(|r| r.as_str())
+        {
+            return Some(format!("refs/heads/{base}"));
+        }
+
+        if let Some(r) = payload.get("ref").and_then(|r| r.as_str()) {
+            return Some(r.to_owned());
+        }
+
+        Some(self.github.get("ref")?.to_exposed().into_owned())
+    }
+
+    /// Get the paths which have changed for the simulated event.
+    ///
+    /// This compares the working tree against the base of the event if it's
+    /// available in the payload, or the upstream branch or the parent of HEAD
+    /// otherwise.
+    fn changed_paths(&self) -> Result<Vec<String>> {
+        let Some(git) = self.cx.system.git.first() else {
+            bail!("Git is required to evaluate path filters");
+        };
+
+        let payload = &self.event_payload;
+
+        let base = match self.event_name() {
+            "push" => payload.get("before").and_then(|v| v.as_str()),
+            "pull_request" => payload
+                .get("pull_request")
+                .and_then(|pr| pr.get("base"))
+                .and_then(|base| base.get("sha"))
+                .and_then(|sha| sha.as_str()),
+            _ => None,
+        };
+
+        let base = base.filter(|base| !base.chars().all(|c| c == '0'));
+
+        let base = match base {
+            Some(base) => base,
+            None if git.rev_parse(&self.path, "@{upstream}").is_ok() => "@{upstream}",
+            None => "HEAD~1",
+        };
+
+        git.diff_names(&self.path, base)
+            .with_context(|| anyhow!("Getting paths changed since {base}"))
+    }
+
     /// Load workflows from a repository.
+    ///
+    /// If an event has been specified, workflows which would not be triggered
+    /// by it are marked as such.
     pub(crate) fn load_github_workflows(&self, repo: &Repo) -> Result<LoadedWorkflows<'_, 'cx>> {
         let mut workflows = Vec::new();
         let wfs = WorkflowManifests::new(self.cx, repo)?;
 
+        let mut base = self.github_tree();
+        base.insert_prefix(["secrets"], self.secrets.iter());
+        base.insert_prefix(["vars"], self.vars.clone());
+        let event = self.event_name();
+        let reference = self.event_ref();
+        let mut changed = None;
+
         for workflow in wfs.workflows() {
             let workflow = workflow?;
+            let triggers = workflow.triggers()?;
+
+            let mut tree = base.clone();
+            let mut not_triggered = None;
+
+            let inputs = triggers.inputs(event);
+
+            if !inputs.is_empty() {
+                let mut values = BTreeMap::new();
+
+                for input in inputs {
+                    match self.inputs.get(&input.name).or(input.default.as_ref()) {
+                        Some(value) => {
+                            values.insert(input.name.clone(), value.clone());
+                        }
+                        None if input.required && not_triggered.is_none() => {
+                            not_triggered =
+                                Some(format!("missing required input `{}`", input.name));
+                        }
+                        None => {}
+                    }
+                }
+
+                tree.insert_prefix(["github", "event", "inputs"], values.clone());
+                tree.insert_prefix(["inputs"], values);
+            }
+
+            if self.event.is_some() && not_triggered.is_none() {
+                not_triggered = triggers.test(event, reference.as_deref(), || match &changed {
+                    Some(changed) => Ok(Vec::clone(changed)),
+                    None => {
+                        let paths = self.changed_paths()?;
+                        changed = Some(paths.clone());
+                        Ok(paths)
+                    }
+                })?;
+            }
 
             let mut jobs = Vec::new();
 
-            for job in workflow.jobs(&self.matrix_ignore, &self.matrix_filter)? {
+            for job in workflow.jobs(&tree, &self.matrix_ignore, &self.matrix_filter)? {
                 jobs.push(job);
             }
 
-            workflows.push((workflow, jobs));
+            workflows.push((workflow, jobs, not_triggered));
         }
 
         Ok(LoadedWorkflows::new(self, workflows))
     }
 }
+
+/// Insert a JSON value into a tree, where objects and arrays become nested
+/// keys.
+fn insert_json(tree: &mut Tree, key: &mut Vec<String>, value: &serde_json::Value) {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => {}
+        Value::Bool(value) => tree.insert(&*key, value.to_string()),
+        Value::Number(value) => tree.insert(&*key, value.to_string()),
+        Value::String(value) => tree.insert(&*key, value),
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                key.push(index.to_string());
+                insert_json(tree, key, value);
+                key.pop();
+            }
+        }
+        Value::Object(values) => {
+            for (name, value) in values {
+                key.push(name.clone());
+                insert_json(tree, key, value);
+                key.pop();
+            }
+        }
+    }
+}
+
+/// Construct the `ref`, `ref_name` and `ref_type` values of the `github`
+/// context from the full name of the reference HEAD points to.
+fn ref_context(head: String, ref_type: &str) -> [(&'static str, String); 3] {
+    let name = head
+        .strip_prefix("refs/heads/")
+        .or_else(|| head.strip_prefix("refs/tags/"))
+        .unwrap_or(&head)
+        .to_owned();
+
+    [
+        ("ref_name", name),
+        ("ref_type", ref_type.to_owned()),
+        ("ref", head),
+    ]
+}
+
+/// Fill in the parts of the payload of a simulated event which can be derived
+/// from the `github` context and the inputs, unless they've been provided.
+fn fill_event_payload(
+    payload: &mut serde_json::Map<String, serde_json::Value>,
+    event_name: &str,
+    github: &BTreeMap<String, RString>,
+    inputs: &BTreeMap<String, String>,
+) {
+    use serde_json::Value;
+
+    let get = |key: &str| github.get(key).map(|v| Value::from(v.to_exposed()));
+
+    if let Some(value) = get("ref") {
+        payload.entry("ref").or_insert(value);
+    }
+
+    if event_name == "push"
+        && let Some(value) = get("sha")
+    {
+        payload.entry("after").or_insert(value);
+    }
+
+    if event_name == "workflow_dispatch" {
+        let inputs_value = payload
+            .entry("inputs")
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+
+        if let Value::Object(values) = inputs_value {
+            for (key, value) in inputs {
+                values.insert(key.clone(), Value::from(value.as_str()));
+            }
+        }
+    }
+
+    if let (Some(repository), Some(owner)) = (get("repository"), get("repository_owner")) {
+        payload.entry("repository").or_insert_with(|| {
+            serde_json::json!({
+                "full_name": repository,
+                "owner": { "login": owner },
+            })
+        });
+    }
+
+    if let Some(value) = get("actor") {
+        payload
+            .entry("sender")
+            .or_insert_with(|| serde_json::json!({ "login": value }));
+    }
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+fn context(head: &str, ref_type: &str) -> Vec<(&'static str, String)> {
+    ref_context(head.to_owned(), ref_type).into()
+}
+
+#[test]
+fn ref_from_head() {
+    assert_eq!(
+        context("refs/heads/feature/x", "branch"),
+        [
+            ("ref_name", "feature/x".to_owned()),
+            ("ref_type", "branch".to_owned()),
+            ("ref", "refs/heads/feature/x".to_owned()),
+        ]
+    );
+
+    assert_eq!(
+        context("refs/tags/1.0.0", "tag"),
+        [
+            ("ref_name", "1.0.0".to_owned()),
+            ("ref_type", "tag".to_owned()),
+            ("ref", "refs/tags/1.0.0".to_owned()),
+        ]
+    );
+
+    assert_eq!(
+        context("refs/remotes/origin/main", "branch")[0],
+        ("ref_name", "refs/remotes/origin/main".to_owned())
+    );
+}
+
+fn github(values: &[(&str, &str)]) -> BTreeMap<String, RString> {
+    values
+        .iter()
+        .map(|(key, value)| (key.to_string(), RString::from(*value)))
+        .collect()
+}
+
+#[test]
+fn event_payload() {
+    let github = github(&[
+        ("actor", "octocat"),
+        ("ref", "refs/heads/main"),
+        ("repository", "udoprog/kick"),
+        ("repository_owner", "udoprog"),
+        ("sha", "abcdef"),
+    ]);
+
+    let mut payload = serde_json::Map::new();
+    payload.insert("ref".to_owned(), serde_json::json!("refs/heads/provided"));
+    fill_event_payload(&mut payload, "push", &github, &BTreeMap::new());
+
+    // NB: Values in the provided payload are kept.
+    assert_eq!(
+        serde_json::Value::Object(payload),
+        serde_json::json!({
+            "ref": "refs/heads/provided",
+            "after": "abcdef",
+            "repository": {
+                "full_name": "udoprog/kick",
+                "owner": { "login": "udoprog" },
+            },
+            "sender": { "login": "octocat" },
+        })
+    );
+
+    let mut inputs = BTreeMap::new();
+    inputs.insert("level".to_owned(), "debug".to_owned());
+
+    let mut payload = serde_json::Map::new();
+    payload.insert("inputs".to_owned(), serde_json::json!({ "other": "x" }));
+    fill_event_payload(&mut payload, "workflow_dispatch", &github, &inputs);
+
+    assert_eq!(payload.get("after"), None);
+    assert_eq!(
+        payload.get("inputs"),
+        Some(&serde_json::json!({ "level": "debug", "other": "x" }))
+    );
+}
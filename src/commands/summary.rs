@@ -0,0 +1,408 @@
+#[cfg(test)]
+mod tests;
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use termcolor::WriteColor;
+
+use super::{Colors, SessionConfig};
+
+/// Concatenate the step summaries written to `GITHUB_STEP_SUMMARY`.
+fn join_summaries(summaries: &[String]) -> String {
+    let mut output = String::new();
+
+    for summary in summaries {
+        if !output.is_empty() && !output.ends_with("\n\n") {
+            output.push_str(if output.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+
+        output.push_str(summary);
+    }
+
+    output
+}
+
+/// Write the raw markdown of step summaries to the given path.
+pub(crate) fn write_summary_file(path: &Path, summaries: &[String]) -> Result<()> {
+    fs::write(path, join_summaries(summaries))
+        .with_context(|| anyhow!("Writing {}", path.display()))
+}
+
+/// Render step summaries written to `GITHUB_STEP_SUMMARY` to the terminal.
+pub(crate) fn write_summaries<O>(
+    o: &mut O,
+    c: &SessionConfig<'_, '_>,
+    summaries: &[String],
+) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    if summaries.is_empty() {
+        return Ok(());
+    }
+
+    o.set_color(&c.colors.title)?;
+    writeln!(o, "# Step summary:")?;
+    o.reset()?;
+
+    render(o, &c.colors, &join_summaries(summaries))
+}
+
+/// Render markdown to the terminal.
+fn render<O>(o: &mut O, colors: &Colors, markdown: &str) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    let mut r = Renderer {
+        o,
+        colors,
+        lists: Vec::new(),
+        table: None,
+        link: None,
+        at_line_start: true,
+        block_written: false,
+        in_code_block: false,
+    };
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS) {
+        r.event(event)?;
+    }
+
+    if !r.at_line_start {
+        writeln!(r.o)?;
+    }
+
+    Ok(())
+}
+
+/// A table which is being collected, since its columns have to be aligned
+/// before it can be written.
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    header: bool,
+}
+
+struct Renderer<'a, O>
+where
+    O: ?Sized,
+{
+    o: &'a mut O,
+    colors: &'a Colors,
+    /// Stack of lists, with the next number of ordered lists.
+    lists: Vec<Option<u64>>,
+    table: Option<Table>,
+    /// The destination of the link being written.
+    link: Option<String>,
+    at_line_start: bool,
+    /// Whether a block has been written, used to separate blocks.
+    block_written: bool,
+    in_code_block: bool,
+}
+
+impl<O> Renderer<'_, O>
+where
+    O: ?Sized + WriteColor,
+{
+    fn event(&mut self, event: Event<'_>) -> Result<()> {
+        match event {
+            Event::Start(tag) => self.start(tag)?,
+            Event::End(tag) => self.end(tag)?,
+            Event::Text(text) => {
+                if self.in_code_block {
+                    for line in text.lines() {
+                        self.indent()?;
+                        self.o.set_color(&self.colors.dim)?;
+                        write!(self.o, "    {line}")?;
+                        self.o.reset()?;
+                        self.newline()?;
+                    }
+                } else {
+                    self.text(&text)?;
+                }
+            }
+            Event::Code(code) => {
+                if let Some(cell) = self.cell() {
+                    cell.push_str(&code);
+                } else {
+                    self.indent()?;
+                    self.o.set_color(&self.colors.matrix)?;
+                    write!(self.o, "{code}")?;
+                    self.o.reset()?;
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                // NB: Html is commonly used for things like `<details>`, which
+                // we can't do anything useful with.
+                if html.trim().is_empty() || self.table.is_some() {
+                    return Ok(());
+                }
+
+                self.indent()?;
+                self.o.set_color(&self.colors.dim)?;
+                write!(self.o, "{}", html.trim_end())?;
+                self.o.reset()?;
+
+                if html.ends_with('\n') {
+                    self.newline()?;
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(cell) = self.cell() {
+                    cell.push(' ');
+                } else {
+                    self.newline()?;
+                }
+            }
+            Event::Rule => {
+                self.block()?;
+                self.o.set_color(&self.colors.dim)?;
+                write!(self.o, "---")?;
+                self.o.reset()?;
+                self.newline()?;
+            }
+            Event::TaskListMarker(checked) => {
+                self.text(if checked { "[x] " } else { "[ ] " })?;
+            }
+            Event::FootnoteReference(name) => {
+                self.text(&format!("[^{name}]"))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self, tag: Tag<'_>) -> Result<()> {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.block()?;
+                self.o.set_color(&self.colors.title)?;
+                write!(self.o, "{} ", "#".repeat(level as usize))?;
+            }
+            Tag::Paragraph if self.lists.is_empty() => {
+                self.block()?;
+            }
+            Tag::CodeBlock(kind) => {
+                self.block()?;
+                self.in_code_block = true;
+
+                if let CodeBlockKind::Fenced(lang) = kind
+                    && !lang.is_empty()
+                {
+                    self.indent()?;
+                    self.o.set_color(&self.colors.dim)?;
+                    write!(self.o, "    ({lang})")?;
+                    self.o.reset()?;
+                    self.newline()?;
+                }
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.block()?;
+                } else if !self.at_line_start {
+                    self.newline()?;
+                }
+
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                if !self.at_line_start {
+                    self.newline()?;
+                }
+
+                let depth = self.lists.len().saturating_sub(1);
+                write!(self.o, "{:width$}", "", width = depth * 2)?;
+
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        write!(self.o, "{n}. ")?;
+                        *n += 1;
+                    }
+                    _ => write!(self.o, "- ")?,
+                }
+
+                self.at_line_start = false;
+            }
+            Tag::Table(..) => {
+                self.block()?;
+                self.table = Some(Table::default());
+            }
+            Tag::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                    table.header = true;
+                }
+            }
+            Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => {
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(String::new());
+                }
+            }
+            Tag::Link { dest_url, .. } => {
+                self.link = Some(dest_url.into_string());
+            }
+            Tag::BlockQuote(..) => {
+                self.block()?;
+                self.o.set_color(&self.colors.dim)?;
+                write!(self.o, "> ")?;
+                self.o.reset()?;
+                self.at_line_start = false;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self, tag: TagEnd) -> Result<()> {
+        match tag {
+            TagEnd::Heading(..) => {
+                self.o.reset()?;
+                self.newline()?;
+            }
+            TagEnd::Paragraph if !self.at_line_start => {
+                self.newline()?;
+            }
+            TagEnd::CodeBlock => {
+                self.in_code_block = false;
+            }
+            TagEnd::List(..) => {
+                self.lists.pop();
+
+                if !self.at_line_start {
+                    self.newline()?;
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.write_table(&table)?;
+                }
+            }
+            TagEnd::Link => {
+                if let Some(link) = self.link.take() {
+                    self.o.set_color(&self.colors.dim)?;
+                    self.text(&format!(" ({link})"))?;
+                    self.o.reset()?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Get the table cell currently being written to, if any.
+    fn cell(&mut self) -> Option<&mut String> {
+        self.table.as_mut()?.rows.last_mut()?.last_mut()
+    }
+
+    fn text(&mut self, text: &str) -> Result<()> {
+        if let Some(cell) = self.cell() {
+            cell.push_str(text);
+            return Ok(());
+        }
+
+        self.indent()?;
+        write!(self.o, "{text}")?;
+        Ok(())
+    }
+
+    fn write_table(&mut self, table: &Table) -> Result<()> {
+        let mut widths = Vec::new();
+
+        for row in &table.rows {
+            for (n, cell) in row.iter().enumerate() {
+                let width = cell.chars().count();
+
+                match widths.get_mut(n) {
+                    Some(w) => *w = width.max(*w),
+                    None => widths.push(width),
+                }
+            }
+        }
+
+        for (n, row) in table.rows.iter().enumerate() {
+            let header = n == 0 && table.header;
+
+            if header {
+                self.o.set_color(&self.colors.title)?;
+            }
+
+            for (i, width) in widths.iter().enumerate() {
+                let cell = row.get(i).map(String::as_str).unwrap_or_default();
+
+                if i == 0 {
+                    write!(self.o, "{cell:width$}")?;
+                } else if i + 1 == widths.len() {
+                    write!(self.o, " | {cell}")?;
+                } else {
+                    write!(self.o, " | {cell:width$}")?;
+                }
+            }
+
+            if header {
+                self.o.reset()?;
+            }
+
+            writeln!(self.o)?;
+
+            if header {
+                let line = widths
+                    .iter()
+                    .map(|w| "-".repeat(*w))
+                    .collect::<Vec<_>>()
+                    .join("-|-");
+
+                self.o.set_color(&self.colors.dim)?;
+                write!(self.o, "{line}")?;
+                self.o.reset()?;
+                writeln!(self.o)?;
+            }
+        }
+
+        self.at_line_start = true;
+        Ok(())
+    }
+
+    /// Start a new block, separating it from the previous one.
+    fn block(&mut self) -> Result<()> {
+        if !self.at_line_start {
+            self.newline()?;
+        }
+
+        if self.block_written {
+            writeln!(self.o)?;
+        }
+
+        self.block_written = true;
+        Ok(())
+    }
+
+    /// Indent the current line if we're nested inside of lists.
+    fn indent(&mut self) -> Result<()> {
+        if self.at_line_start {
+            if !self.lists.is_empty() {
+                write!(self.o, "{:width$}", "", width = self.lists.len() * 2)?;
+            }
+
+            self.at_line_start = false;
+        }
+
+        Ok(())
+    }
+
+    fn newline(&mut self) -> Result<()> {
+        writeln!(self.o)?;
+        self.at_line_start = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use termcolor::NoColor;
+
+use super::*;
+
+fn rendered(markdown: &str) -> String {
+    let mut o = NoColor::new(Vec::new());
+    render(&mut o, &Colors::new(), markdown).unwrap();
+    String::from_utf8(o.into_inner()).unwrap()
+}
+
+#[test]
+fn join() {
+    let summaries = ["a", "b\n", "c\n\n", "d"].map(String::from);
+    assert_eq!(join_summaries(&summaries), "a\n\nb\n\nc\n\nd");
+    assert_eq!(join_summaries(&[]), "");
+}
+
+#[test]
+fn render_text() {
+    assert_eq!(
+        rendered(
+            "## Results\n\nSome *text* with `code` and a [link](https://example.com).\n\n- one\n- two\n  1. nested\n"
+        ),
+        "## Results\n\nSome text with code and a link (https://example.com).\n\n- one\n- two\n  1. nested\n"
+    );
+
+    assert_eq!(
+        rendered("```\nlet x = 1;\n```\n\n---\n- [x] done\n"),
+        "    let x = 1;\n\n---\n\n- [x] done\n"
+    );
+}
+
+#[test]
+fn render_table() {
+    assert_eq!(
+        rendered("| Name | Status |\n|------|--------|\n| build | ok |\n| test-long | failed |\n"),
+        "Name      | Status\n----------|-------\nbuild     | ok\ntest-long | failed\n"
+    );
+}
@@ -0,0 +1,418 @@
+#[cfg(test)]
+mod tests;
+
+use std::borrow::Cow;
+use std::str;
+
+use anyhow::{Result, bail};
+use termcolor::WriteColor;
+
+use crate::rstr;
+
+use super::{Session, SessionConfig};
+
+/// The level of an annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AnnotationLevel {
+    Error,
+    Warning,
+    Notice,
+}
+
+impl AnnotationLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            AnnotationLevel::Error => "error",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Notice => "notice",
+        }
+    }
+}
+
+/// An annotation emitted by a step through `::error::`, `::warning::` or
+/// `::notice::`.
+pub(crate) struct Annotation {
+    level: AnnotationLevel,
+    /// The name of the step which emitted the annotation.
+    step: Option<String>,
+    title: Option<String>,
+    file: Option<String>,
+    line: Option<String>,
+    col: Option<String>,
+    message: String,
+}
+
+impl Annotation {
+    /// Write the location of the annotation, like `src/main.rs:10:4`.
+    fn location(&self) -> Option<String> {
+        let mut location = self.file.clone()?;
+
+        if let Some(line) = &self.line {
+            location.push(':');
+            location.push_str(line);
+
+            if let Some(col) = &self.col {
+                location.push(':');
+                location.push_str(col);
+            }
+        }
+
+        Some(location)
+    }
+}
+
+/// A workflow command printed by a step, like `::group::Title`.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum WorkflowCommand<'a> {
+    Group(Cow<'a, str>),
+    EndGroup,
+    Annotation {
+        level: AnnotationLevel,
+        properties: Vec<(&'a str, Cow<'a, str>)>,
+        message: Cow<'a, str>,
+    },
+    AddMask(Cow<'a, str>),
+    Debug(Cow<'a, str>),
+    StopCommands(&'a str),
+    SetOutput {
+        name: Cow<'a, str>,
+        value: Cow<'a, str>,
+    },
+}
+
+impl<'a> WorkflowCommand<'a> {
+    /// Parse a workflow command from a line of output.
+    ///
+    /// Lines which aren't commands, or which are commands that we don't
+    /// interpret, result in `None`.
+    pub(super) fn parse(line: &'a str) -> Option<Self> {
+        let rest = line.trim_start().strip_prefix("::")?;
+        let (command, message) = rest.split_once("::")?;
+
+        let (name, properties) = match command.split_once(' ') {
+            Some((name, properties)) => (name, properties),
+            None => (command, ""),
+        };
+
+        let level = match name {
+            "group" => return Some(Self::Group(unescape_data(message))),
+            "endgroup" => return Some(Self::EndGroup),
+            "add-mask" => return Some(Self::AddMask(unescape_data(message))),
+            "debug" => return Some(Self::Debug(unescape_data(message))),
+            "stop-commands" => return Some(Self::StopCommands(message)),
+            "set-output" => {
+                let (_, name) = parse_properties(properties)
+                    .into_iter()
+                    .find(|(key, _)| *key == "name")?;
+
+                return Some(Self::SetOutput {
+                    name,
+                    value: unescape_data(message),
+                });
+            }
+            "error" => AnnotationLevel::Error,
+            "warning" => AnnotationLevel::Warning,
+            "notice" => AnnotationLevel::Notice,
+            _ => return None,
+        };
+
+        Some(Self::Annotation {
+            level,
+            properties: parse_properties(properties),
+            message: unescape_data(message),
+        })
+    }
+}
+
+fn parse_properties(properties: &str) -> Vec<(&str, Cow<'_, str>)> {
+    properties
+        .split(',')
+        .filter_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            Some((key.trim(), unescape_property(value)))
+        })
+        .collect()
+}
+
+/// Interprets workflow commands in the output of a single step.
+pub(super) struct StepOutput {
+    step: Option<String>,
+    in_group: bool,
+    /// The token which resumes command processing after `::stop-commands::`.
+    stopped: Option<String>,
+    /// Outputs set through `::set-output::`.
+    outputs: Vec<(String, String)>,
+}
+
+impl StepOutput {
+    /// Construct a new handler of output for the given step.
+    pub(super) fn new(step: Option<String>) -> Self {
+        Self {
+            step,
+            in_group: false,
+            stopped: None,
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Get the outputs set through `::set-output::`.
+    pub(super) fn into_outputs(self) -> Vec<(String, String)> {
+        self.outputs
+    }
+
+    /// Process a single line of output.
+    pub(super) fn line<O>(
+        &mut self,
+        o: &mut O,
+        c: &SessionConfig<'_, '_>,
+        session: &mut Session,
+        line: &[u8],
+    ) -> Result<()>
+    where
+        O: ?Sized + WriteColor,
+    {
+        let line = String::from_utf8_lossy(line);
+
+        if let Some(token) = &self.stopped {
+            if line.trim() == format!("::{token}::") {
+                self.stopped = None;
+            } else {
+                self.write_line(o, &line)?;
+            }
+
+            return Ok(());
+        }
+
+        let Some(command) = WorkflowCommand::parse(&line) else {
+            self.write_line(o, &line)?;
+            return Ok(());
+        };
+
+        let colors = &c.colors;
+
+        match command {
+            WorkflowCommand::Group(title) => {
+                o.set_color(&colors.title)?;
+                writeln!(o, "## {}", rstr::mask(&title))?;
+                o.reset()?;
+                self.in_group = true;
+            }
+            WorkflowCommand::EndGroup => {
+                self.in_group = false;
+            }
+            WorkflowCommand::Annotation {
+                level,
+                properties,
+                message,
+            } => {
+                let mut annotation = Annotation {
+                    level,
+                    step: self.step.clone(),
+                    title: None,
+                    file: None,
+                    line: None,
+                    col: None,
+                    message: rstr::mask(&message).into_owned(),
+                };
+
+                for (key, value) in properties {
+                    let value = Some(rstr::mask(&value).into_owned());
+
+                    match key {
+                        "title" => annotation.title = value,
+                        "file" => annotation.file = value,
+                        "line" => annotation.line = value,
+                        "col" => annotation.col = value,
+                        _ => {}
+                    }
+                }
+
+                write_annotation(o, c, &annotation, false)?;
+                session.push_annotation(annotation);
+            }
+            WorkflowCommand::AddMask(value) => {
+                rstr::add_mask(&value);
+            }
+            WorkflowCommand::Debug(message) => {
+                if c.verbose >= 1 {
+                    o.set_color(&colors.dim)?;
+                    writeln!(o, "Debug: {}", rstr::mask(&message))?;
+                    o.reset()?;
+                }
+            }
+            WorkflowCommand::StopCommands(token) => {
+                self.stopped = Some(token.to_owned());
+            }
+            WorkflowCommand::SetOutput { name, value } => {
+                self.outputs.push((name.into_owned(), value.into_owned()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_line<O>(&self, o: &mut O, line: &str) -> Result<()>
+    where
+        O: ?Sized + WriteColor,
+    {
+        if self.in_group {
+            write!(o, "  ")?;
+        }
+
+        writeln!(o, "{}", rstr::mask(line))?;
+        Ok(())
+    }
+}
+
+/// Write a summary of all annotations emitted by steps.
+pub(crate) fn write_annotations<O>(
+    o: &mut O,
+    c: &SessionConfig<'_, '_>,
+    annotations: &[Annotation],
+) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    if annotations.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(o, "# Annotations:")?;
+
+    for annotation in annotations {
+        write!(o, "  ")?;
+        write_annotation(o, c, annotation, true)?;
+    }
+
+    Ok(())
+}
+
+fn write_annotation<O>(
+    o: &mut O,
+    c: &SessionConfig<'_, '_>,
+    annotation: &Annotation,
+    with_step: bool,
+) -> Result<()>
+where
+    O: ?Sized + WriteColor,
+{
+    let color = match annotation.level {
+        AnnotationLevel::Error => &c.colors.red,
+        AnnotationLevel::Warning => &c.colors.warn,
+        AnnotationLevel::Notice => &c.colors.title,
+    };
+
+    o.set_color(color)?;
+    write!(o, "{}", annotation.level.as_str())?;
+    o.reset()?;
+
+    if with_step && let Some(step) = &annotation.step {
+        write!(o, " [{step}]")?;
+    }
+
+    if let Some(location) = annotation.location() {
+        write!(o, " {location}")?;
+    }
+
+    if let Some(title) = &annotation.title {
+        write!(o, " ({title})")?;
+    }
+
+    writeln!(o, ": {}", annotation.message)?;
+    Ok(())
+}
+
+/// Parse the contents of a file like `GITHUB_ENV` or `GITHUB_OUTPUT`.
+///
+/// Each line is either `{name}={value}`, or `{name}<<{delimiter}` followed by
+/// the lines of a multi-line value and finally a line with only the
+/// delimiter.
+pub(super) fn parse_key_values(contents: &[u8]) -> Result<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    let mut lines = contents
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+    while let Some(line) = lines.next() {
+        let Ok(line) = str::from_utf8(line) else {
+            continue;
+        };
+
+        if let Some((key, delimiter)) = line.split_once("<<")
+            && !key.contains('=')
+        {
+            let key = key.trim();
+            let delimiter = delimiter.trim();
+
+            if key.is_empty() || delimiter.is_empty() {
+                continue;
+            }
+
+            let mut value = Vec::new();
+
+            loop {
+                let Some(line) = lines.next() else {
+                    bail!("{key}: Missing delimiter `{delimiter}`");
+                };
+
+                if line == delimiter.as_bytes() {
+                    break;
+                }
+
+                value.push(String::from_utf8_lossy(line));
+            }
+
+            out.push((key.to_owned(), value.join("\n")));
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let key = key.trim();
+        let value = value.trim();
+
+        if key.is_empty() || value.is_empty() {
+            continue;
+        }
+
+        out.push((key.to_owned(), value.to_owned()));
+    }
+
+    Ok(out)
+}
+
+/// Unescape the message of a workflow command.
+fn unescape_data(value: &str) -> Cow<'_, str> {
+    unescape(value, &[("%0D", "\r"), ("%0A", "\n"), ("%25", "%")])
+}
+
+/// Unescape a property of a workflow command.
+fn unescape_property(value: &str) -> Cow<'_, str> {
+    unescape(
+        value,
+        &[
+            ("%0D", "\r"),
+            ("%0A", "\n"),
+            ("%3A", ":"),
+            ("%2C", ","),
+            ("%25", "%"),
+        ],
+    )
+}
+
+fn unescape<'a>(value: &'a str, escapes: &[(&str, &str)]) -> Cow<'a, str> {
+    if !value.contains('%') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut out = value.to_owned();
+
+    // NB: `%25` is unescaped last, so that it can't form new escapes.
+    for (from, to) in escapes {
+        out = out.replace(from, to);
+    }
+
+    Cow::Owned(out)
+}
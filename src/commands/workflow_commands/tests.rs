@@ -0,0 +1,95 @@
+use super::*;
+
+#[test]
+fn parse_group() {
+    assert_eq!(
+        WorkflowCommand::parse("::group::Build %25 test"),
+        Some(WorkflowCommand::Group(Cow::Borrowed("Build % test")))
+    );
+    assert_eq!(
+        WorkflowCommand::parse("  ::endgroup::"),
+        Some(WorkflowCommand::EndGroup)
+    );
+    assert_eq!(WorkflowCommand::parse("group::Build"), None);
+    assert_eq!(WorkflowCommand::parse("::unknown::Build"), None);
+}
+
+#[test]
+fn parse_add_mask() {
+    let Some(WorkflowCommand::AddMask(value)) = WorkflowCommand::parse("::add-mask::hunter2%0A")
+    else {
+        panic!("expected add-mask");
+    };
+
+    assert_eq!(value, "hunter2\n");
+
+    rstr::add_mask(&value);
+    assert_eq!(rstr::mask("password is hunter2"), "password is ***");
+}
+
+#[test]
+fn parse_set_output() {
+    assert_eq!(
+        WorkflowCommand::parse("::set-output name=value::first%0Asecond%25"),
+        Some(WorkflowCommand::SetOutput {
+            name: Cow::Borrowed("value"),
+            value: Cow::Borrowed("first\nsecond%"),
+        })
+    );
+
+    assert_eq!(WorkflowCommand::parse("::set-output::value"), None);
+}
+
+#[test]
+fn parse_annotation() {
+    assert_eq!(
+        WorkflowCommand::parse("::error file=src/main.rs,line=10,title=A%2CB::Broken%0Aagain"),
+        Some(WorkflowCommand::Annotation {
+            level: AnnotationLevel::Error,
+            properties: vec![
+                ("file", Cow::Borrowed("src/main.rs")),
+                ("line", Cow::Borrowed("10")),
+                ("title", Cow::Borrowed("A,B")),
+            ],
+            message: Cow::Borrowed("Broken\nagain"),
+        })
+    );
+}
+
+#[test]
+fn key_values() -> Result<()> {
+    let contents = b"a=1\nempty=\n\nb = 2 \nc<<EOF\nfirst\nsecond=2\n\nEOF\nd=x<<y\n";
+
+    assert_eq!(
+        parse_key_values(contents)?,
+        [
+            ("a".to_owned(), "1".to_owned()),
+            ("b".to_owned(), "2".to_owned()),
+            ("c".to_owned(), "first\nsecond=2\n".to_owned()),
+            ("d".to_owned(), "x<<y".to_owned()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn key_values_crlf() -> Result<()> {
+    let contents = b"a=1\r\nb<<EOF\r\nfirst\r\nsecond\r\nEOF\r\n";
+
+    assert_eq!(
+        parse_key_values(contents)?,
+        [
+            ("a".to_owned(), "1".to_owned()),
+            ("b".to_owned(), "first\nsecond".to_owned()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn key_values_missing_delimiter() {
+    let error = parse_key_values(b"a<<EOF\nfirst\nEOF2\n").unwrap_err();
+    assert_eq!(error.to_string(), "a: Missing delimiter `EOF`");
+}
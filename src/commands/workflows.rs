@@ -1,27 +1,40 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 use std::str;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 
 use crate::config::{Distribution, Os};
-use crate::rstr::RStr;
-use crate::workflows::{Job, Matrix, Steps, WorkflowManifest};
+use crate::rstr::{RStr, RString};
+use crate::workflows::{
+    Eval, Job, JobCondition, Matrix, Steps, Tree, WorkflowManifest, has_status_function,
+};
 
-use super::{Batch, RunOn, SessionConfig, build_steps};
+use super::{Batch, Env, RunOn, Schedule, ScheduleOutputs, SessionConfig, build_steps};
 
 /// A collection of loaded workflows.
 pub(crate) struct LoadedWorkflow<'a, 'cx> {
     workflows: &'a LoadedWorkflows<'a, 'cx>,
     manifest: &'a WorkflowManifest<'a, 'cx>,
     jobs: &'a [Job],
+    not_triggered: Option<&'a str>,
 }
 
-impl<'cx> LoadedWorkflow<'_, 'cx> {
+impl<'a, 'cx> LoadedWorkflow<'a, 'cx> {
     /// Get the identifier of the workflow.
-    pub(crate) fn id(&self) -> &str {
+    pub(crate) fn id(&self) -> &'a str {
         self.manifest.id()
     }
 
+    /// Get the reason why the workflow would not be triggered by the simulated
+    /// event, if any.
+    pub(crate) fn not_triggered(&self) -> Option<&'a str> {
+        self.not_triggered
+    }
+
     /// Iterate over all jobs in the current workflow.
     pub(crate) fn jobs(&self) -> impl Iterator<Item = LoadedJob<'_, 'cx>> + '_ {
         self.jobs.iter().map(|job| LoadedJob {
@@ -29,6 +42,98 @@ impl<'cx> LoadedWorkflow<'_, 'cx> {
             job,
         })
     }
+
+    /// Resolve jobs matching the given filter along with all of their
+    /// transitive prerequisites.
+    ///
+    /// Jobs are ordered so that every job comes after the jobs it needs, ties
+    /// are broken by the order in which jobs are declared in the workflow.
+    pub(crate) fn resolve_jobs<F>(&self, filter: F) -> Result<Vec<LoadedJob<'a, 'cx>>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let graph = self
+            .jobs
+            .iter()
+            .map(|job| (job.id.as_str(), job.needs.as_slice()))
+            .collect::<Vec<_>>();
+
+        Ok(job_order(self.id(), &graph, filter)?
+            .into_iter()
+            .map(|n| LoadedJob {
+                workflows: self.workflows,
+                job: &self.jobs[n],
+            })
+            .collect())
+    }
+}
+
+/// Get the indexes of the jobs in the graph matching the filter along with all
+/// of their transitive prerequisites, where each job in the graph is its id and
+/// the ids of the jobs it needs.
+///
+/// Jobs are ordered so that every job comes after the jobs it needs, ties are
+/// broken by the order in which jobs are declared.
+fn job_order<F>(workflow: &str, graph: &[(&str, &[String])], filter: F) -> Result<Vec<usize>>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut visits = vec![Visit::Unvisited; graph.len()];
+    let mut order = Vec::new();
+
+    let index = graph
+        .iter()
+        .enumerate()
+        .map(|(n, (id, _))| (*id, n))
+        .collect::<HashMap<_, _>>();
+
+    for (n, (id, _)) in graph.iter().enumerate() {
+        if filter(id) {
+            visit_job(workflow, graph, n, &index, &mut visits, &mut order)?;
+        }
+    }
+
+    Ok(order)
+}
+
+fn visit_job(
+    workflow: &str,
+    graph: &[(&str, &[String])],
+    n: usize,
+    index: &HashMap<&str, usize>,
+    visits: &mut [Visit],
+    order: &mut Vec<usize>,
+) -> Result<()> {
+    let (id, needs) = graph[n];
+
+    match visits[n] {
+        Visit::Done => return Ok(()),
+        Visit::InProgress => {
+            bail!("{workflow}: Job `{id}` is part of a dependency cycle");
+        }
+        Visit::Unvisited => {}
+    }
+
+    visits[n] = Visit::InProgress;
+
+    for need in needs {
+        let Some(&need) = index.get(need.as_str()) else {
+            bail!("{workflow}: Job `{id}` needs unknown job `{need}`");
+        };
+
+        visit_job(workflow, graph, need, index, visits, order)?;
+    }
+
+    visits[n] = Visit::Done;
+    order.push(n);
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Visit {
+    Unvisited,
+    InProgress,
+    Done,
 }
 
 /// A single loaded job.
@@ -37,21 +142,168 @@ pub(crate) struct LoadedJob<'a, 'cx> {
     job: &'a Job,
 }
 
-impl<'cx> LoadedJob<'_, 'cx> {
+impl<'a, 'cx> LoadedJob<'a, 'cx> {
     /// Get the identifier of the job.
-    pub(crate) fn id(&self) -> &str {
+    pub(crate) fn id(&self) -> &'a str {
         &self.job.id
     }
 
+    /// Get the identifiers of the jobs this job needs.
+    pub(crate) fn needs(&self) -> &'a [String] {
+        &self.job.needs
+    }
+
+    /// Get the name a job this job needs is known by in the `needs` context.
+    ///
+    /// Jobs inside of a reusable workflow refer to each other without the
+    /// prefix of the job which called the workflow.
+    pub(crate) fn need_name<'n>(&self, need: &'n str) -> &'n str {
+        let Some(called_by) = &self.job.called_by else {
+            return need;
+        };
+
+        need.strip_prefix(called_by.as_str())
+            .and_then(|need| need.strip_prefix('/'))
+            .unwrap_or(need)
+    }
+
+    /// Get the reusable workflow called by this job, if any.
+    pub(crate) fn uses(&self) -> Option<&'a str> {
+        self.job.uses.as_deref()
+    }
+
+    /// Get the name of a job in the workflow called by this job, if `need`
+    /// refers to one.
+    pub(crate) fn called_job<'n>(&self, need: &'n str) -> Option<&'n str> {
+        self.job.uses.as_ref()?;
+        need.strip_prefix(self.job.id.as_str())?.strip_prefix('/')
+    }
+
+    /// Evaluate the outputs of a job which calls a reusable workflow, where
+    /// `jobs` is expected to contain the `jobs` context of the called
+    /// workflow.
+    pub(crate) fn workflow_outputs(&self, jobs: &Tree) -> Result<BTreeMap<String, RString>> {
+        let eval = Eval::new(jobs);
+        let mut outputs = BTreeMap::new();
+
+        for (key, value) in &self.job.outputs {
+            let value = eval
+                .eval(value)
+                .with_context(|| anyhow!("Evaluating output `{key}`"))?;
+            outputs.insert(key.clone(), value.into_owned());
+        }
+
+        Ok(outputs)
+    }
+
+    /// Test if the `if` condition of the job, or of a job calling the
+    /// reusable workflow it was declared in, calls a status function. In which
+    /// case it decides whether the job runs even if a job it needs did not
+    /// succeed.
+    pub(crate) fn has_status_function(&self) -> bool {
+        self.conditions().any(|c| has_status_function(&c.source))
+    }
+
+    /// Evaluate the `if` condition of the job, and of any job calling the
+    /// reusable workflow it was declared in, returning the first condition
+    /// which doesn't hold if the job should be skipped.
+    ///
+    /// The `needs` tree is expected to contain the `needs` context of the job,
+    /// and `status` is the combined result of the jobs it needs which status
+    /// functions like `success()` and `failure()` are evaluated against.
+    pub(crate) fn skipped(&self, needs: &Tree, status: &str) -> Result<Option<&'a str>> {
+        for condition in self.conditions() {
+            let mut tree = match &self.job.deferred {
+                // NB: The condition of the job itself might refer to inputs
+                // which are only evaluated once the jobs it needs have
+                // completed.
+                Some(deferred) if self.is_own_condition(condition) => deferred.tree(needs)?,
+                _ => {
+                    let mut tree = condition.tree.as_ref().clone();
+                    tree.extend(needs);
+                    tree
+                }
+            };
+
+            tree.insert(["job", "status"], status);
+
+            if !Eval::new(&tree).test(&condition.source)? {
+                return Ok(Some(&condition.source));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn is_own_condition(&self, condition: &JobCondition) -> bool {
+        self.job
+            .condition
+            .as_ref()
+            .is_some_and(|c| std::ptr::eq(c, condition))
+    }
+
+    fn conditions(&self) -> impl Iterator<Item = &'a JobCondition> {
+        self.job
+            .condition
+            .iter()
+            .chain(self.job.caller_conditions.iter())
+    }
+
+    /// Test if the remaining matrix combinations of the job should be
+    /// cancelled once one has failed.
+    pub(crate) fn fail_fast(&self) -> bool {
+        self.job.fail_fast
+    }
+
+    /// Get the maximum number of matrix combinations to run at the same time.
+    pub(crate) fn max_parallel(&self) -> Option<usize> {
+        self.job.max_parallel
+    }
+
+    /// Get the error which prevented the job from being loaded, if any.
+    pub(crate) fn error(&self) -> Option<&'a str> {
+        self.job.error.as_deref()
+    }
+
+    /// Test if the matrix of the job, or the inputs of the reusable workflow
+    /// it was declared in, depend on the `needs` context. In which case its
+    /// matrices have to be built through [`LoadedJob::build_deferred`].
+    pub(crate) fn is_deferred(&self) -> bool {
+        self.job.deferred.is_some()
+    }
+
+    /// Build the matrices of a job which depends on the `needs` context,
+    /// returning `None` if it doesn't.
+    ///
+    /// The `needs` tree is expected to contain the `needs` context of the job.
+    pub(crate) fn build_deferred(&self, needs: &Tree) -> Result<Option<Vec<(Matrix, Steps)>>> {
+        let Some(deferred) = &self.job.deferred else {
+            return Ok(None);
+        };
+
+        Ok(Some(deferred.build(needs).context("Building matrix")?))
+    }
+
     /// Iterate over all matrices of the current job.
     pub(crate) fn matrices(&self) -> impl Iterator<Item = LoadedJobMatrix<'_, 'cx>> + '_ {
-        self.job
-            .matrices
+        self.matrices_with(None)
+    }
+
+    /// Iterate over the given matrices built through
+    /// [`LoadedJob::build_deferred`], or the matrices of the current job if
+    /// there are none.
+    pub(crate) fn matrices_with<'m>(
+        &'m self,
+        deferred: Option<&'m [(Matrix, Steps)]>,
+    ) -> impl Iterator<Item = LoadedJobMatrix<'m, 'cx>> + 'm {
+        deferred
+            .unwrap_or(&self.job.matrices)
             .iter()
             .map(|(matrix, steps)| LoadedJobMatrix {
                 workflows: self.workflows,
                 matrix,
                 steps,
+                outputs: &self.job.outputs,
             })
     }
 }
@@ -61,6 +313,7 @@ pub(crate) struct LoadedJobMatrix<'a, 'cx> {
     workflows: &'a LoadedWorkflows<'a, 'cx>,
     matrix: &'a Matrix,
     steps: &'a Steps,
+    outputs: &'a BTreeMap<String, String>,
 }
 
 impl LoadedJobMatrix<'_, '_> {
@@ -74,29 +327,45 @@ impl LoadedJobMatrix<'_, '_> {
         self.matrix
     }
 
+    /// Test if the job is allowed to fail.
+    pub(crate) fn continue_on_error(&self) -> bool {
+        self.steps.continue_on_error
+    }
+
     /// Build a batch from the current job matrix.
+    ///
+    /// The `needs` tree is made available to the steps of the job, and is
+    /// expected to contain the `needs` context of the job.
     pub(crate) fn build(
         &self,
         parent_step_id: Option<&Rc<RStr>>,
         same_os: bool,
         current_os: &Os,
+        needs: &Tree,
     ) -> Result<Batch> {
-        self.workflows
-            .build_batch(self.matrix, self.steps, parent_step_id, same_os, current_os)
+        self.workflows.build_batch(
+            self.matrix,
+            self.steps,
+            self.outputs,
+            parent_step_id,
+            same_os,
+            current_os,
+            needs,
+        )
     }
 }
 
 /// Loaded workflows.
 pub(crate) struct LoadedWorkflows<'a, 'cx> {
     batch: &'a SessionConfig<'a, 'cx>,
-    workflows: Vec<(WorkflowManifest<'a, 'cx>, Vec<Job>)>,
+    workflows: Vec<(WorkflowManifest<'a, 'cx>, Vec<Job>, Option<String>)>,
 }
 
 impl<'a, 'cx> LoadedWorkflows<'a, 'cx> {
     /// Construct a new collection of loaded workflows.
     pub(super) fn new(
         batch: &'a SessionConfig<'a, 'cx>,
-        workflows: Vec<(WorkflowManifest<'a, 'cx>, Vec<Job>)>,
+        workflows: Vec<(WorkflowManifest<'a, 'cx>, Vec<Job>, Option<String>)>,
     ) -> Self {
         Self { batch, workflows }
     }
@@ -105,48 +374,78 @@ impl<'a, 'cx> LoadedWorkflows<'a, 'cx> {
     pub(crate) fn iter(&self) -> impl Iterator<Item = LoadedWorkflow<'_, 'cx>> + '_ {
         self.workflows
             .iter()
-            .map(|(manifest, jobs)| LoadedWorkflow {
+            .map(|(manifest, jobs, not_triggered)| LoadedWorkflow {
                 workflows: self,
                 manifest,
                 jobs,
+                not_triggered: not_triggered.as_deref(),
             })
     }
 
     /// Add jobs from a workflows, matrix, and associated steps.
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn build_batch(
         &self,
         matrix: &Matrix,
         steps: &Steps,
+        outputs: &BTreeMap<String, String>,
         parent_step_id: Option<&Rc<RStr>>,
         same_os: bool,
         current_os: &Os,
+        needs: &Tree,
     ) -> Result<Batch> {
         let runs_on = steps.runs_on.to_exposed();
 
-        let (os, dist) = match runs_on.split_once('-').map(|(os, _)| os) {
-            Some("ubuntu") => (Os::Linux, Distribution::Ubuntu),
-            Some("windows") => (Os::Windows, Distribution::Other),
-            Some("macos") => (Os::Mac, Distribution::Other),
-            _ => bail!("Unsupported runs-on directive: {}", steps.runs_on),
+        let os_dist = match runs_on.split_once('-').map(|(os, _)| os) {
+            Some("ubuntu") => Some((Os::Linux, Distribution::Ubuntu)),
+            Some("windows") => Some((Os::Windows, Distribution::Other)),
+            Some("macos") => Some((Os::Mac, Distribution::Other)),
+            _ => None,
         };
 
         let (run_on, os) = if same_os {
             (RunOn::Same, current_os.clone())
+        } else if let Some(container) = &steps.container {
+            let image = container.image.to_exposed();
+            (RunOn::Container(Rc::from(image.as_ref())), Os::Linux)
+        } else if let Some(image) = self.batch.runners.get(runs_on.as_ref()) {
+            (RunOn::Container(Rc::from(image.as_str())), Os::Linux)
         } else {
+            let Some((os, dist)) = os_dist else {
+                bail!("Unsupported runs-on directive: {}", steps.runs_on);
+            };
+
             (RunOn::from_os(self.batch, &os, dist)?, os)
         };
 
-        let commands = build_steps(self.batch, None, parent_step_id, None, &steps.steps, None)?;
+        let mut group = build_steps(self.batch, None, parent_step_id, None, &steps.steps, None)?;
+
+        if !needs.is_empty() {
+            group = group.with_tree(Rc::new(needs.clone()));
+        }
+
+        if !outputs.is_empty() {
+            let env = Env::new(self.batch, None, None)?;
+            let tree = env.tree.with_extended(&steps.tree);
+
+            group = group.with_outputs(ScheduleOutputs {
+                env: env.with_tree(Rc::new(tree)),
+                outputs: Rc::new(outputs.clone()),
+            });
+        }
 
         Ok(Batch::new(
             run_on,
             os,
-            vec![commands],
+            vec![Schedule::Group(group)],
             if !matrix.is_empty() {
                 Some(matrix.clone())
             } else {
                 None
             },
-        ))
+        )
+        .with_timeout(steps.timeout)
+        .with_container(steps.container.clone())
+        .with_services(steps.services.clone()))
     }
 }
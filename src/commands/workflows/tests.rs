@@ -0,0 +1,85 @@
+use super::*;
+
+/// Build a graph of jobs from their ids and the ids of the jobs they need.
+fn jobs(graph: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+    graph
+        .iter()
+        .map(|(id, needs)| {
+            let needs = needs.iter().map(|n| n.to_string()).collect();
+            (id.to_string(), needs)
+        })
+        .collect()
+}
+
+/// Order the jobs matching the filter, returning their ids.
+fn order(jobs: &[(String, Vec<String>)], filter: &[&str]) -> Result<Vec<String>> {
+    let graph = jobs
+        .iter()
+        .map(|(id, needs)| (id.as_str(), needs.as_slice()))
+        .collect::<Vec<_>>();
+
+    let order = job_order("ci", &graph, |id| filter.is_empty() || filter.contains(&id))?;
+
+    Ok(order.into_iter().map(|n| jobs[n].0.clone()).collect())
+}
+
+#[test]
+fn topological_order() -> Result<()> {
+    let jobs = jobs(&[
+        ("deploy", &["test", "build"]),
+        ("test", &["build"]),
+        ("lint", &[]),
+        ("build", &["setup"]),
+        ("setup", &[]),
+    ]);
+
+    assert_eq!(
+        order(&jobs, &[])?,
+        ["setup", "build", "test", "deploy", "lint"]
+    );
+    Ok(())
+}
+
+#[test]
+fn transitive_prerequisites() -> Result<()> {
+    let jobs = jobs(&[
+        ("setup", &[]),
+        ("build", &["setup"]),
+        ("lint", &[]),
+        ("test", &["build"]),
+        ("docs", &["lint"]),
+    ]);
+
+    assert_eq!(order(&jobs, &["test"])?, ["setup", "build", "test"]);
+    assert_eq!(order(&jobs, &["docs"])?, ["lint", "docs"]);
+    assert_eq!(
+        order(&jobs, &["docs", "test"])?,
+        ["setup", "build", "test", "lint", "docs"]
+    );
+    assert_eq!(order(&jobs, &["setup"])?, ["setup"]);
+    assert!(order(&jobs, &["missing"])?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn dependency_cycle() {
+    let jobs = jobs(&[("a", &["c"]), ("b", &["a"]), ("c", &["b"]), ("d", &[])]);
+
+    let e = order(&jobs, &["b"]).unwrap_err();
+    assert_eq!(e.to_string(), "ci: Job `b` is part of a dependency cycle");
+
+    let jobs = self::jobs(&[("a", &["a"])]);
+    let e = order(&jobs, &[]).unwrap_err();
+    assert_eq!(e.to_string(), "ci: Job `a` is part of a dependency cycle");
+}
+
+#[test]
+fn unknown_needs() {
+    let jobs = jobs(&[("build", &[]), ("test", &["build", "setup"])]);
+
+    let e = order(&jobs, &["test"]).unwrap_err();
+    assert_eq!(e.to_string(), "ci: Job `test` needs unknown job `setup`");
+
+    // Jobs which aren't selected are not checked.
+    assert_eq!(order(&jobs, &["build"]).unwrap(), ["build"]);
+}
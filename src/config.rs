@@ -1,3 +1,6 @@
+#[cfg(test)]
+mod tests;
+
 use core::cell::RefCell;
 use core::mem;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
@@ -189,6 +192,19 @@ impl Actions {
     }
 }
 
+/// Configuration for `kick run`.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct RunConfig {
+    /// Values of the `vars` context.
+    pub(crate) vars: toml::Table,
+}
+
+impl RunConfig {
+    fn merge_with(&mut self, other: Self) {
+        merge_map(&mut self.vars, other.vars);
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub(crate) struct PackageFile {
     /// The source of an rpm file.
@@ -492,6 +508,10 @@ pub(crate) struct RepoConfig {
     pub(crate) package: Package,
     /// Actions configuration.
     pub(crate) actions: Actions,
+    /// Container images to use for `runs-on` labels.
+    pub(crate) runners: BTreeMap<String, String>,
+    /// Configuration for `kick run`.
+    pub(crate) run: RunConfig,
 }
 
 impl RepoConfig {
@@ -522,6 +542,8 @@ impl RepoConfig {
         self.upgrade.merge_with(other.upgrade);
         self.package.merge_with(other.package);
         self.actions.merge_with(other.actions);
+        self.runners.extend(other.runners);
+        self.run.merge_with(other.run);
 
         merge_map(&mut self.variables, other.variables);
     }
@@ -810,6 +832,31 @@ impl Config<'_> {
         self.repos(repo).flat_map(|r| &r.actions.latest).collect()
     }
 
+    /// Get container images to use for `runs-on` labels.
+    pub(crate) fn runners(&self, repo: &RepoRef) -> BTreeMap<String, String> {
+        let mut runners = BTreeMap::new();
+
+        for r in self.repos(repo) {
+            runners.extend(r.runners.clone());
+        }
+
+        runners
+    }
+
+    /// Get the values of the `vars` context.
+    ///
+    /// Values which are not strings are converted to strings, while tables
+    /// and arrays are ignored.
+    pub(crate) fn run_vars(&self, repo: &RepoRef) -> BTreeMap<String, String> {
+        let mut vars = toml::Table::new();
+
+        for r in self.repos(repo) {
+            merge_map(&mut vars, r.run.vars.clone());
+        }
+
+        stringify_vars(vars)
+    }
+
     /// Get all elements corresponding to the given field.
     pub(crate) fn get_all<'a, O: 'a, I>(
         &'a self,
@@ -1355,6 +1402,12 @@ impl<'a> Cx<'a> {
 
         let actions = self.in_key(table, "actions", Self::actions);
 
+        let runners = self.in_table(table, "runners", |cx, label, value| {
+            Ok((label, cx.string(value)?))
+        });
+
+        let run = self.in_key(table, "run", Self::run);
+
         Ok(RepoConfig {
             sources: BTreeSet::from_iter([RepoSource::Config(self.current.to_owned())]),
             name: name?,
@@ -1378,6 +1431,8 @@ impl<'a> Cx<'a> {
             upgrade: upgrade?.unwrap_or_default(),
             package: package?.unwrap_or_default(),
             actions: actions?.unwrap_or_default(),
+            runners: runners?,
+            run: run?.unwrap_or_default(),
         })
     }
 
@@ -1563,6 +1618,16 @@ impl<'a> Cx<'a> {
             })
         })
     }
+
+    fn run(&self, value: toml::Value) -> Result<RunConfig, ErrorMarker> {
+        self.with_table(value, |cx, table| {
+            let vars = cx.as_table(table, "vars", |_, table| Ok(table));
+
+            Ok(RunConfig {
+                vars: vars?.unwrap_or_default(),
+            })
+        })
+    }
 }
 
 /// Load a configuration from the given path.
@@ -1720,6 +1785,29 @@ fn merge_map(target: &mut toml::Table, source: toml::Table) {
     }
 }
 
+/// Convert the values of `run.vars` into strings, ignoring tables and arrays.
+fn stringify_vars(vars: toml::Table) -> BTreeMap<String, String> {
+    let mut output = BTreeMap::new();
+
+    for (key, value) in vars {
+        let value = match value {
+            toml::Value::String(value) => value,
+            toml::Value::Integer(value) => value.to_string(),
+            toml::Value::Float(value) => value.to_string(),
+            toml::Value::Boolean(value) => value.to_string(),
+            toml::Value::Datetime(value) => value.to_string(),
+            toml::Value::Array(..) | toml::Value::Table(..) => {
+                tracing::warn!("run.vars.{key}: Ignoring value which is not a scalar");
+                continue;
+            }
+        };
+
+        output.insert(key, value);
+    }
+
+    output
+}
+
 /// Access `rpm.requies` through [`Config::get_all`].
 pub(crate) fn rpm_requires(config: &RepoConfig) -> &[RpmRequire] {
     &config.package.rpm.requires
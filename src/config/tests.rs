@@ -0,0 +1,39 @@
+use super::*;
+
+fn table(source: &str) -> toml::Table {
+    toml::from_str(source).expect("valid toml")
+}
+
+#[test]
+fn run_vars() {
+    let mut vars = table(
+        r#"
+        ENVIRONMENT = "staging"
+        REPLICAS = 2
+        RATIO = 0.5
+        ENABLED = true
+        RELEASED = 2024-05-01
+        LIST = [1, 2]
+
+        [NESTED]
+        KEY = "value"
+        "#,
+    );
+
+    // NB: Variables configured for a repo override the shared ones.
+    merge_map(&mut vars, table("ENVIRONMENT = \"production\""));
+
+    let expected = [
+        ("ENABLED", "true"),
+        ("ENVIRONMENT", "production"),
+        ("RATIO", "0.5"),
+        ("RELEASED", "2024-05-01"),
+        ("REPLICAS", "2"),
+    ]
+    .map(|(key, value)| (key.to_owned(), value.to_owned()));
+
+    assert_eq!(
+        stringify_vars(vars).into_iter().collect::<Vec<_>>(),
+        expected
+    );
+}
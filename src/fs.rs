@@ -1,5 +1,5 @@
 use std::fmt;
-use std::fs::{self, Permissions};
+use std::fs::{self, Metadata, Permissions};
 use std::io;
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
@@ -92,3 +92,18 @@ pub(crate) fn set_secure(p: &mut Permissions) {
         p.set_mode(0o500);
     }
 }
+
+/// Test if users other than the owner can access the file or directory with
+/// the given metadata.
+pub(crate) fn is_shared(m: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        m.mode() & 0o077 != 0
+    }
+
+    #[cfg(not(unix))]
+    {
+        _ = m;
+        false
+    }
+}
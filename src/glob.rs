@@ -60,7 +60,7 @@ impl<'a> Matcher<'a> {
                     return Ok(());
                 }
             }
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(e) if is_missing(&e) => {
                 return Ok(());
             }
             Err(e) => return Err(e),
@@ -96,10 +96,10 @@ impl<'a> Matcher<'a> {
             match fs::metadata(&path) {
                 Ok(m) => {
                     if !m.is_dir() {
-                        return Ok(());
+                        continue;
                     }
                 }
-                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(e) if is_missing(&e) => {
                     continue;
                 }
                 Err(e) => return Err(e),
@@ -119,6 +119,15 @@ impl<'a> Matcher<'a> {
     }
 }
 
+/// Test if the error indicates that the path is missing, either because it
+/// doesn't exist or because one of its parents is not a directory.
+fn is_missing(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+    )
+}
+
 pub(crate) struct Matcher<'a> {
     root: &'a Path,
     queue: VecDeque<(RelativePathBuf, &'a [Component<'a>])>,
@@ -141,9 +141,142 @@
 //!
 //! This also comes with support for matrix expansion.
 //!
+//! Jobs are run in the order dictated by their `needs`, and running a single job
+//! with `--job` also runs any jobs it transitively depends on first.
+//!
+//! Jobs whose `if` condition evaluates to false are skipped, and so are jobs
+//! which depend on a job that did not succeed unless their `if` calls a status
+//! function like `always()` or `failure()`. When a combination of a matrix
+//! fails, the remaining combinations are skipped unless `strategy.fail-fast` is
+//! set to `false`. A matrix which refers to the `needs` context, like `${{
+//! fromJSON(needs.setup.outputs.matrix) }}`, is expanded once the jobs it needs
+//! have completed.
+//!
+//! Jobs which call a local reusable workflow through `uses:
+//! ./.github/workflows/<name>.yml` are expanded into the jobs of the called
+//! workflow, which are prefixed with the id of the calling job. The `with` and
+//! `secrets` of the calling job are passed on as `inputs` and `secrets`, and the
+//! outputs declared under `on.workflow_call` are available to jobs which need it.
+//! An `if` on the calling job also applies to the jobs of the called workflow.
+//! Inputs and secrets which refer to the `needs` context are evaluated once the
+//! jobs of the called workflow are run. Calling a reusable workflow with a
+//! `strategy` is not supported, and fails the calling job when it is run.
+//!
+//! Passing `--jobs <N>` runs up to `N` matrix combinations of a job in parallel,
+//! each in a separate git worktree of the repo. Output is prefixed with the job and
+//! matrix that produced it, and a summary of each combination is printed at the
+//! end. No more than `strategy.max-parallel` combinations of a job are run at
+//! the same time.
+//! If interrupted with Ctrl-C, combinations which haven't started are cancelled
+//! and the worktrees are removed once the running ones have exited.
+//!
+//! Both steps and jobs honor `continue-on-error` and `timeout-minutes`, where a
+//! step which times out has its entire process tree killed. In a container this
+//! kills every process in it except for its init process, including any background
+//! processes started by earlier steps of the job.
+//!
+//! The `shell` of a `run` step can be `bash`, `sh`, `pwsh`, `powershell`,
+//! `cmd`, `python`, or a custom command like `perl {0}` where `{0}` is replaced
+//! with the path to the script. Scripts are written to a file and invoked the same
+//! way that Github does, which is what is printed with `--dry-run` and `-VV`.
+//! Steps which do not specify a `shell` or `working-directory` use the ones in
+//! `defaults.run` of their job, or of the workflow. Otherwise they are run with
+//! `bash`.
+//!
+//! The steps of the job specified with `--job` can be limited with `--from-step
+//! <step>` and `--until-step <step>`, where a step is either its `id`, its
+//! `name` or its 1-based index. The state of a job is saved before each of its
+//! steps, so if a step fails the job can be continued from that step with
+//! `--resume`. This restores the environment, paths and step outputs that the
+//! job had before the step failed.
+//!
+//! Workflow commands printed by steps are interpreted. `::group::` is rendered as
+//! a header, `::error::`, `::warning::` and `::notice::` are summarized at the end
+//! of the run, values registered through `::add-mask::` are replaced with `***`
+//! in any output, and `::debug::` messages are only shown with `-V`. Outputs can be
+//! set through the deprecated `::set-output name=<name>::` command as well as
+//! through `GITHUB_OUTPUT`, which like `GITHUB_ENV` supports multi-line values
+//! using the `<name><<<delimiter>` syntax.
+//!
+//! Markdown which steps append to `GITHUB_STEP_SUMMARY` is rendered to the
+//! terminal at the end of the run. Passing `--summary-out <path>` also writes the
+//! concatenated summaries to the given file.
+//!
+//! Steps can use actions stored in the repo by path, such as `uses:
+//! ./.github/actions/foo`. These are loaded directly from the `action.yml` or
+//! `action.yaml` in that directory, and `GITHUB_ACTION_PATH` points to it.
+//!
+//! The `github` context is populated from the local git repository, so that
+//! values like `github.sha`, `github.ref` and `github.repository` are available.
+//! The matching `GITHUB_*` environment variables are also set for each step.
+//!
+//! Passing `--event <name>` simulates the event which triggered the run, such as
+//! `push`, `pull_request` or `workflow_dispatch`. Workflows whose `on` block isn't
+//! triggered by the event, or whose branch, tag or path filters don't match, are
+//! skipped. A JSON payload can be provided with `--event-payload <path>`, which is
+//! available as `github.event` and through `GITHUB_EVENT_PATH`. Inputs are
+//! specified with `--input <key>=<value>`, which defaults the event to
+//! `workflow_dispatch` if no event is specified.
+//!
+//! The `secrets` context is populated from files in the `secrets` directory of the
+//! Kick configuration directory, where each file contains a single secret named
+//! after the file. The directory may only be accessible by its owner. Secrets can
+//! also be provided through environment variables prefixed with `KICK_SECRET_`,
+//! which can be changed with `--secret-env-prefix`, or with
+//! `--secret <name>=<value>`. Secrets are redacted from any output.
+//!
+//! The `vars` context is populated from the `[run.vars]` section of `Kick.toml`,
+//! which can be overridden for individual repos. Variables can also be specified
+//! with `--var <key>=<value>`.
+//!
+//! ```toml
+//! [run.vars]
+//! ENVIRONMENT = "staging"
+//! ```
+//!
+//! Uses of `actions/cache`, `actions/cache/restore` and `actions/cache/save` are
+//! emulated locally by storing caches in the Kick cache directory. Keys and
+//! `restore-keys` are matched the same way as on Github, and the `cache-hit`
+//! output is set accordingly. The least recently used caches are evicted once
+//! they exceed 10 GiB in total, which can be changed with `--cache-limit <MB>`.
+//! Paths relative to the home directory like `~/.cargo` are only supported for
+//! jobs which run on the host, since the home directory of a container is not
+//! accessible.
+//!
+//! Uses of `actions/upload-artifact` and `actions/download-artifact` are emulated
+//! by storing artifacts in a directory for the current run in the Kick cache
+//! directory, so that artifacts can be passed between jobs. Artifacts are removed
+//! once their `retention-days` have passed, which defaults to one day. To keep
+//! the artifacts of a run, they can be copied into a directory with `--artifacts
+//! <dir>`.
+//!
+//! When downloading with `merge-multiple: true`, a file from an artifact which
+//! sorts later by name replaces a file at the same path from an earlier one.
+//!
 //! Supported integrations are:
 //! * Running on the same operating system as where Kick is run (default).
 //! * Running Linux on Windows through WSL.
+//! * Running inside of a [Podman] container, for jobs which specify a
+//!   `container` or whose `runs-on` label is mapped to an image in `Kick.toml`.
+//!   The repo and the Kick cache are mounted at the same paths inside of the
+//!   container.
+//!
+//! ```toml
+//! [runners]
+//! "ubuntu-22.04" = "docker.io/library/ubuntu:22.04"
+//! ```
+//!
+//! Job `services` are started as [Podman] containers before the job runs, and
+//! are torn down once it completes. Published ports are available through
+//! `job.services.<id>.ports`, and services which specify a `--health-cmd` are
+//! waited on until they are healthy. If the job itself runs in a container, its
+//! services are reachable using their id as a hostname.
+//!
+//! Actions which use `runs.using: docker` are also run through [Podman], by
+//! either pulling or building their image. Images are only built once for actions
+//! pinned to a version, while local actions are rebuilt each time they are used.
+//!
+//! [Podman]: https://podman.io
 //!
 //! <br>
 //!
@@ -511,7 +644,7 @@ enum Command {
     /// Build an .rpm package (builtin).
     Rpm(SharedAction<cli::rpm::Opts>),
     /// Run a custom command.
-    Run(SharedAction<cli::run::Opts>),
+    Run(Box<SharedAction<cli::run::Opts>>),
     /// Manage sets.
     Set(SharedAction<cli::set::Opts>),
     /// Synchronize repositories.
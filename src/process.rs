@@ -2,15 +2,22 @@ use std::borrow::Cow;
 use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::fmt::Write;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::{ChildStdin, ChildStdout, ExitStatus, Output, Stdio};
 use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, anyhow};
 
-use crate::rstr::{RStr, RString};
+use crate::rstr::{self, RStr, RString};
 use crate::shell::Shell;
 
+/// How often to poll a process which has a timeout for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 #[derive(Clone)]
 enum OsArgKind {
     Path(Box<Path>),
@@ -27,12 +34,19 @@ pub(crate) struct OsArg {
 }
 
 impl OsArg {
+    /// Get the argument as a lossy string, where redacted sequences and
+    /// masked values are replaced with `***`.
     pub(crate) fn to_string_lossy(&self) -> Cow<'_, str> {
-        match &self.kind {
+        let string = match &self.kind {
             OsArgKind::Path(p) => p.to_string_lossy(),
             OsArgKind::Str(p) => Cow::Borrowed(p.as_ref()),
             OsArgKind::OsStr(s) => s.to_string_lossy(),
             OsArgKind::RStr(s) => Cow::Owned(s.to_string()),
+        };
+
+        match rstr::mask(&string) {
+            Cow::Borrowed(..) => string,
+            Cow::Owned(masked) => Cow::Owned(masked),
         }
     }
 
@@ -237,6 +251,94 @@ impl Command {
         Ok(status)
     }
 
+    /// Run the command to completion, killing it and any processes it has
+    /// spawned if it doesn't complete within `timeout`.
+    ///
+    /// The standard output of the command is read line by line and passed to
+    /// `line` without the trailing newline.
+    ///
+    /// Returns `None` if the command timed out.
+    #[tracing::instrument(skip_all, fields(command = self.display().to_string(), current_dir = ?self.current_dir_repr()))]
+    pub(crate) fn status_with_lines<F>(
+        &mut self,
+        timeout: Option<Duration>,
+        mut line: F,
+    ) -> Result<Option<ExitStatus>>
+    where
+        F: FnMut(&[u8]) -> Result<()>,
+    {
+        let mut command = self.command();
+        command.stdout(Stdio::piped());
+
+        let result = command.spawn();
+        let mut child = result.with_context(|| anyhow!("Spawning `{}`", self.display()))?;
+        let stdout = child.stdout.take().context("Missing stdout")?;
+
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).split(b'\n') {
+                let Ok(mut line) = line else {
+                    break;
+                };
+
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut status = None;
+
+        loop {
+            let wait = match deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(POLL_INTERVAL),
+                None => POLL_INTERVAL,
+            };
+
+            match rx.recv_timeout(wait) {
+                Ok(bytes) => {
+                    line(&bytes)?;
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                // NB: Output might still be held open by processes spawned by
+                // the command, so we stop reading once it's idle.
+                Err(mpsc::RecvTimeoutError::Timeout) if status.is_some() => break,
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+            }
+
+            if status.is_none() {
+                status = child.try_wait()?;
+            }
+
+            if status.is_none()
+                && let Some(deadline) = deadline
+                && Instant::now() >= deadline
+            {
+                tracing::trace!(?timeout, "Timed out");
+                kill_tree(&mut child);
+                child.wait()?;
+                return Ok(None);
+            }
+        }
+
+        let status = match status {
+            Some(status) => status,
+            None => child.wait()?,
+        };
+
+        tracing::trace!(status = status.to_string());
+        Ok(Some(status))
+    }
+
     #[tracing::instrument(skip_all, fields(command = self.display().to_string(), current_dir = ?self.current_dir_repr()))]
     pub(crate) fn output(&mut self) -> Result<Output> {
         let mut command = self.command();
@@ -335,6 +437,82 @@ impl fmt::Debug for Command {
     }
 }
 
+/// Kill the given child process and all of its descendants.
+///
+/// Note that we can't rely on process groups here, since shells with job
+/// control put each job they run into a process group of its own.
+fn kill_tree(child: &mut std::process::Child) {
+    #[cfg(unix)]
+    {
+        let mut pids = vec![child.id()];
+
+        // Collect all descendants before killing anything, since killing a
+        // process causes its children to be re-parented.
+        let output = std::process::Command::new("ps")
+            .args(["-A", "-o", "pid=", "-o", "ppid="])
+            .stderr(Stdio::null())
+            .output();
+
+        match output {
+            Ok(output) => {
+                let processes = String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter_map(|line| {
+                        let mut it = line.split_whitespace();
+                        let pid = it.next()?.parse::<u32>().ok()?;
+                        let ppid = it.next()?.parse::<u32>().ok()?;
+                        Some((pid, ppid))
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut n = 0;
+
+                while let Some(&parent) = pids.get(n) {
+                    pids.extend(
+                        processes
+                            .iter()
+                            .filter(|&&(_, ppid)| ppid == parent)
+                            .map(|&(pid, _)| pid),
+                    );
+
+                    n += 1;
+                }
+            }
+            Err(error) => {
+                tracing::warn!(?error, "Failed to list processes");
+            }
+        }
+
+        let status = std::process::Command::new("kill")
+            .arg("-KILL")
+            .args(pids.iter().map(u32::to_string))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        if let Err(error) = status {
+            tracing::warn!(?error, "Failed to kill process tree");
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        let status = std::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &child.id().to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        if let Err(error) = status {
+            tracing::warn!(?error, "Failed to kill process tree");
+        }
+    }
+
+    // Make sure the immediate child is killed, even if killing the tree
+    // failed.
+    _ = child.kill();
+}
+
 pub(crate) struct Child {
     child: std::process::Child,
 }
@@ -348,6 +526,12 @@ impl Child {
         self.child.stdout.take().context("Missing stdout")
     }
 
+    pub(crate) fn wait(&mut self) -> Result<ExitStatus> {
+        let status = self.child.wait()?;
+        tracing::trace!(?status);
+        Ok(status)
+    }
+
     pub(crate) fn wait_with_output(self) -> Result<Output> {
         let output = self.child.wait_with_output()?;
         tracing::trace!(?output.status);
@@ -378,7 +562,7 @@ impl fmt::Display for Display<'_> {
 
         let escaped = self.shell.escape(lossy.as_ref());
 
-        if let (Shell::Powershell, Cow::Owned(..)) = (self.shell, &escaped) {
+        if let (Shell::Powershell | Shell::Pwsh, Cow::Owned(..)) = (self.shell, &escaped) {
             "& ".fmt(f)?;
         }
 
@@ -11,6 +11,7 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::{Mutex, MutexGuard};
 
 /// The redaction string.
 const REDACTION: &str = "***";
@@ -23,6 +24,54 @@ const START: u32 = 0xE0000;
 const TAG_START: &str = "\u{E0001}";
 const TAG_END: &str = "\u{E007F}";
 
+/// Values which have been masked, like through the `::add-mask::` workflow
+/// command.
+///
+/// These are shared by every thread in the process, since output is written
+/// from several threads when jobs are run in parallel.
+static MASKS: Mutex<Vec<Box<str>>> = Mutex::new(Vec::new());
+
+fn masks() -> MutexGuard<'static, Vec<Box<str>>> {
+    MASKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Register a value which should be masked whenever it is displayed.
+pub(crate) fn add_mask(value: &str) {
+    let value = value.trim();
+
+    if value.is_empty() {
+        return;
+    }
+
+    let mut masks = masks();
+
+    if !masks.iter().any(|m| **m == *value) {
+        masks.push(value.into());
+        // NB: Longer values are masked first, in case they overlap.
+        masks.sort_by_key(|m| std::cmp::Reverse(m.len()));
+    }
+}
+
+/// Get all values which have been registered through [`add_mask`], so that
+/// they can be passed on to another process.
+pub(crate) fn registered_masks() -> Vec<String> {
+    masks().iter().map(|m| m.to_string()).collect()
+}
+
+/// Replace all masked values in the given string with `***`.
+pub(crate) fn mask(string: &str) -> Cow<'_, str> {
+    let masks = masks();
+    let mut string = Cow::Borrowed(string);
+
+    for m in masks.iter() {
+        if string.contains(&**m) {
+            string = Cow::Owned(string.replace(&**m, REDACTION));
+        }
+    }
+
+    string
+}
+
 /// A borrowed string which might contain redacted sequences.
 ///
 /// Trying to format the string will result in those redacted sequences being
@@ -219,15 +268,7 @@ impl AsRef<RStr> for RStr {
 
 impl fmt::Display for RStr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for chunk in self.chunks() {
-            f.write_str(chunk.public())?;
-
-            if !chunk.redacted().is_empty() {
-                f.write_str(REDACTION)?;
-            }
-        }
-
-        Ok(())
+        f.write_str(&mask(&self.to_string_lossy()))
     }
 }
 
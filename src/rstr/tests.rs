@@ -41,3 +41,45 @@ fn test_eq() {
     assert!(a.str_eq("prefixfoobarsuffix"));
     assert!(b.str_eq("prefixfoobarsuffix"));
 }
+
+#[test]
+fn test_mask() {
+    add_mask("hunter2");
+    add_mask("hunter");
+    add_mask("  ");
+
+    assert_eq!(mask("no secrets"), "no secrets");
+    assert_eq!(mask("hunter2 and hunter"), "*** and ***");
+
+    let owned = RString::from("password: hunter2");
+    assert_eq!(owned.to_string(), "password: ***");
+    assert_eq!(owned.to_exposed(), "password: hunter2");
+}
+
+#[test]
+fn test_mask_display() {
+    // NB: Masks are process-wide, so use values no other test uses.
+    add_mask("kick-display-mask");
+
+    let mut owned = RString::new();
+    owned.push_rstr("mask: kick-display-mask, ");
+    owned.push_redacted("kick-display-secret");
+
+    assert_eq!(format!("{owned}"), "mask: ***, ***");
+    assert_eq!(format!("{}", owned.as_rstr()), "mask: ***, ***");
+    assert_eq!(
+        owned.to_exposed(),
+        "mask: kick-display-mask, kick-display-secret"
+    );
+}
+
+#[test]
+fn test_mask_threads() {
+    std::thread::spawn(|| add_mask("kick-thread-mask"))
+        .join()
+        .unwrap();
+
+    assert!(registered_masks().iter().any(|m| m == "kick-thread-mask"));
+    assert_eq!(mask("token kick-thread-mask"), "token ***");
+    assert_eq!(RString::from("kick-thread-mask").to_string(), "***");
+}
@@ -1,8 +1,15 @@
+#[cfg(test)]
+mod tests;
+
 use std::borrow::Cow;
 use std::fmt;
 
+use anyhow::{Result, bail};
 use clap::ValueEnum;
 
+use crate::rstr::{RStr, RString};
+use crate::workflows::split_options;
+
 macro_rules! base {
     ($($pat:pat_param)|*) => {
         'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '=' | '/' | ',' | '.' | '+' $(| $pat)*
@@ -13,7 +20,10 @@ macro_rules! base {
 pub(crate) enum Shell {
     #[default]
     Bash,
+    Sh,
     Powershell,
+    Pwsh,
+    Cmd,
 }
 
 impl Shell {
@@ -21,7 +31,7 @@ impl Shell {
     pub(crate) fn escape<'a>(&self, source: &'a str) -> Cow<'a, str> {
         let i = 'escape: {
             match *self {
-                Shell::Bash => {
+                Shell::Bash | Shell::Sh => {
                     for (i, c) in source.char_indices() {
                         match c {
                             base!() => continue,
@@ -29,7 +39,15 @@ impl Shell {
                         }
                     }
                 }
-                Shell::Powershell => {
+                Shell::Cmd => {
+                    for (i, c) in source.char_indices() {
+                        match c {
+                            base!('\\' | ':') => continue,
+                            _ => break 'escape i,
+                        }
+                    }
+                }
+                Shell::Powershell | Shell::Pwsh => {
                     for (i, c) in source.char_indices() {
                         match c {
                             base!('\\' | ':' | '`') => continue,
@@ -53,10 +71,10 @@ impl Shell {
     /// Test if the environment literal needs to be escaped.
     pub(crate) fn is_env_literal(&self, s: &str) -> bool {
         match *self {
-            Shell::Bash => s
+            Shell::Bash | Shell::Cmd => s
                 .chars()
                 .all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-')),
-            Shell::Powershell => s
+            Shell::Sh | Shell::Powershell | Shell::Pwsh => s
                 .chars()
                 .all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_')),
         }
@@ -86,26 +104,54 @@ impl Shell {
     fn escapes(&self) -> &'static Escapes {
         match *self {
             Shell::Bash => &Escapes {
-                dollar: "\\$",
+                dollar: Some("\\$"),
                 backslash: Some("\\\\"),
-                backtick: "\\`",
-                double: "\\\"",
-                single: "\\'",
-                esclamation: "\\!",
-                n: "\\n",
-                r: "\\r",
-                t: "\\t",
+                backtick: Some("\\`"),
+                double: Some("\\\""),
+                single: Some("\\'"),
+                esclamation: Some("\\!"),
+                percent: None,
+                n: Some("\\n"),
+                r: Some("\\r"),
+                t: Some("\\t"),
+            },
+            // NB: POSIX shells only treat a handful of characters specially
+            // inside of double quotes, and have no escapes for whitespace.
+            Shell::Sh => &Escapes {
+                dollar: Some("\\$"),
+                backslash: Some("\\\\"),
+                backtick: Some("\\`"),
+                double: Some("\\\""),
+                single: None,
+                esclamation: None,
+                percent: None,
+                n: None,
+                r: None,
+                t: None,
+            },
+            Shell::Powershell | Shell::Pwsh => &Escapes {
+                dollar: Some("`$"),
+                backslash: None,
+                backtick: Some("``"),
+                double: Some("`\""),
+                single: Some("`'"),
+                esclamation: Some("`!"),
+                percent: None,
+                n: Some("`n"),
+                r: Some("`r"),
+                t: Some("`t"),
             },
-            Shell::Powershell => &Escapes {
-                dollar: "`$",
+            Shell::Cmd => &Escapes {
+                dollar: None,
                 backslash: None,
-                backtick: "``",
-                double: "`\"",
-                single: "`'",
-                esclamation: "`!",
-                n: "`n",
-                r: "`r",
-                t: "`t",
+                backtick: None,
+                double: Some("\"\""),
+                single: None,
+                esclamation: None,
+                percent: Some("%%"),
+                n: None,
+                r: None,
+                t: None,
             },
         }
     }
@@ -116,36 +162,158 @@ impl fmt::Display for Shell {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Shell::Bash => write!(f, "bash"),
+            Shell::Sh => write!(f, "sh"),
             Shell::Powershell => write!(f, "powershell"),
+            Shell::Pwsh => write!(f, "pwsh"),
+            Shell::Cmd => write!(f, "cmd"),
         }
     }
 }
 
 pub(crate) struct Escapes {
-    dollar: &'static str,
+    dollar: Option<&'static str>,
     backslash: Option<&'static str>,
-    backtick: &'static str,
-    double: &'static str,
-    single: &'static str,
-    esclamation: &'static str,
-    n: &'static str,
-    r: &'static str,
-    t: &'static str,
+    backtick: Option<&'static str>,
+    double: Option<&'static str>,
+    single: Option<&'static str>,
+    esclamation: Option<&'static str>,
+    percent: Option<&'static str>,
+    n: Option<&'static str>,
+    r: Option<&'static str>,
+    t: Option<&'static str>,
 }
 
 impl Escapes {
     pub(crate) fn escape(&self, c: char) -> Option<&str> {
         match c {
-            '$' => Some(self.dollar),
+            '$' => self.dollar,
             '\\' => self.backslash,
-            '`' => Some(self.backtick),
-            '"' => Some(self.double),
-            '\'' => Some(self.single),
-            '!' => Some(self.esclamation),
-            '\n' => Some(self.n),
-            '\r' => Some(self.r),
-            '\t' => Some(self.t),
+            '`' => self.backtick,
+            '"' => self.double,
+            '\'' => self.single,
+            '!' => self.esclamation,
+            '%' => self.percent,
+            '\n' => self.n,
+            '\r' => self.r,
+            '\t' => self.t,
             _ => None,
         }
     }
 }
+
+/// The shell that the script of a `run` step is run with, as specified
+/// through `shell:`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ScriptShell {
+    /// The shell used when none is specified.
+    Default,
+    Bash,
+    Sh,
+    Pwsh,
+    Powershell,
+    Cmd,
+    Python,
+    /// A custom shell command, where `{0}` is replaced with the path to the
+    /// script.
+    Custom(Box<str>),
+}
+
+impl ScriptShell {
+    /// Parse the value of `shell:`.
+    pub(crate) fn parse(shell: &str) -> Result<Self> {
+        let shell = shell.trim();
+
+        Ok(match shell {
+            "bash" => Self::Bash,
+            "sh" => Self::Sh,
+            "pwsh" => Self::Pwsh,
+            "powershell" => Self::Powershell,
+            "cmd" => Self::Cmd,
+            "python" => Self::Python,
+            _ => {
+                if !shell.contains("{0}") {
+                    bail!("Unsupported shell `{shell}`, custom shells must contain `{{0}}`");
+                }
+
+                Self::Custom(shell.into())
+            }
+        })
+    }
+
+    /// The command used to run a script, where `{0}` is the path to the
+    /// script file.
+    pub(crate) fn template(&self) -> Result<Vec<String>> {
+        let args: &[&str] = match self {
+            Self::Default => &["bash", "-e", "{0}"],
+            Self::Bash => &["bash", "--noprofile", "--norc", "-eo", "pipefail", "{0}"],
+            Self::Sh => &["sh", "-e", "{0}"],
+            Self::Pwsh => &["pwsh", "-command", ". '{0}'"],
+            Self::Powershell => &["powershell", "-command", ". '{0}'"],
+            Self::Cmd => &["cmd", "/D", "/E:ON", "/V:OFF", "/S", "/C", "CALL", "{0}"],
+            Self::Python => &["python", "{0}"],
+            Self::Custom(template) => return split_options(template),
+        };
+
+        Ok(args.iter().map(|s| (*s).to_owned()).collect())
+    }
+
+    /// The extension of the script file, if any.
+    ///
+    /// Custom shells get the extension of the shell they run, like `python` in
+    /// `python -u {0}`.
+    pub(crate) fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::Default | Self::Bash | Self::Sh => Some("sh"),
+            Self::Pwsh | Self::Powershell => Some("ps1"),
+            Self::Cmd => Some("cmd"),
+            Self::Python => Some("py"),
+            Self::Custom(template) => {
+                let program = template.split_whitespace().next()?;
+                let program = program.rsplit(['/', '\\']).next()?;
+                let program = program.strip_suffix(".exe").unwrap_or(program);
+
+                match program {
+                    "bash" | "sh" => Some("sh"),
+                    "pwsh" | "powershell" => Some("ps1"),
+                    "cmd" => Some("cmd"),
+                    "python" | "python3" => Some("py"),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Prepare the contents of the script file.
+    ///
+    /// Like GitHub, PowerShell scripts stop at the first error and exit with
+    /// the code of the last native command.
+    pub(crate) fn contents(&self, script: &RStr) -> Box<RStr> {
+        match self {
+            Self::Pwsh | Self::Powershell => {
+                let mut out = RString::new();
+                out.push_rstr("$ErrorActionPreference = 'stop'\n");
+                out.push_rstr(script);
+                out.push_rstr(
+                    "\nif ((Test-Path -LiteralPath variable:\\LASTEXITCODE)) { exit $LASTEXITCODE }\n",
+                );
+                out.into()
+            }
+            _ => script.into(),
+        }
+    }
+}
+
+impl fmt::Display for ScriptShell {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default | Self::Bash => write!(f, "bash"),
+            Self::Sh => write!(f, "sh"),
+            Self::Pwsh => write!(f, "pwsh"),
+            Self::Powershell => write!(f, "powershell"),
+            Self::Cmd => write!(f, "cmd"),
+            Self::Python => write!(f, "python"),
+            Self::Custom(template) => template.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use super::*;
+
+#[test]
+fn test_escape() {
+    assert_eq!(Shell::Bash.escape("hello"), "hello");
+    assert_eq!(Shell::Bash.escape("it's $HOME!"), r#""it\'s \$HOME\!""#);
+    assert_eq!(Shell::Sh.escape("it's $HOME!"), r#""it's \$HOME!""#);
+    assert_eq!(Shell::Pwsh.escape("it's $HOME"), r#""it`'s `$HOME""#);
+    assert_eq!(Shell::Cmd.escape(r"C:\dir"), r"C:\dir");
+    assert_eq!(Shell::Cmd.escape(r#"say "100%""#), r#""say ""100%%""""#);
+}
+
+#[test]
+fn test_script_shell() -> Result<()> {
+    assert_eq!(ScriptShell::parse("pwsh")?, ScriptShell::Pwsh);
+    assert!(ScriptShell::parse("fish").is_err());
+
+    let shell = ScriptShell::parse("perl -w {0}")?;
+    assert_eq!(shell.template()?, ["perl", "-w", "{0}"]);
+    assert_eq!(shell.extension(), None);
+
+    let shell = ScriptShell::parse("/usr/bin/python3 -u {0}")?;
+    assert_eq!(shell.extension(), Some("py"));
+
+    assert_eq!(ScriptShell::Cmd.extension(), Some("cmd"));
+    assert_eq!(ScriptShell::Powershell.extension(), Some("ps1"));
+    Ok(())
+}
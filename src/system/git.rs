@@ -315,6 +315,161 @@ impl Git {
         Ok(str::from_utf8(&output.stdout)?.trim().to_owned())
     }
 
+    /// Get the full name of the branch that HEAD points to, like
+    /// `refs/heads/main`.
+    ///
+    /// Returns `None` if HEAD is detached.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path))]
+    pub(crate) fn symbolic_ref(&self, dir: impl AsRef<Path>) -> Result<Option<String>> {
+        let output = Command::new(&self.path)
+            .args(["symbolic-ref", "--quiet", "HEAD"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .output()?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(Some(str::from_utf8(&output.stdout)?.trim().to_owned()))
+    }
+
+    /// Get the tags which point to the given revision.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path))]
+    pub(crate) fn tags_at(
+        &self,
+        dir: impl AsRef<Path>,
+        rev: impl AsRef<str>,
+    ) -> Result<Vec<String>> {
+        let output = Command::new(&self.path)
+            .args(["tag", "--points-at", rev.as_ref()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .output()?;
+
+        ensure!(output.status.success(), output.status);
+
+        let tags = str::from_utf8(&output.stdout)?
+            .lines()
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect();
+
+        Ok(tags)
+    }
+
+    /// Get the paths which differ between the given revision and the working
+    /// tree.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path))]
+    pub(crate) fn diff_names(
+        &self,
+        dir: impl AsRef<Path>,
+        rev: impl AsRef<str>,
+    ) -> Result<Vec<String>> {
+        let output = Command::new(&self.path)
+            .args(["diff", "--name-only", rev.as_ref()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .output()?;
+
+        ensure!(output.status.success(), output.status);
+
+        let paths = str::from_utf8(&output.stdout)?
+            .lines()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(str::to_owned)
+            .collect();
+
+        Ok(paths)
+    }
+
+    /// Get a configuration value.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path))]
+    pub(crate) fn config_get(&self, dir: impl AsRef<Path>, key: &str) -> Result<Option<String>> {
+        let output = Command::new(&self.path)
+            .args(["config", "--get", key])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .output()?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(Some(str::from_utf8(&output.stdout)?.trim().to_owned()))
+    }
+
+    /// Create a commit which captures uncommitted changes to tracked files,
+    /// without modifying the working tree.
+    ///
+    /// Returns `None` if there are no changes.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path))]
+    pub(crate) fn stash_create(&self, dir: impl AsRef<Path>) -> Result<Option<String>> {
+        let output = Command::new(&self.path)
+            .args(["stash", "create"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .output()?;
+
+        ensure!(output.status.success(), output.status);
+        let rev = str::from_utf8(&output.stdout)?.trim();
+        Ok((!rev.is_empty()).then(|| rev.to_owned()))
+    }
+
+    /// Add a detached worktree at the given revision.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path, path = ?path.as_ref()))]
+    pub(crate) fn worktree_add(
+        &self,
+        dir: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+        rev: impl AsRef<str>,
+    ) -> Result<()> {
+        let status = Command::new(&self.path)
+            .args(["worktree", "add", "--force", "--detach"])
+            .arg(path.as_ref())
+            .arg(rev.as_ref())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .status()?;
+
+        ensure!(status.success(), status);
+        Ok(())
+    }
+
+    /// Remove a worktree.
+    #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path, path = ?path.as_ref()))]
+    pub(crate) fn worktree_remove(
+        &self,
+        dir: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let status = Command::new(&self.path)
+            .args(["worktree", "remove", "--force"])
+            .arg(path.as_ref())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .current_dir(dir)
+            .status()?;
+
+        ensure!(status.success(), status);
+        Ok(())
+    }
+
     /// Get HEAD commit.
     #[tracing::instrument(skip_all, fields(dir = ?dir.as_ref(), command = ?self.path, ?fetch))]
     pub(crate) fn describe_tags(
@@ -2,6 +2,7 @@ mod dnf;
 mod generic;
 pub(crate) mod git;
 mod node;
+mod podman;
 mod wsl;
 
 use std::collections::BTreeSet;
@@ -25,6 +26,7 @@ pub(crate) use self::generic::Generic;
 pub(crate) use self::git::Git;
 pub(crate) use self::node::Node;
 use self::node::NodeVersion;
+pub(crate) use self::podman::Podman;
 pub(crate) use self::wsl::Wsl;
 
 type ProbeFn = fn(&mut System, &Path) -> Result<()>;
@@ -61,7 +63,7 @@ pub(crate) struct System {
     pub(crate) powershell: Vec<Generic>,
     pub(crate) bash: Vec<Generic>,
     pub(crate) node: Vec<Node>,
-    pub(crate) podman: Vec<Generic>,
+    pub(crate) podman: Vec<Podman>,
     pub(crate) dnf: Vec<Dnf>,
     pub(crate) sudo: Vec<Generic>,
     pub(crate) dpkg_query: Vec<Generic>,
@@ -418,7 +420,7 @@ fn bash_probe(s: &mut System, path: &Path) -> Result<()> {
 
 fn podman_probe(s: &mut System, path: &Path) -> Result<()> {
     if probe(path, "--version")? {
-        s.podman.push(Generic::new(path.to_owned()));
+        s.podman.push(Podman::new(path.to_owned()));
     }
 
     Ok(())
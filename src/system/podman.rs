@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{Context, Result, ensure};
+
+use crate::process::Command;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Podman {
+    pub(crate) path: PathBuf,
+}
+
+impl Podman {
+    #[inline]
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Set up a command which runs a container which is removed once it
+    /// exits, with every path in `mounts` mounted at the same location inside
+    /// of the container.
+    ///
+    /// The caller is responsible for adding any options, followed by the image
+    /// and its arguments.
+    pub(crate) fn run<'a>(&self, mounts: impl IntoIterator<Item = &'a Path>) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(["run", "--rm", "--security-opt", "label=disable"]);
+
+        for mount in mounts {
+            let mount = mount.to_string_lossy();
+            command.arg("--volume").arg(format!("{mount}:{mount}"));
+        }
+
+        command
+    }
+
+    /// Set up a command which executes inside of a container.
+    ///
+    /// The caller is responsible for adding any options, followed by the name
+    /// of the container and the command to execute.
+    pub(crate) fn exec(&self, dir: &Path) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(["exec", "--interactive", "--workdir"]);
+        command.arg(dir);
+        command
+    }
+
+    /// Test if an image with the given name exists locally.
+    pub(crate) fn image_exists(&self, name: &str) -> Result<bool> {
+        let status = Command::new(&self.path)
+            .args(["image", "exists", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+
+        Ok(status.success())
+    }
+
+    /// Kill every process running inside of a container except for its init
+    /// process.
+    pub(crate) fn kill_all(&self, name: &str) -> Result<()> {
+        let status = Command::new(&self.path)
+            .args(["exec", name, "sh", "-c", "kill -KILL -1"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+
+        ensure!(status.success(), status);
+        Ok(())
+    }
+
+    /// Get the `PATH` configured inside of a running container, if any.
+    pub(crate) fn path(&self, name: &str) -> Result<Option<String>> {
+        let output = Command::new(&self.path)
+            .args(["inspect", "--format", "{{json .Config.Env}}", name])
+            .stderr(Stdio::inherit())
+            .output()?;
+
+        ensure!(output.status.success(), output.status);
+
+        let env: Vec<String> =
+            serde_json::from_slice(&output.stdout).context("Parsing container environment")?;
+
+        let path = env.iter().find_map(|e| e.strip_prefix("PATH="));
+        Ok(path.map(str::to_owned))
+    }
+
+    /// Run the health check of a container, returning `true` if it is healthy.
+    pub(crate) fn healthcheck(&self, name: &str) -> Result<bool> {
+        let status = Command::new(&self.path)
+            .args(["healthcheck", "run", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+
+        Ok(status.success())
+    }
+
+    /// Get the ports published by a container, as pairs of container ports and
+    /// the host ports they are published on.
+    pub(crate) fn ports(&self, name: &str) -> Result<Vec<(String, String)>> {
+        let output = Command::new(&self.path)
+            .args(["port", name])
+            .stderr(Stdio::inherit())
+            .output()?;
+
+        ensure!(output.status.success(), output.status);
+
+        let output = String::from_utf8(output.stdout).context("Decoding port output")?;
+        let mut ports = Vec::new();
+
+        // Each line looks like `5432/tcp -> 0.0.0.0:49153`.
+        for line in output.lines() {
+            let Some((container, host)) = line.split_once(" -> ") else {
+                continue;
+            };
+
+            let (container, _) = container.split_once('/').unwrap_or((container, ""));
+
+            let Some((_, host)) = host.rsplit_once(':') else {
+                continue;
+            };
+
+            if ports.iter().any(|(c, _)| c == container) {
+                continue;
+            }
+
+            ports.push((container.to_owned(), host.trim().to_owned()));
+        }
+
+        Ok(ports)
+    }
+
+    /// Create a network.
+    pub(crate) fn create_network(&self, name: &str) -> Result<()> {
+        let status = Command::new(&self.path)
+            .args(["network", "create", name])
+            .stdout(Stdio::null())
+            .status()?;
+
+        ensure!(status.success(), status);
+        Ok(())
+    }
+
+    /// Remove a network.
+    pub(crate) fn remove_network(&self, name: &str) -> Result<()> {
+        let status = Command::new(&self.path)
+            .args(["network", "rm", "--force", name])
+            .stdout(Stdio::null())
+            .status()?;
+
+        ensure!(status.success(), status);
+        Ok(())
+    }
+
+    /// Forcibly remove a container.
+    pub(crate) fn remove(&self, name: &str) -> Result<()> {
+        let status = Command::new(&self.path)
+            .args(["rm", "--force", "--time", "0", name])
+            .stdout(Stdio::null())
+            .status()?;
+
+        ensure!(status.success(), status);
+        Ok(())
+    }
+}
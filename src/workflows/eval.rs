@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 
@@ -84,6 +85,8 @@ use EvalErrorKind::*;
 pub(crate) enum Expr<'m> {
     /// An array of values.
     Array(Box<[Expr<'m>]>),
+    /// An object of values.
+    Object(BTreeMap<String, Expr<'m>>),
     /// A string expression.
     String(Cow<'m, RStr>),
     /// A floating-point expression.
@@ -115,6 +118,7 @@ impl Expr<'_> {
     pub(crate) fn as_f64(&self) -> f64 {
         match *self {
             Self::Array(..) => f64::NAN,
+            Self::Object(..) => f64::NAN,
             // NB: non-empty strings treated as numbers are not numbers.
             Self::String(ref string) => {
                 if string.is_empty() {
@@ -139,6 +143,7 @@ impl Expr<'_> {
     pub(crate) fn as_bool(&self) -> bool {
         match *self {
             Self::Array(..) => false,
+            Self::Object(..) => true,
             Self::String(ref string) => !string.is_empty(),
             Self::Float(float) => {
                 if float.is_nan() {
@@ -152,11 +157,15 @@ impl Expr<'_> {
         }
     }
 
-    /// Get the expression as a string.
-    pub(crate) fn as_str(&self) -> Option<&RStr> {
+    /// Coerce an expression into a string.
+    pub(crate) fn to_rstr(&self) -> Cow<'_, RStr> {
         match *self {
-            Self::String(ref string) => Some(string),
-            _ => None,
+            Self::Array(..) => Cow::Borrowed(RStr::new("Array")),
+            Self::Object(..) => Cow::Borrowed(RStr::new("Object")),
+            Self::String(ref string) => Cow::Borrowed(string),
+            Self::Float(float) => Cow::Owned(RString::from(float.to_string())),
+            Self::Bool(b) => Cow::Borrowed(RStr::new(if b { "true" } else { "false" })),
+            Self::Null => Cow::Borrowed(RStr::new("")),
         }
     }
 }
@@ -187,7 +196,7 @@ fn eval_node<'a, 'b: 'a>(
                     return Err(EvalError::new(*node.span(), MissingFunction(ident.into())));
                 };
 
-                function(node.span(), &args)
+                function(eval, node.span(), &args)
             }
             Group => {
                 node = node
@@ -211,24 +220,47 @@ fn eval_node<'a, 'b: 'a>(
                 Ok(Expr::Float(number))
             }
             Lookup => {
-                let keys = node
-                    .children()
-                    .skip_tokens()
-                    .map(|n| &source[n.span().range()]);
-
-                let value = eval.lookup(keys);
-
-                match &value[..] {
-                    [] => Ok(Expr::Null),
-                    [value] => Ok(Expr::String(Cow::Borrowed(*value))),
-                    values => {
-                        let values = values
-                            .iter()
-                            .map(|v| Expr::String(Cow::Borrowed(*v)))
-                            .collect::<Vec<_>>()
-                            .into();
-                        Ok(Expr::Array(values))
-                    }
+                let mut keys = Vec::new();
+                let mut filtered = false;
+
+                for n in node.children().skip_tokens() {
+                    let key = lookup_key(n, source, eval)?;
+                    filtered |= key.is_none();
+                    keys.push(key);
+                }
+
+                let mut values = eval.lookup(keys.iter().map(|k| k.as_deref().unwrap_or("*")));
+
+                if filtered {
+                    Ok(Expr::Array(values.into()))
+                } else {
+                    Ok(values.pop().unwrap_or(Expr::Null))
+                }
+            }
+            Access => {
+                let mut it = node.children().skip_tokens();
+
+                let first = it
+                    .next()
+                    .ok_or(EvalError::new(*node.span(), ExpectedExpression))?;
+
+                let mut values = vec![eval_node(first, source, eval)?];
+                let mut filtered = false;
+
+                for n in it {
+                    let key = lookup_key(n, source, eval)?;
+                    filtered |= key.is_none();
+
+                    values = values
+                        .into_iter()
+                        .flat_map(|value| select(value, key.as_deref()))
+                        .collect();
+                }
+
+                if filtered {
+                    Ok(Expr::Array(values.into()))
+                } else {
+                    Ok(values.pop().unwrap_or(Expr::Null))
                 }
             }
             SingleString | DoubleString => {
@@ -309,6 +341,62 @@ fn eval_node<'a, 'b: 'a>(
     }
 }
 
+/// Evaluate the key of a lookup segment, where `None` indicates the `*` object
+/// filter.
+fn lookup_key<'a, 'b: 'a>(
+    node: Node<'a, Syntax, FlavorDefault>,
+    source: &'b str,
+    eval: &'b Eval,
+) -> Result<Option<Cow<'b, str>>, EvalError> {
+    match node.value() {
+        Star => Ok(None),
+        Index => {
+            let index = node
+                .children()
+                .skip_tokens()
+                .next()
+                .ok_or(EvalError::new(*node.span(), ExpectedExpression))?;
+
+            let index = eval_node(index, source, eval)?;
+
+            match index {
+                Expr::Float(f) if f.is_finite() && f.fract() == 0.0 && f >= 0.0 => {
+                    Ok(Some(Cow::Owned(f.to_string())))
+                }
+                Expr::String(string) => Ok(Some(Cow::Owned(string.to_exposed().into_owned()))),
+                index => Err(EvalError::custom(
+                    *node.span(),
+                    format_args!("Cannot index using {index:?}"),
+                )),
+            }
+        }
+        _ => Ok(Some(Cow::Borrowed(&source[node.span().range()]))),
+    }
+}
+
+/// Select the given key out of a value, where `None` selects every element of
+/// an array or object.
+fn select<'a>(value: Expr<'a>, key: Option<&str>) -> Vec<Expr<'a>> {
+    match (value, key) {
+        (Expr::Array(values), None) => values.into_vec(),
+        (Expr::Object(values), None) => values.into_values().collect(),
+        (Expr::Array(values), Some(key)) => {
+            let Ok(index) = key.parse::<usize>() else {
+                return Vec::new();
+            };
+
+            values
+                .into_vec()
+                .into_iter()
+                .nth(index)
+                .into_iter()
+                .collect()
+        }
+        (Expr::Object(mut values), Some(key)) => values.remove(key).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
 fn op_not<'a>(_: &Span<u32>, expr: Expr<'a>) -> Result<Expr<'a>, EvalError> {
     Ok(Expr::Bool(!expr.as_bool()))
 }
@@ -428,6 +516,23 @@ fn unescape(string: &str) -> Option<Cow<'_, str>> {
     Some(Cow::Owned(unescaped))
 }
 
+/// Test if any lookup in the tree refers to the given context, like `needs` in
+/// `needs.build.outputs.value`.
+pub(crate) fn uses_context(
+    tree: &Tree<Syntax, FlavorDefault>,
+    source: &str,
+    context: &str,
+) -> bool {
+    tree.walk().any(|node| {
+        node.value() == Lookup
+            && node
+                .children()
+                .skip_tokens()
+                .next()
+                .is_some_and(|first| &source[first.range()] == context)
+    })
+}
+
 /// Eval a tree emitting all available expressions parsed from it.
 pub(crate) fn eval<'a, 'b: 'a>(
     tree: &'a Tree<Syntax, FlavorDefault>,
@@ -1,27 +1,40 @@
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
 
 use anyhow::Result;
+use relative_path::Component;
+use sha2::{Digest, Sha256};
 use syntree::Span;
 
-use crate::rstr::RString;
+use crate::glob::Glob;
+use crate::rstr::{RStr, RString};
 
-use super::{CustomFunction, EvalError, Expr};
+use super::{CustomFunction, Eval, EvalError, Expr};
 
 /// Default lookup function.
 pub(crate) fn lookup_function(name: &str) -> Option<CustomFunction> {
     match name {
         "fromJSON" => Some(from_json),
+        "toJSON" => Some(to_json),
         "startsWith" => Some(starts_with),
+        "endsWith" => Some(ends_with),
         "contains" => Some(contains),
+        "format" => Some(format),
+        "join" => Some(join),
+        "always" => Some(always),
         "cancelled" => Some(cancelled),
         "failure" => Some(failure),
         "success" => Some(success),
-        "hashFiles" => Some(not_implemented),
+        "hashFiles" => Some(hash_files),
         _ => None,
     }
 }
 
-fn from_json<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+fn from_json<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
     let [Expr::String(string)] = args else {
         return Err(EvalError::custom(*span, "Expected one string argument"));
     };
@@ -37,74 +50,362 @@ fn from_json<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalEr
     }
 }
 
-fn starts_with<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
-    let [Expr::String(what), Expr::String(expect)] = args else {
+fn to_json<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let [value] = args else {
+        return Err(EvalError::custom(*span, "Expected one argument"));
+    };
+
+    let mut out = RString::new();
+    write_json(&mut out, value, 0);
+    Ok(Expr::String(Cow::Owned(out)))
+}
+
+fn starts_with<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let [what, expect] = args else {
         return Err(EvalError::custom(*span, "Expected two arguments"));
     };
 
-    let what = what.to_exposed();
-    let expect = expect.to_exposed();
-    Ok(Expr::Bool(what.starts_with(&*expect)))
+    let what = what.to_rstr().to_exposed().to_lowercase();
+    let expect = expect.to_rstr().to_exposed().to_lowercase();
+    Ok(Expr::Bool(what.starts_with(&expect)))
 }
 
-fn contains<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
-    let [lhs, Expr::String(needle)] = args else {
-        return Err(EvalError::custom(
-            *span,
-            "Expected two strings as arguments",
-        ));
+fn ends_with<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let [what, expect] = args else {
+        return Err(EvalError::custom(*span, "Expected two arguments"));
     };
 
-    match lhs {
-        Expr::String(string) => {
-            let needle = needle.to_exposed();
-            let string = string.to_exposed();
-            Ok(Expr::Bool(string.contains(&*needle)))
+    let what = what.to_rstr().to_exposed().to_lowercase();
+    let expect = expect.to_rstr().to_exposed().to_lowercase();
+    Ok(Expr::Bool(what.ends_with(&expect)))
+}
+
+fn contains<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let [search, item] = args else {
+        return Err(EvalError::custom(*span, "Expected two arguments"));
+    };
+
+    if let Expr::Array(array) = search {
+        return Ok(Expr::Bool(array.iter().any(|value| loose_eq(value, item))));
+    }
+
+    let search = search.to_rstr().to_exposed().to_lowercase();
+    let item = item.to_rstr().to_exposed().to_lowercase();
+    Ok(Expr::Bool(search.contains(&item)))
+}
+
+/// Test if two values are equal in the way that `contains` compares an item
+/// against the elements of an array, where strings are compared ignoring case.
+fn loose_eq(lhs: &Expr<'_>, rhs: &Expr<'_>) -> bool {
+    match (lhs, rhs) {
+        (Expr::String(lhs), Expr::String(rhs)) => {
+            lhs.to_exposed().to_lowercase() == rhs.to_exposed().to_lowercase()
+        }
+        (Expr::Array(..) | Expr::Object(..), _) | (_, Expr::Array(..) | Expr::Object(..)) => false,
+        (Expr::Null, Expr::Null) => true,
+        (lhs, rhs) => lhs.as_f64() == rhs.as_f64(),
+    }
+}
+
+fn format<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let [format, args @ ..] = args else {
+        return Err(EvalError::custom(*span, "Expected format string argument"));
+    };
+
+    let format = format.to_rstr();
+
+    // NB: Redacted sections of the format string are never braces, so we can
+    // process the raw string and retain redaction.
+    let mut it = format.as_raw().chars().peekable();
+    let mut out = RString::new();
+
+    while let Some(c) = it.next() {
+        match c {
+            '{' if it.next_if_eq(&'{').is_some() => {
+                out.push('{');
+            }
+            '}' if it.next_if_eq(&'}').is_some() => {
+                out.push('}');
+            }
+            '{' => {
+                let mut index = String::new();
+
+                loop {
+                    match it.next() {
+                        Some('}') => break,
+                        Some(c) if c.is_ascii_digit() => index.push(c),
+                        _ => {
+                            return Err(EvalError::custom(
+                                *span,
+                                "Invalid format string, expected `{N}`",
+                            ));
+                        }
+                    }
+                }
+
+                let Some(arg) = index.parse::<usize>().ok().and_then(|i| args.get(i)) else {
+                    return Err(EvalError::custom(
+                        *span,
+                        format_args!("Format argument `{{{index}}}` is out of bounds"),
+                    ));
+                };
+
+                out.push_rstr(arg.to_rstr());
+            }
+            '}' => {
+                return Err(EvalError::custom(
+                    *span,
+                    "Invalid format string, unmatched `}`",
+                ));
+            }
+            c => {
+                out.push(c);
+            }
         }
-        Expr::Array(array) => {
-            let found = array
-                .iter()
-                .flat_map(|v| v.as_str())
-                .any(|s| s.exposed_eq(needle.as_ref()));
+    }
+
+    Ok(Expr::String(Cow::Owned(out)))
+}
 
-            Ok(Expr::Bool(found))
+fn join<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let (value, separator) = match args {
+        [value] => (value, Cow::Borrowed(RStr::new(","))),
+        [value, separator] => (value, separator.to_rstr()),
+        _ => {
+            return Err(EvalError::custom(*span, "Expected one or two arguments"));
         }
-        lhs => Err(EvalError::custom(
-            *span,
-            format_args!("Expected string or array, got {lhs:?}"),
-        )),
+    };
+
+    let Expr::Array(values) = value else {
+        return Ok(Expr::String(Cow::Owned(value.to_rstr().into_owned())));
+    };
+
+    let mut out = RString::new();
+
+    for (n, value) in values.iter().enumerate() {
+        if n > 0 {
+            out.push_rstr(separator.as_ref());
+        }
+
+        out.push_rstr(value.to_rstr());
     }
+
+    Ok(Expr::String(Cow::Owned(out)))
 }
 
-fn cancelled<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+fn always<'m>(_: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
     let [] = args else {
         return Err(EvalError::custom(*span, "Expected no arguments"));
     };
 
-    Ok(Expr::Bool(false))
+    Ok(Expr::Bool(true))
 }
 
-fn failure<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+fn cancelled<'m>(eval: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
     let [] = args else {
         return Err(EvalError::custom(*span, "Expected no arguments"));
     };
 
-    Ok(Expr::Bool(false))
+    Ok(Expr::Bool(
+        job_status(eval).is_some_and(|s| s == "cancelled"),
+    ))
 }
 
-fn success<'m>(span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+fn failure<'m>(eval: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
     let [] = args else {
         return Err(EvalError::custom(*span, "Expected no arguments"));
     };
 
-    Ok(Expr::Bool(true))
+    Ok(Expr::Bool(job_status(eval).is_some_and(|s| s == "failure")))
 }
 
-fn not_implemented<'m>(span: &Span<u32>, _: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
-    Err(EvalError::custom(
-        *span,
-        "Function has not been implemented yet",
-    ))
+fn success<'m>(eval: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    let [] = args else {
+        return Err(EvalError::custom(*span, "Expected no arguments"));
+    };
+
+    Ok(Expr::Bool(job_status(eval).is_none_or(|s| s == "success")))
+}
+
+/// Get the `job.status` which status functions are evaluated against.
+///
+/// This is only set when evaluating the `if` condition of a job, where it
+/// reflects the results of the jobs it needs.
+fn job_status(eval: &Eval) -> Option<Cow<'_, str>> {
+    let status = eval.tree().get(["job", "status"]).into_iter().next()?;
+    Some(status.to_exposed())
+}
+
+fn hash_files<'m>(eval: &Eval, span: &Span<u32>, args: &[Expr<'m>]) -> Result<Expr<'m>, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::custom(
+            *span,
+            "Expected at least one pattern argument",
+        ));
+    }
+
+    let Some(workspace) = eval.tree().get(["github", "workspace"]).into_iter().next() else {
+        return Err(EvalError::custom(*span, "Missing github.workspace"));
+    };
+
+    let workspace = PathBuf::from(workspace.to_exposed().as_ref());
+
+    let mut files = BTreeSet::new();
+
+    // NB: Patterns are applied in order, so a negated pattern only removes
+    // files matched by patterns preceding it.
+    for arg in args {
+        let Expr::String(pattern) = arg else {
+            return Err(EvalError::custom(
+                *span,
+                format_args!("Expected string pattern, got {arg:?}"),
+            ));
+        };
+
+        let pattern = pattern.to_exposed();
+
+        let (negate, pattern) = match pattern.trim().strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern.trim()),
+        };
+
+        let glob = Glob::new(&workspace, pattern);
+
+        for path in glob.matcher() {
+            let path = path.map_err(|error| EvalError::custom(*span, format_args!("{error}")))?;
+            let path = path.normalize();
+
+            if matches!(path.components().next(), Some(Component::ParentDir)) {
+                continue;
+            }
+
+            if negate {
+                files.remove(&path);
+            } else if path.to_path(&workspace).is_file() {
+                files.insert(path);
+            }
+        }
+    }
+
+    if files.is_empty() {
+        return Ok(Expr::String(Cow::Owned(RString::new())));
+    }
+
+    let mut result = Sha256::new();
+
+    for path in files {
+        let path = path.to_path(&workspace);
+
+        let hash = File::open(&path).and_then(|mut f| {
+            let mut hasher = Sha256::new();
+            io::copy(&mut f, &mut hasher)?;
+            Ok(hasher.finalize())
+        });
+
+        let hash = hash.map_err(|error| {
+            EvalError::custom(*span, format_args!("{}: {error}", path.display()))
+        })?;
+
+        result.update(hash);
+    }
+
+    let mut hex = String::with_capacity(64);
+
+    for b in result.finalize() {
+        _ = write!(hex, "{b:02x}");
+    }
+
+    Ok(Expr::String(Cow::Owned(RString::from(hex))))
+}
+
+/// Write an expression as pretty-printed JSON, retaining any redacted
+/// sections of strings.
+fn write_json(out: &mut RString, value: &Expr<'_>, indent: usize) {
+    fn newline(out: &mut RString, indent: usize) {
+        out.push('\n');
+
+        for _ in 0..indent {
+            out.push_rstr("  ");
+        }
+    }
+
+    match value {
+        Expr::Array(values) => {
+            if values.is_empty() {
+                out.push_rstr("[]");
+                return;
+            }
+
+            out.push('[');
+
+            for (n, value) in values.iter().enumerate() {
+                if n > 0 {
+                    out.push(',');
+                }
+
+                newline(out, indent + 1);
+                write_json(out, value, indent + 1);
+            }
+
+            newline(out, indent);
+            out.push(']');
+        }
+        Expr::Object(values) => {
+            if values.is_empty() {
+                out.push_rstr("{}");
+                return;
+            }
+
+            out.push('{');
+
+            for (n, (key, value)) in values.iter().enumerate() {
+                if n > 0 {
+                    out.push(',');
+                }
+
+                newline(out, indent + 1);
+                write_json_string(out, RStr::new(key));
+                out.push_rstr(": ");
+                write_json(out, value, indent + 1);
+            }
+
+            newline(out, indent);
+            out.push('}');
+        }
+        Expr::String(string) => {
+            write_json_string(out, string);
+        }
+        Expr::Float(float) if float.is_finite() => {
+            out.push_rstr(float.to_string());
+        }
+        Expr::Float(..) | Expr::Null => {
+            out.push_rstr("null");
+        }
+        Expr::Bool(b) => {
+            out.push_rstr(if *b { "true" } else { "false" });
+        }
+    }
+}
+
+fn write_json_string(out: &mut RString, string: &RStr) {
+    fn escape(string: &str) -> String {
+        let escaped = serde_json::to_string(string).unwrap_or_default();
+        escaped[1..escaped.len() - 1].to_owned()
+    }
+
+    out.push('"');
+
+    for chunk in string.chunks() {
+        out.push_rstr(escape(chunk.public()));
+
+        let redacted = chunk.redacted().collect::<String>();
+
+        if !redacted.is_empty() {
+            out.push_redacted(&escape(&redacted));
+        }
+    }
+
+    out.push('"');
 }
 
 fn value_to_expr(
@@ -147,8 +448,14 @@ fn value_to_expr(
 
             Expr::Array(values.into())
         }
-        serde_json::Value::Object(_) => {
-            return Err(EvalError::custom(*span, "Objects are not supported"));
+        serde_json::Value::Object(object) => {
+            let mut values = BTreeMap::new();
+
+            for (key, value) in object {
+                values.insert(key, value_to_expr(span, value, is_secret)?);
+            }
+
+            Expr::Object(values)
         }
     };
 
@@ -1,6 +1,7 @@
 use std::ops::BitAndAssign;
 
 use anyhow::Result;
+use syntree::{Checkpoint, Flavor, FlavorDefault};
 
 use super::parsing::Parser;
 use super::{ExprError, Syntax};
@@ -57,6 +58,7 @@ fn expr(p: &mut Parser<'_>, min: u8) -> Result<Outcome, ExprError> {
         }
         OpenParen => {
             ok &= group(p, CloseParen)?;
+            ok &= access(p, &c)?;
         }
         OpenExpr => {
             ok &= group(p, CloseExpr)?;
@@ -67,6 +69,7 @@ fn expr(p: &mut Parser<'_>, min: u8) -> Result<Outcome, ExprError> {
             if p.eat(OpenParen)? {
                 ok &= function(p)?;
                 p.tree.close_at(&c, Function)?;
+                ok &= access(p, &c)?;
             } else if lookup(p)? {
                 p.tree.close_at(&c, Lookup)?;
             } else {
@@ -107,17 +110,50 @@ fn expr(p: &mut Parser<'_>, min: u8) -> Result<Outcome, ExprError> {
 fn lookup(p: &mut Parser) -> Result<bool, ExprError> {
     let mut ok = true;
 
-    while p.eat(Dot)? {
-        let what = p.peek()?.syntax;
-        ok &= matches!(what, Ident | Star);
-        // Bump whatever is there anyway in the hope that we can "keep going",
-        // but treat the expression as an error.
-        p.bump(what)?;
+    loop {
+        match p.peek()?.syntax {
+            Dot => {
+                p.token()?;
+                let what = p.peek()?.syntax;
+                ok &= matches!(what, Ident | Star);
+                // Bump whatever is there anyway in the hope that we can "keep
+                // going", but treat the expression as an error.
+                p.bump(what)?;
+            }
+            OpenBracket => {
+                let c = p.tree.checkpoint()?;
+                p.token()?;
+                ok &= matches!(expr(p, 0)?, Outcome::Ok);
+                ok &= p.eat(CloseBracket)?;
+                p.tree.close_at(&c, Index)?;
+            }
+            _ => break,
+        }
     }
 
     Ok(ok)
 }
 
+/// Parse any trailing member accesses on an expression at the given
+/// checkpoint.
+fn access(
+    p: &mut Parser,
+    c: &Checkpoint<<FlavorDefault as Flavor>::Pointer>,
+) -> Result<Outcome, ExprError> {
+    if !matches!(p.peek()?.syntax, Dot | OpenBracket) {
+        return Ok(Outcome::Ok);
+    }
+
+    let ok = lookup(p)?;
+    p.tree.close_at(c, Access)?;
+
+    if ok {
+        Ok(Outcome::Ok)
+    } else {
+        Ok(Outcome::Error)
+    }
+}
+
 fn function(p: &mut Parser) -> Result<Outcome, ExprError> {
     let mut ok = Outcome::Ok;
     let mut end = false;
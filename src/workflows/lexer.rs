@@ -182,6 +182,14 @@ impl<'a> Lexer<'a> {
                 self.step(1);
                 CloseParen
             }
+            ('[', _, _) => {
+                self.step(1);
+                OpenBracket
+            }
+            (']', _, _) => {
+                self.step(1);
+                CloseBracket
+            }
             ('-' | '0'..='9', _, _) => {
                 self.number();
                 Number
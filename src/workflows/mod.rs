@@ -9,6 +9,9 @@ mod grammar;
 mod lexer;
 mod parsing;
 
+mod triggers;
+pub(crate) use self::triggers::Triggers;
+
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::fmt;
@@ -16,7 +19,8 @@ use std::fs;
 use std::io;
 use std::mem::take;
 use std::rc::Rc;
-use std::str;
+use std::str::{self, FromStr};
+use std::time::Duration;
 
 use anyhow::{Context, Result, anyhow, bail};
 use bstr::ByteSlice;
@@ -30,10 +34,15 @@ use crate::rstr::{RStr, RString};
 use crate::shell::Shell;
 
 use self::eval::{EvalError, Expr};
+use self::triggers::WorkflowInput;
 
 static EMPTY_TREE: Tree = Tree::new();
 
-type CustomFunction = for<'m> fn(&Span<u32>, &[Expr<'m>]) -> Result<Expr<'m>, eval::EvalError>;
+/// The maximum depth at which reusable workflows can be nested.
+const MAX_WORKFLOW_DEPTH: usize = 4;
+
+type CustomFunction =
+    for<'m> fn(&Eval, &Span<u32>, &[Expr<'m>]) -> Result<Expr<'m>, eval::EvalError>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
@@ -62,12 +71,20 @@ pub(crate) enum Syntax {
     OpenParen,
     // `)`.
     CloseParen,
+    // `[`.
+    OpenBracket,
+    // `]`.
+    CloseBracket,
     // `${{`.
     OpenExpr,
     // `}}`.
     CloseExpr,
-    // A lookup expression `<ident> [<dot> <ident>]*`.
+    // A lookup expression `<ident> [<dot> <ident> | <dot> <star> | <index>]*`.
     Lookup,
+    // An index expression `[<expr>]`.
+    Index,
+    // Member access on the value of an expression, like `fromJSON(..).*.foo`.
+    Access,
     // A function call.
     Function,
     // A unary operation.
@@ -125,6 +142,7 @@ impl std::error::Error for ExprError {
 
 pub struct WorkflowManifests<'a, 'cx> {
     cx: &'a Ctxt<'cx>,
+    repo: RelativePathBuf,
     path: RelativePathBuf,
     ids: BTreeSet<String>,
 }
@@ -134,7 +152,13 @@ impl<'a, 'cx> WorkflowManifests<'a, 'cx> {
     pub(crate) fn new(cx: &'a Ctxt<'cx>, repo: &Repo) -> Result<Self> {
         let path = repo.path().join(".github").join("workflows");
         let ids = list_workflow_ids(cx, &path)?;
-        Ok(Self { cx, path, ids })
+
+        Ok(Self {
+            cx,
+            repo: repo.path().to_owned(),
+            path,
+            ids,
+        })
     }
 
     /// Get ids of existing workflows.
@@ -154,74 +178,552 @@ impl<'a, 'cx> WorkflowManifests<'a, 'cx> {
 
     /// Open a workflow by id.
     fn open(&self, id: &str) -> Result<Option<WorkflowManifest<'a, 'cx>>> {
-        let path = self.path(id);
-        let p = self.cx.to_path(&path);
+        open_manifest(self.cx, &self.repo, id, self.path(id))
+    }
+}
 
-        let bytes = match fs::read(&p) {
-            Ok(bytes) => bytes,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
-            Err(e) => return Err(e).context(p.display().to_string()),
-        };
+/// Open the workflow manifest at the given path.
+fn open_manifest<'a, 'cx>(
+    cx: &'a Ctxt<'cx>,
+    repo: &RelativePath,
+    id: &str,
+    path: RelativePathBuf,
+) -> Result<Option<WorkflowManifest<'a, 'cx>>> {
+    let p = cx.to_path(&path);
 
-        let doc = yaml::from_slice(bytes)
-            .with_context(|| anyhow!("{}: Reading YAML file", p.display()))?;
+    let bytes = match fs::read(&p) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(p.display().to_string()),
+    };
 
-        Ok(Some(WorkflowManifest {
-            cx: self.cx,
-            id: id.to_owned(),
-            path,
-            doc,
-        }))
-    }
+    let doc =
+        yaml::from_slice(bytes).with_context(|| anyhow!("{}: Reading YAML file", p.display()))?;
+
+    Ok(Some(WorkflowManifest {
+        cx,
+        repo: repo.to_owned(),
+        id: id.to_owned(),
+        path,
+        doc,
+    }))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_job(
     id: &str,
+    doc: &Rc<yaml::Document>,
     value: yaml::Mapping<'_>,
     ignore: &HashSet<String>,
     filter: &[(String, String)],
     eval: &Eval,
+    defaults: &RunDefaults,
+    calls: &[Rc<WorkflowCall>],
 ) -> Result<Job> {
+    let name = value.get("name").and_then(|v| v.as_str());
+    let needs = extract_needs(&value)?;
+    let outputs = extract_raw_outputs(&value)?;
+    let condition = value.get("if").and_then(|v| v.as_str());
+    let defaults = RunDefaults::extract(&value, defaults);
+
+    let strategy = value.get("strategy").and_then(|v| v.as_mapping());
+
+    let fail_fast = strategy
+        .as_ref()
+        .and_then(|s| s.get("fail-fast"))
+        .and_then(extract_scalar)
+        .map(|source| eval_bool(eval, &source))
+        .transpose()
+        .context(".strategy.fail-fast")?
+        .unwrap_or(true);
+
+    let max_parallel = strategy
+        .as_ref()
+        .and_then(|s| s.get("max-parallel"))
+        .and_then(extract_scalar)
+        .map(|source| eval_max_parallel(eval, &source))
+        .transpose()
+        .context(".strategy.max-parallel")?;
+
+    let matrix = value
+        .get("strategy")
+        .and_then(|s| s.as_mapping())
+        .and_then(|s| s.get("matrix"));
+
+    // NB: A matrix or inputs which depend on the outputs of the jobs this job
+    // needs can only be evaluated once they have completed.
+    let (matrices, deferred) = if uses_needs(matrix) || calls.iter().any(|c| c.uses_needs) {
+        let deferred = DeferredJob {
+            doc: doc.clone(),
+            id: value.id(),
+            tree: Rc::new(eval.tree().clone()),
+            defaults,
+            ignore: ignore.clone(),
+            filter: filter.to_vec(),
+            calls: calls.to_vec(),
+        };
+
+        (Vec::new(), Some(deferred))
+    } else {
+        (
+            build_job_matrices(&value, ignore, filter, eval, &defaults)?,
+            None,
+        )
+    };
+
+    Ok(Job {
+        id: id.to_owned(),
+        name: name.map(str::to_owned),
+        needs,
+        outputs,
+        matrices,
+        uses: None,
+        called_by: None,
+        condition: condition.map(|source| JobCondition::new(source, eval)),
+        caller_conditions: Vec::new(),
+        fail_fast,
+        max_parallel,
+        deferred,
+        error: None,
+    })
+}
+
+/// Build every combination of the matrix of a job along with its steps.
+fn build_job_matrices(
+    value: &yaml::Mapping<'_>,
+    ignore: &HashSet<String>,
+    filter: &[(String, String)],
+    eval: &Eval,
+    defaults: &RunDefaults,
+) -> Result<Vec<(Matrix, Steps)>> {
     let runs_on = value
         .get("runs-on")
         .and_then(|value| value.as_str())
         .context("Missing runs-on")?;
 
     let name = value.get("name").and_then(|v| v.as_str());
+    let continue_on_error = value.get("continue-on-error").and_then(extract_scalar);
+    let timeout_minutes = value.get("timeout-minutes").and_then(extract_scalar);
 
     let mut matrices = Vec::new();
 
-    for matrix in build_matrices(&value, ignore, filter, eval)? {
+    for matrix in build_matrices(value, ignore, filter, eval)? {
         let tree = eval.tree().with_prefix(["matrix"], matrix.matrix.clone());
         let eval = Eval::new(&tree);
 
-        let (steps, step_mappings, tree) = load_steps(&value, eval)?;
+        let (steps, step_mappings, tree) = load_steps(value, eval, defaults)?;
         let eval = Eval::new(&tree);
 
         let steps = Steps {
             runs_on: eval.eval(runs_on)?.into_owned(),
+            container: extract_container(eval, value).context(".container")?,
+            services: extract_services(eval, value).context(".services")?,
             name: name
                 .map(|name| eval.eval(name))
                 .transpose()?
                 .map(Cow::into_owned),
             steps,
             step_mappings,
+            continue_on_error: continue_on_error
+                .as_deref()
+                .map(|source| eval_bool(eval, source))
+                .transpose()
+                .context(".continue-on-error")?
+                .unwrap_or_default(),
+            timeout: timeout_minutes
+                .as_deref()
+                .map(|source| eval_minutes(eval, source))
+                .transpose()
+                .context(".timeout-minutes")?,
+            tree,
         };
 
         matrices.push((matrix, steps));
     }
 
-    Ok(Job {
-        id: id.to_owned(),
-        name: name.map(str::to_owned),
-        matrices,
+    Ok(matrices)
+}
+
+/// Test if any expression in the given value refers to the `needs` context.
+fn uses_needs(value: Option<yaml::Value<'_>>) -> bool {
+    let Some(value) = value else {
+        return false;
+    };
+
+    if let Some(source) = value.as_str() {
+        return expressions(source).any(|expr| expr_uses_context(expr, "needs"));
+    }
+
+    if let Some(sequence) = value.as_sequence() {
+        return sequence.iter().any(|value| uses_needs(Some(value)));
+    }
+
+    if let Some(mapping) = value.as_mapping() {
+        return mapping.iter().any(|(_, value)| uses_needs(Some(value)));
+    }
+
+    false
+}
+
+/// Test if the given expression refers to the specified context.
+///
+/// Expressions which can't be parsed don't refer to anything, they instead
+/// produce an error once they're evaluated.
+fn expr_uses_context(source: &str, context: &str) -> bool {
+    let mut p = parsing::Parser::new(source);
+
+    if grammar::root(&mut p).is_err() {
+        return false;
+    }
+
+    let Ok(tree) = p.tree.build() else {
+        return false;
+    };
+
+    self::eval::uses_context(&tree, source, context)
+}
+
+/// Iterate over the `${{ <expr> }}` expressions in the given string.
+fn expressions(mut source: &str) -> impl Iterator<Item = &str> {
+    std::iter::from_fn(move || {
+        let (_, rest) = source.split_once("${{")?;
+        let (expr, rest) = split_expr(rest)?;
+        source = rest;
+        Some(expr)
     })
 }
 
+/// Split an expression from the string which follows its opening `${{`,
+/// returning the expression and what follows its closing `}}`.
+fn split_expr(rest: &str) -> Option<(&str, &str)> {
+    let mut it = rest.chars();
+    let mut e = 0;
+    let mut level = 2usize;
+
+    loop {
+        if level == 2
+            && let Some(o) = it.as_str().strip_prefix("}}")
+        {
+            return Some((&rest[..e], o));
+        }
+
+        let c = it.next()?;
+
+        level = level.wrapping_add_signed(match c {
+            '{' => 1,
+            '}' => -1,
+            _ => 0,
+        });
+
+        e += c.len_utf8();
+    }
+}
+
+/// Evaluate `strategy.max-parallel` into a positive number of jobs.
+fn eval_max_parallel(eval: &Eval, source: &str) -> Result<usize> {
+    let value = eval.eval(source)?;
+    let value = value.to_exposed();
+
+    match usize::from_str(value.trim()) {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => bail!("Expected a positive number, but got `{value}`"),
+    }
+}
+
+/// Extract the `needs` of a job, which is either a single job id or a sequence
+/// of job ids.
+fn extract_needs(value: &yaml::Mapping<'_>) -> Result<Vec<String>> {
+    let mut needs = Vec::new();
+
+    let Some(value) = value.get("needs") else {
+        return Ok(needs);
+    };
+
+    if let Some(sequence) = value.as_sequence() {
+        for (index, value) in sequence.iter().enumerate() {
+            let need = value
+                .as_str()
+                .with_context(|| anyhow!(".needs[{index}]: Expected a job id"))?;
+
+            needs.push(need.to_owned());
+        }
+    } else {
+        let need = value.as_str().context(".needs: Expected a job id")?;
+        needs.push(need.to_owned());
+    }
+
+    Ok(needs)
+}
+
+/// Extract the `container` of a job.
+fn extract_container(eval: &Eval, value: &yaml::Mapping<'_>) -> Result<Option<Container>> {
+    let Some(value) = value.get("container") else {
+        return Ok(None);
+    };
+
+    extract_container_value(eval, value)
+}
+
+/// Extract the `services` of a job, which is a mapping from service id to a
+/// container definition.
+fn extract_services(eval: &Eval, value: &yaml::Mapping<'_>) -> Result<Vec<(String, Container)>> {
+    let mut services = Vec::new();
+
+    let Some(value) = value.get("services") else {
+        return Ok(services);
+    };
+
+    let m = value.as_mapping().context("Expected a mapping")?;
+
+    for (id, value) in m {
+        let id = id.to_str()?;
+
+        let service = extract_container_value(eval, value).with_context(|| anyhow!(".{id}"))?;
+
+        if let Some(service) = service {
+            services.push((id.to_owned(), service));
+        }
+    }
+
+    Ok(services)
+}
+
+/// Extract a container, which is either an image or a mapping with an `image`
+/// key.
+fn extract_container_value(eval: &Eval, value: yaml::Value<'_>) -> Result<Option<Container>> {
+    let container = if let Some(image) = value.as_str() {
+        Container {
+            image: eval.eval(image)?.into_owned(),
+            env: BTreeMap::new(),
+            ports: Vec::new(),
+            options: Vec::new(),
+        }
+    } else if let Some(m) = value.as_mapping() {
+        let image = m
+            .get("image")
+            .and_then(|v| v.as_str())
+            .context("Missing .image")?;
+
+        let options = match m.get("options").and_then(|v| v.as_str()) {
+            Some(options) => {
+                split_options(&eval.eval(options)?.to_exposed()).context(".options")?
+            }
+            None => Vec::new(),
+        };
+
+        let mut ports = Vec::new();
+
+        if let Some(sequence) = m.get("ports").and_then(|v| v.as_sequence()) {
+            for (index, value) in sequence.iter().enumerate() {
+                let port = extract_scalar(value)
+                    .with_context(|| anyhow!(".ports[{index}]: Expected a port"))?;
+                ports.push(eval.eval(&port)?.to_exposed().into_owned());
+            }
+        }
+
+        Container {
+            image: eval.eval(image)?.into_owned(),
+            env: extract_env(eval, &m)?,
+            ports,
+            options,
+        }
+    } else {
+        bail!("Expected an image or a mapping");
+    };
+
+    // NB: An empty image means that the job should run directly on the
+    // runner, which is used to conditionally enable containers in a matrix.
+    if container.image.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(container))
+}
+
+/// Split container options into arguments, honoring single and double quotes
+/// as well as backslash escapes like a shell would.
+pub(crate) fn split_options(options: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut has_arg = false;
+    let mut quote = None;
+    let mut it = options.chars();
+
+    while let Some(c) = it.next() {
+        match (quote, c) {
+            (Some(q), c) if q == c => {
+                quote = None;
+            }
+            (None | Some('"'), '\\') => {
+                let Some(c) = it.next() else {
+                    bail!("Trailing escape in options");
+                };
+
+                arg.push(c);
+                has_arg = true;
+            }
+            (Some(_), c) => {
+                arg.push(c);
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                has_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if has_arg {
+                    args.push(std::mem::take(&mut arg));
+                    has_arg = false;
+                }
+            }
+            (None, c) => {
+                arg.push(c);
+                has_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        bail!("Unterminated quote in options");
+    }
+
+    if has_arg {
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// Extract a scalar value as a string so that it can be evaluated.
+fn extract_scalar(value: yaml::Value<'_>) -> Option<String> {
+    match value.into_any() {
+        yaml::Any::Bool(b) => Some(b.to_string()),
+        yaml::Any::Number(n) => Some(n.as_raw().to_string()),
+        yaml::Any::String(s) => Some(s.to_str().ok()?.to_owned()),
+        _ => None,
+    }
+}
+
+/// Evaluate a boolean option such as `continue-on-error`.
+pub(crate) fn eval_bool(eval: &Eval, source: &str) -> Result<bool> {
+    let value = eval.eval(source)?;
+
+    match value.to_exposed().trim() {
+        "true" => Ok(true),
+        "false" | "" => Ok(false),
+        other => bail!("Expected boolean, but got `{other}`"),
+    }
+}
+
+/// Test if an expression calls one of the status functions `success()`,
+/// `failure()`, `always()` or `cancelled()`.
+///
+/// Conditions which don't are implicitly combined with `success()`.
+pub(crate) fn has_status_function(source: &str) -> bool {
+    const FUNCTIONS: [&str; 4] = ["success", "failure", "always", "cancelled"];
+
+    let bytes = source.as_bytes();
+    let mut n = 0;
+
+    while let Some(&b) = bytes.get(n) {
+        match b {
+            b'\'' => {
+                n += 1;
+
+                while bytes.get(n).is_some_and(|&b| b != b'\'') {
+                    n += 1;
+                }
+
+                n += 1;
+            }
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                let start = n;
+
+                while bytes
+                    .get(n)
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-'))
+                {
+                    n += 1;
+                }
+
+                let is_property = start > 0 && bytes[start - 1] == b'.';
+                let ident = &source[start..n];
+
+                let mut m = n;
+
+                while bytes.get(m).is_some_and(u8::is_ascii_whitespace) {
+                    m += 1;
+                }
+
+                if !is_property
+                    && bytes.get(m) == Some(&b'(')
+                    && FUNCTIONS.iter().any(|f| f.eq_ignore_ascii_case(ident))
+                {
+                    return true;
+                }
+            }
+            _ => {
+                n += 1;
+            }
+        }
+    }
+
+    false
+}
+
+/// Evaluate a `timeout-minutes` option into a duration.
+pub(crate) fn eval_minutes(eval: &Eval, source: &str) -> Result<Duration> {
+    let value = eval.eval(source)?;
+    let value = value.to_exposed();
+
+    let minutes = match f64::from_str(value.trim()) {
+        Ok(minutes) if minutes.is_finite() && minutes > 0.0 => minutes,
+        _ => bail!("Expected a positive number of minutes, but got `{value}`"),
+    };
+
+    Ok(Duration::from_secs_f64(minutes * 60.0))
+}
+
+/// Defaults for `run` steps, as specified through `defaults.run` in a workflow
+/// or a job.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct RunDefaults {
+    pub(crate) shell: Option<String>,
+    pub(crate) working_directory: Option<String>,
+}
+
+impl RunDefaults {
+    /// Extract `defaults.run` from the given YAML value, where values which
+    /// are not set are inherited from `parent`.
+    pub(crate) fn extract(mapping: &yaml::Mapping<'_>, parent: &RunDefaults) -> Self {
+        let run = mapping
+            .get("defaults")
+            .and_then(|v| v.as_mapping())
+            .and_then(|v| v.get("run"))
+            .and_then(|v| v.as_mapping());
+
+        let get = |key: &str| {
+            run.as_ref()?
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::to_owned)
+        };
+
+        Self {
+            shell: get("shell").or_else(|| parent.shell.clone()),
+            working_directory: get("working-directory")
+                .or_else(|| parent.working_directory.clone()),
+        }
+    }
+}
+
 /// Load steps from the given YAML value.
+///
+/// The `shell` and `working-directory` of `run` steps which do not specify
+/// them are taken from `defaults`.
 pub(crate) fn load_steps(
     mapping: &yaml::Mapping<'_>,
     eval: &Eval,
+    defaults: &RunDefaults,
 ) -> Result<(Vec<Rc<Step>>, Vec<StepMapping>, Rc<Tree>)> {
     let mut steps = Vec::new();
     let mut step_mappings = Vec::new();
@@ -243,7 +745,22 @@ pub(crate) fn load_steps(
         };
 
         let env = extract_raw_env(&value)?;
-        let working_directory = value.get("working-directory").and_then(|v| v.as_str());
+        let run = value.get("run").and_then(|v| v.as_str());
+
+        // NB: Defaults only apply to `run` steps.
+        let defaults = run.map(|_| defaults);
+
+        let working_directory = value
+            .get("working-directory")
+            .and_then(|v| v.as_str())
+            .map(str::to_owned)
+            .or_else(|| defaults?.working_directory.clone());
+
+        let shell = value
+            .get("shell")
+            .and_then(|v| v.as_str())
+            .map(str::to_owned)
+            .or_else(|| defaults?.shell.clone());
 
         let mut condition = None;
         let mut condition_mapping = None;
@@ -265,31 +782,42 @@ pub(crate) fn load_steps(
 
         if let Some(mapping) = value.get("with").and_then(|v| v.as_mapping()) {
             for (key, value) in mapping {
-                let (Ok(key), Some(value)) = (str::from_utf8(key), value.as_str()) else {
+                let Ok(key) = str::from_utf8(key) else {
                     continue;
                 };
 
-                with.insert(key.to_owned(), value.to_owned());
+                // NB: Inputs are always strings, so scalars like `true` or `1`
+                // are stored as written.
+                let value = match value.as_any() {
+                    yaml::Any::Bool(b) => String::from(if b { "true" } else { "false" }),
+                    yaml::Any::Number(n) => n.as_raw().to_str()?.to_owned(),
+                    yaml::Any::String(s) => s.to_str()?.to_owned(),
+                    _ => continue,
+                };
+
+                with.insert(key.to_owned(), value);
             }
         }
 
         let id = value.get("id").and_then(|v| v.as_str());
         let id = id.map(|id| eval.eval(id)).transpose()?;
         let name = value.get("name").and_then(|v| v.as_str());
-        let run = value.get("run").and_then(|v| v.as_str());
-        let shell = value.get("shell").and_then(|v| v.as_str());
+        let continue_on_error = value.get("continue-on-error").and_then(extract_scalar);
+        let timeout_minutes = value.get("timeout-minutes").and_then(extract_scalar);
 
         steps.push(Rc::new(Step {
             id: id.map(Cow::into_owned).map(RString::into_rc),
             uses,
             tree: tree.clone(),
             env,
-            working_directory: working_directory.map(str::to_owned),
+            working_directory,
             condition: condition.map(str::to_owned),
             with,
             name: name.map(str::to_owned),
             run: run.map(str::to_owned),
-            shell: shell.map(str::to_owned),
+            shell,
+            continue_on_error,
+            timeout_minutes,
         }));
 
         step_mappings.push(StepMapping {
@@ -309,151 +837,358 @@ pub(crate) fn build_matrices(
     filter: &[(String, String)],
     eval: &Eval,
 ) -> Result<Vec<Matrix>> {
-    let mut matrices = Vec::new();
-    let mut included = Vec::new();
-    let mut variables = Vec::new();
+    let mut spec = MatrixSpec::default();
 
-    'bail: {
-        let Some(strategy) = value.get("strategy").and_then(|s| s.as_mapping()) else {
-            break 'bail;
-        };
+    let matrix = value
+        .get("strategy")
+        .and_then(|s| s.as_mapping())
+        .and_then(|s| s.get("matrix"));
 
-        let Some(matrix) = strategy
-            .get("matrix")
-            .and_then(|matrix| matrix.as_mapping())
-        else {
-            break 'bail;
-        };
+    if let Some(matrix) = matrix {
+        if let Some(mapping) = matrix.as_mapping() {
+            spec.load_mapping(&mapping, ignore, eval)?;
+        } else if let Some(source) = matrix.as_str() {
+            let expr = eval.expr(source).context(".strategy.matrix")?;
+
+            let Expr::Object(object) = expr else {
+                bail!(".strategy.matrix: Expected expression to evaluate to an object");
+            };
+
+            spec.load_object(object, ignore)?;
+        }
+    }
 
-        let filter = |key: &str, value: &RStr| {
-            let mut has_key = false;
+    let is_empty = spec.is_empty();
+    let mut matrices = spec.expand()?;
 
-            for (k, v) in filter {
-                if k == key {
-                    has_key = true;
+    matrices.retain(|matrix| {
+        let mut keys = HashSet::new();
+        let mut matched = HashSet::new();
 
-                    if value.str_eq(v) {
-                        return true;
-                    }
-                }
+        for (k, v) in filter {
+            let Some(value) = matrix.matrix.get(k) else {
+                continue;
+            };
+
+            keys.insert(k.as_str());
+
+            if value.str_eq(v) {
+                matched.insert(k.as_str());
             }
+        }
 
-            !has_key
-        };
+        keys == matched
+    });
+
+    if is_empty {
+        matrices.push(Matrix::new());
+    }
+
+    Ok(matrices)
+}
+
+/// The specification of a matrix before it has been expanded.
+#[derive(Default)]
+struct MatrixSpec {
+    /// Variables whose values are expanded into a cartesian product.
+    variables: Vec<(String, Vec<(RString, Option<yaml::Id>)>)>,
+    /// Partial matrices to exclude from the product.
+    exclude: Vec<Matrix>,
+    /// Matrices to include in the product.
+    include: Vec<Matrix>,
+}
 
+impl MatrixSpec {
+    /// Test if the specification doesn't define any matrices.
+    fn is_empty(&self) -> bool {
+        self.variables.is_empty() && self.include.is_empty()
+    }
+
+    /// Load a matrix specification from a YAML mapping.
+    fn load_mapping(
+        &mut self,
+        matrix: &yaml::Mapping<'_>,
+        ignore: &HashSet<String>,
+        eval: &Eval,
+    ) -> Result<()> {
         for (key, value) in matrix {
             let key = str::from_utf8(key).context("Bad matrix key")?;
 
-            if key == "include" {
-                for (index, mapping) in value
-                    .as_sequence()
-                    .into_iter()
-                    .flatten()
-                    .flat_map(|v| v.as_mapping())
-                    .enumerate()
-                {
-                    let mut matrix = Matrix::new();
+            match key {
+                "include" => {
+                    self.include.extend(load_matrix_entries(key, value, eval)?);
+                }
+                "exclude" => {
+                    self.exclude.extend(load_matrix_entries(key, value, eval)?);
+                }
+                key => {
+                    if ignore.contains(key) {
+                        continue;
+                    }
+
+                    let values = load_matrix_values(key, value, eval)?;
 
-                    for (key, value) in mapping {
-                        let id = value.id();
-                        let key = str::from_utf8(key).context("Bad matrix key")?;
+                    if values.is_empty() {
+                        bail!("Matrix vector `{key}` does not contain any values");
+                    }
 
-                        if let Some(sequence) = value.as_sequence() {
-                            for value in sequence {
-                                let id = value.id();
+                    self.variables.push((key.to_owned(), values));
+                }
+            }
+        }
 
-                                let value = value_as_string(eval, value)?
-                                    .with_context(|| anyhow!(".include[{index}][{key}]: Matrix value in array must be scalar"))?;
+        Ok(())
+    }
 
-                                if filter(key, value.as_ref()) {
-                                    matrix.insert_with_id(key, value, id);
-                                }
-                            }
-                        }
+    /// Load a matrix specification from an evaluated object.
+    fn load_object(
+        &mut self,
+        object: BTreeMap<String, Expr<'_>>,
+        ignore: &HashSet<String>,
+    ) -> Result<()> {
+        for (key, value) in object {
+            match key.as_str() {
+                "include" => {
+                    self.include.extend(expr_to_matrices(&key, value)?);
+                }
+                "exclude" => {
+                    self.exclude.extend(expr_to_matrices(&key, value)?);
+                }
+                _ => {
+                    if ignore.contains(&key) {
+                        continue;
+                    }
 
-                        let value = value_as_string(eval, value)?.with_context(|| {
-                            anyhow!(".include[{index}][{key}]: Value must be a scalar value")
-                        })?;
+                    let values = expr_to_values(&key, value)?
+                        .into_iter()
+                        .map(|value| (value, None))
+                        .collect::<Vec<_>>();
 
-                        if filter(key, value.as_ref()) {
-                            matrix.insert_with_id(key, value, id);
-                        }
+                    if values.is_empty() {
+                        bail!("Matrix vector `{key}` does not contain any values");
                     }
 
-                    included.push(matrix);
+                    self.variables.push((key, values));
                 }
+            }
+        }
 
-                continue;
+        Ok(())
+    }
+
+    /// Expand the specification into matrices.
+    ///
+    /// This follows the semantics used by GitHub, where excluded combinations
+    /// are removed from the cartesian product of all variables before any
+    /// combinations are included. An included combination extends every
+    /// original combination whose variables it doesn't overwrite, and if it
+    /// can't extend any of them it is added as a combination of its own.
+    fn expand(self) -> Result<Vec<Matrix>> {
+        let mut matrices = Vec::new();
+
+        if !self.variables.is_empty() {
+            let mut positions = vec![0usize; self.variables.len()];
+
+            'outer: loop {
+                let mut matrix = Matrix::new();
+
+                for (n, &p) in positions.iter().enumerate() {
+                    let (ref key, ref values) = self.variables[n];
+                    let (ref value, id) = values[p];
+                    matrix.insert_value(key, value, id);
+                }
+
+                matrices.push(matrix);
+
+                for (p, (_, values)) in positions.iter_mut().zip(&self.variables) {
+                    *p += 1;
+
+                    if *p < values.len() {
+                        continue 'outer;
+                    }
+
+                    *p = 0;
+                }
+
+                break;
             }
+        }
 
-            if ignore.contains(key) {
-                continue;
+        matrices.retain(|matrix| !self.exclude.iter().any(|e| matrix.is_superset(e)));
+
+        if matrices.is_empty() && !self.variables.is_empty() && self.include.is_empty() {
+            bail!("Matrix exclusions remove every combination");
+        }
+
+        let mut added = Vec::new();
+
+        for include in self.include {
+            let mut extended = false;
+
+            for matrix in &mut matrices {
+                let overwrites = include.matrix.iter().any(|(key, value)| {
+                    self.variables.iter().any(|(k, _)| k == key)
+                        && matrix
+                            .matrix
+                            .get(key)
+                            .is_some_and(|existing| !existing.exposed_eq(value))
+                });
+
+                if !overwrites {
+                    matrix.extend(&include);
+                    extended = true;
+                }
+            }
+
+            if !extended {
+                added.push(include);
             }
+        }
+
+        matrices.extend(added);
+        Ok(matrices)
+    }
+}
+
+/// Load the values of a single matrix variable.
+fn load_matrix_values(
+    key: &str,
+    value: yaml::Value<'_>,
+    eval: &Eval,
+) -> Result<Vec<(RString, Option<yaml::Id>)>> {
+    let mut values = Vec::new();
+
+    if let Some(sequence) = value.as_sequence() {
+        for (index, value) in sequence.iter().enumerate() {
+            let id = value.id();
+
+            let value = value_as_string(eval, value)?
+                .with_context(|| anyhow!(".{key}[{index}]: Matrix array value must be scalar"))?;
+
+            values.push((value, Some(id)));
+        }
+
+        return Ok(values);
+    }
+
+    if let Some(source) = value.as_str().and_then(as_single_expr) {
+        let expr = eval.expr(source).with_context(|| anyhow!(".{key}"))?;
+        values.extend(expr_to_values(key, expr)?.into_iter().map(|v| (v, None)));
+        return Ok(values);
+    }
+
+    let id = value.id();
+
+    if let Some(string) = value_as_string(eval, value)? {
+        values.push((string, Some(id)));
+    }
+
+    Ok(values)
+}
+
+/// Load `include` or `exclude` entries of a matrix.
+fn load_matrix_entries(key: &str, value: yaml::Value<'_>, eval: &Eval) -> Result<Vec<Matrix>> {
+    if let Some(source) = value.as_str().and_then(as_single_expr) {
+        let expr = eval.expr(source).with_context(|| anyhow!(".{key}"))?;
+        return expr_to_matrices(key, expr);
+    }
+
+    let mut matrices = Vec::new();
+
+    for (index, mapping) in value
+        .as_sequence()
+        .into_iter()
+        .flatten()
+        .flat_map(|v| v.as_mapping())
+        .enumerate()
+    {
+        let mut matrix = Matrix::new();
+
+        for (name, value) in mapping {
+            let id = value.id();
+            let name = str::from_utf8(name).context("Bad matrix key")?;
+
+            let value = value_as_string(eval, value)?.with_context(|| {
+                anyhow!(".{key}[{index}][{name}]: Value must be a scalar value")
+            })?;
+
+            matrix.insert_with_id(name, value, id);
+        }
+
+        matrices.push(matrix);
+    }
+
+    Ok(matrices)
+}
 
-            let mut values = Vec::new();
-            let id = value.id();
+/// Convert an evaluated array of objects into matrices.
+fn expr_to_matrices(key: &str, expr: Expr<'_>) -> Result<Vec<Matrix>> {
+    let Expr::Array(values) = expr else {
+        bail!(".{key}: Expected an array of objects");
+    };
 
-            if let Some(sequence) = value.as_sequence() {
-                for (index, value) in sequence.iter().enumerate() {
-                    let id = value.id();
+    let mut matrices = Vec::new();
 
-                    let value = value_as_string(eval, value)?.with_context(|| {
-                        anyhow!(".{key}[{index}]: Matrix array value must be scalar")
-                    })?;
+    for (index, value) in values.into_iter().enumerate() {
+        let Expr::Object(object) = value else {
+            bail!(".{key}[{index}]: Expected an object");
+        };
 
-                    if filter(key, value.as_ref()) {
-                        values.push((value, id));
-                    }
-                }
-            }
+        let mut matrix = Matrix::new();
 
-            if let Some(value) = value_as_string(eval, value)?
-                && filter(key, &value)
-            {
-                values.push((value, id));
-            }
+        for (name, value) in object {
+            let value = expr_to_string(value).with_context(|| {
+                anyhow!(".{key}[{index}][{name}]: Value must be a scalar value")
+            })?;
 
-            if !values.is_empty() {
-                variables.push((key, values));
-            }
+            matrix.insert_value(name, value, None);
         }
-    };
-
-    if !variables.is_empty() {
-        let mut positions = vec![0usize; variables.len()];
 
-        'outer: loop {
-            let mut matrix = Matrix::new();
+        matrices.push(matrix);
+    }
 
-            for (n, &p) in positions.iter().enumerate() {
-                let (key, ref values) = variables[n];
-                let (ref value, id) = values[p];
-                matrix.insert_with_id(key, value, id);
-            }
+    Ok(matrices)
+}
 
-            matrices.push(matrix);
+/// Convert an evaluated expression into matrix values.
+fn expr_to_values(key: &str, expr: Expr<'_>) -> Result<Vec<RString>> {
+    let Expr::Array(values) = expr else {
+        let value =
+            expr_to_string(expr).with_context(|| anyhow!(".{key}: Value must be scalar"))?;
+        return Ok(vec![value]);
+    };
 
-            for (p, (_, values)) in positions.iter_mut().zip(&variables) {
-                *p += 1;
+    let mut output = Vec::with_capacity(values.len());
 
-                if *p < values.len() {
-                    continue 'outer;
-                }
+    for (index, value) in values.into_iter().enumerate() {
+        let value = expr_to_string(value)
+            .with_context(|| anyhow!(".{key}[{index}]: Matrix array value must be scalar"))?;
+        output.push(value);
+    }
 
-                *p = 0;
-            }
+    Ok(output)
+}
 
-            break;
-        }
+/// Convert a scalar expression into a string.
+fn expr_to_string(expr: Expr<'_>) -> Option<RString> {
+    match expr {
+        Expr::String(string) => Some(string.into_owned()),
+        Expr::Float(float) => Some(RString::from(float.to_string())),
+        Expr::Bool(b) => Some(RString::from(if b { "true" } else { "false" })),
+        Expr::Null => Some(RString::new()),
+        Expr::Array(..) | Expr::Object(..) => None,
     }
+}
 
-    matrices.extend(included);
+/// Test if the given string consists of a single `${{ <expr> }}` expression.
+fn as_single_expr(source: &str) -> Option<&str> {
+    let inner = source.trim().strip_prefix("${{")?.strip_suffix("}}")?;
 
-    if matrices.is_empty() {
-        matrices.push(Matrix::new());
+    if inner.contains("${{") {
+        return None;
     }
 
-    Ok(matrices)
+    Some(source)
 }
 
 fn value_as_string(eval: &Eval, value: yaml::Value<'_>) -> Result<Option<RString>> {
@@ -465,6 +1200,32 @@ fn value_as_string(eval: &Eval, value: yaml::Value<'_>) -> Result<Option<RString
     }
 }
 
+/// Extract and evaluate a mapping of values, like the `with` of a job.
+fn extract_values(
+    eval: &Eval,
+    m: &yaml::Mapping<'_>,
+    key: &str,
+) -> Result<BTreeMap<String, RString>> {
+    let mut values = BTreeMap::new();
+
+    let Some(m) = m.get(key).and_then(|v| v.as_mapping()) else {
+        return Ok(values);
+    };
+
+    for (name, value) in m {
+        let name = str::from_utf8(name).context("Decoding key")?;
+
+        let Some(value) = value_as_string(eval, value).with_context(|| anyhow!(".{key}.{name}"))?
+        else {
+            continue;
+        };
+
+        values.insert(name.to_owned(), value);
+    }
+
+    Ok(values)
+}
+
 fn extract_env(eval: &Eval, m: &yaml::Mapping<'_>) -> Result<BTreeMap<String, RString>> {
     let mut env = BTreeMap::new();
 
@@ -486,6 +1247,26 @@ fn extract_env(eval: &Eval, m: &yaml::Mapping<'_>) -> Result<BTreeMap<String, RS
     Ok(env)
 }
 
+fn extract_raw_outputs(m: &yaml::Mapping<'_>) -> Result<BTreeMap<String, String>> {
+    let mut outputs = BTreeMap::new();
+
+    let Some(m) = m.get("outputs").and_then(|v| v.as_mapping()) else {
+        return Ok(outputs);
+    };
+
+    for (key, value) in m {
+        let key = str::from_utf8(key).context("Decoding key")?;
+
+        let Some(value) = value.as_str() else {
+            continue;
+        };
+
+        outputs.insert(key.to_owned(), value.to_owned());
+    }
+
+    Ok(outputs)
+}
+
 fn extract_raw_env(m: &yaml::Mapping<'_>) -> Result<BTreeMap<String, String>> {
     let mut env = BTreeMap::new();
 
@@ -508,6 +1289,7 @@ fn extract_raw_env(m: &yaml::Mapping<'_>) -> Result<BTreeMap<String, String>> {
 
 pub(crate) struct WorkflowManifest<'a, 'cx> {
     cx: &'a Ctxt<'cx>,
+    repo: RelativePathBuf,
     pub(crate) id: String,
     pub(crate) path: RelativePathBuf,
     pub(crate) doc: yaml::Document,
@@ -519,12 +1301,8 @@ impl WorkflowManifest<'_, '_> {
         &self.id
     }
 
-    /// Iterate over all jobs.
-    pub(crate) fn jobs(
-        &self,
-        ignore: &HashSet<String>,
-        filter: &[(String, String)],
-    ) -> Result<Vec<Job>> {
+    /// Get the events which trigger the workflow.
+    pub(crate) fn triggers(&self) -> Result<Triggers> {
         let Some(mapping) = self.doc.as_ref().as_mapping() else {
             bail!(
                 "{}: Root is not a mapping",
@@ -532,18 +1310,61 @@ impl WorkflowManifest<'_, '_> {
             );
         };
 
-        let mut tree = Tree::new();
+        Triggers::parse(mapping.get("on")).with_context(|| {
+            anyhow!(
+                "{}: Parsing triggers in .on",
+                self.cx.to_path(&self.path).display()
+            )
+        })
+    }
+
+    /// Iterate over all jobs.
+    ///
+    /// Jobs which call a local reusable workflow are expanded into the jobs of
+    /// the called workflow, see [`Job::uses`].
+    pub(crate) fn jobs(
+        &self,
+        tree: &Tree,
+        ignore: &HashSet<String>,
+        filter: &[(String, String)],
+    ) -> Result<Vec<Job>> {
+        let mut tree = tree.clone();
 
-        if let Some(auth) = self.cx.github_auth()
+        // NB: A `GITHUB_TOKEN` which has been explicitly provided as a secret
+        // takes precedence.
+        if tree.get(["secrets", "GITHUB_TOKEN"]).is_empty()
+            && let Some(auth) = self.cx.github_auth()
             && let Some(owned) = RString::redacted(auth.as_secret())
         {
             tree.insert_prefix(["secrets"], vec![("GITHUB_TOKEN".to_owned(), owned)]);
         }
 
+        self.build_jobs(&tree, ignore, filter, &[])
+    }
+
+    /// Build the jobs of the workflow, where `calls` are the calls to reusable
+    /// workflows which lead to it, outermost first.
+    fn build_jobs(
+        &self,
+        base: &Tree,
+        ignore: &HashSet<String>,
+        filter: &[(String, String)],
+        calls: &[Rc<WorkflowCall>],
+    ) -> Result<Vec<Job>> {
+        let Some(mapping) = self.doc.as_ref().as_mapping() else {
+            bail!(
+                "{}: Root is not a mapping",
+                self.cx.to_path(&self.path).display()
+            );
+        };
+
+        let mut tree = base.clone();
         let new_env = extract_env(Eval::new(&tree), &mapping)?;
         tree.insert_prefix(["env"], new_env);
 
         let eval = Eval::new(&tree);
+        let defaults = RunDefaults::extract(&mapping, &RunDefaults::default());
+        let doc = Rc::new(self.doc.clone());
 
         let jobs = mapping
             .get("jobs")
@@ -562,30 +1383,394 @@ impl WorkflowManifest<'_, '_> {
                 )
             })?;
 
-            outputs.push(build_job(name, job, ignore, filter, eval).with_context(|| {
-                anyhow!(
-                    "{}: Building job `{name}`",
-                    self.cx.to_path(&self.path).display()
-                )
-            })?);
+            if let Some(uses) = job.get("uses").and_then(|v| v.as_str()) {
+                let jobs = self
+                    .call_workflow(name, uses, &doc, &job, base, eval, ignore, filter, calls)
+                    .with_context(|| {
+                        anyhow!(
+                            "{}: Calling workflow `{uses}` from job `{name}`",
+                            self.cx.to_path(&self.path).display()
+                        )
+                    })?;
+
+                outputs.extend(jobs);
+                continue;
+            }
+
+            let job = build_job(name, &doc, job, ignore, filter, eval, &defaults, calls)
+                .with_context(|| {
+                    anyhow!(
+                        "{}: Building job `{name}`",
+                        self.cx.to_path(&self.path).display()
+                    )
+                })?;
+
+            outputs.push(job);
         }
 
         Ok(outputs)
     }
+
+    /// Expand a job which calls a reusable workflow into the jobs of the called
+    /// workflow, followed by a job which represents the call itself.
+    ///
+    /// Jobs of the called workflow are prefixed with the id of the calling job,
+    /// so `build` in a workflow called from `ci` becomes `ci/build`.
+    #[allow(clippy::too_many_arguments)]
+    fn call_workflow(
+        &self,
+        id: &str,
+        uses: &str,
+        doc: &Rc<yaml::Document>,
+        job: &yaml::Mapping<'_>,
+        base: &Tree,
+        eval: &Eval,
+        ignore: &HashSet<String>,
+        filter: &[(String, String)],
+        calls: &[Rc<WorkflowCall>],
+    ) -> Result<Vec<Job>> {
+        let Some(path) = uses.strip_prefix("./") else {
+            bail!("Only local reusable workflows are supported");
+        };
+
+        if calls.len() + 1 >= MAX_WORKFLOW_DEPTH {
+            bail!("Reusable workflows can at most be nested {MAX_WORKFLOW_DEPTH} levels deep");
+        }
+
+        let condition = job
+            .get("if")
+            .and_then(|v| v.as_str())
+            .map(|source| JobCondition::new(source, eval));
+
+        let needs = extract_needs(job)?;
+
+        // NB: This is reported when the job is run, so that it doesn't prevent
+        // the rest of the workflow from being loaded.
+        if job.get("strategy").is_some() {
+            return Ok(vec![Job {
+                id: id.to_owned(),
+                name: job.get("name").and_then(|v| v.as_str()).map(str::to_owned),
+                needs,
+                outputs: BTreeMap::new(),
+                matrices: Vec::new(),
+                uses: Some(uses.to_owned()),
+                called_by: None,
+                condition,
+                caller_conditions: Vec::new(),
+                fail_fast: true,
+                max_parallel: None,
+                deferred: None,
+                error: Some(
+                    "Matrix strategies are not supported for jobs calling reusable workflows"
+                        .to_owned(),
+                ),
+            }]);
+        }
+
+        let path = self.repo.join(path);
+
+        let Some(callee_id) = path.file_stem() else {
+            bail!("Missing workflow file name");
+        };
+
+        let Some(callee) = open_manifest(self.cx, &self.repo, callee_id, path.clone())? else {
+            bail!("{}: Missing workflow", self.cx.to_path(&path).display());
+        };
+
+        let triggers = callee.triggers()?;
+
+        let Some(declared) = triggers.outputs("workflow_call") else {
+            bail!("Workflow is not triggered by `workflow_call`");
+        };
+
+        let call = Rc::new(WorkflowCall {
+            doc: doc.clone(),
+            id: job.id(),
+            tree: Rc::new(eval.tree().clone()),
+            inputs: triggers.inputs("workflow_call").to_vec(),
+            uses_needs: uses_needs(job.get("with")) || uses_needs(job.get("secrets")),
+        });
+
+        // NB: Inputs and secrets which refer to the `needs` context are
+        // evaluated once the jobs of the called workflow are run, see
+        // `DeferredJob`.
+        let mut tree = base.clone();
+        call.apply((!call.uses_needs).then_some(eval.tree()), &mut tree)?;
+
+        let mut calls = calls.to_vec();
+        calls.push(call);
+
+        let mut jobs = callee.build_jobs(&tree, ignore, filter, &calls)?;
+
+        let mut called = Vec::new();
+
+        for job in &mut jobs {
+            job.id = format!("{id}/{}", job.id);
+
+            for need in &mut job.needs {
+                *need = format!("{id}/{need}");
+            }
+
+            job.needs.extend(needs.iter().cloned());
+
+            job.called_by = Some(match job.called_by.take() {
+                Some(called_by) => format!("{id}/{called_by}"),
+                None => id.to_owned(),
+            });
+
+            // NB: Jobs of the called workflow only run if the condition of the
+            // calling job holds.
+            job.caller_conditions.extend(condition.clone());
+
+            called.push(job.id.clone());
+        }
+
+        called.extend(needs);
+
+        jobs.push(Job {
+            id: id.to_owned(),
+            name: job.get("name").and_then(|v| v.as_str()).map(str::to_owned),
+            needs: called,
+            outputs: declared.clone(),
+            matrices: Vec::new(),
+            uses: Some(uses.to_owned()),
+            called_by: None,
+            condition,
+            caller_conditions: Vec::new(),
+            fail_fast: true,
+            max_parallel: None,
+            deferred: None,
+            error: None,
+        });
+
+        Ok(jobs)
+    }
+}
+
+/// An unevaluated `if` condition of a job.
+#[derive(Clone)]
+pub(crate) struct JobCondition {
+    pub(crate) source: String,
+    /// The tree which the condition is evaluated against.
+    pub(crate) tree: Rc<Tree>,
+}
+
+impl JobCondition {
+    fn new(source: &str, eval: &Eval) -> Self {
+        Self {
+            source: source.to_owned(),
+            tree: Rc::new(eval.tree().clone()),
+        }
+    }
 }
 
 pub(crate) struct Job {
     pub(crate) id: String,
     #[allow(unused)]
     pub(crate) name: Option<String>,
+    /// Jobs which must complete successfully before this job can run.
+    pub(crate) needs: Vec<String>,
+    /// Unevaluated outputs of the job.
+    ///
+    /// For jobs which call a reusable workflow, these are the outputs declared
+    /// by the called workflow, which are evaluated against the `jobs` context.
+    pub(crate) outputs: BTreeMap<String, String>,
     pub(crate) matrices: Vec<(Matrix, Steps)>,
+    /// The reusable workflow called by this job, in which case the job has no
+    /// matrices and instead needs every job of the called workflow.
+    pub(crate) uses: Option<String>,
+    /// The id of the job which called the reusable workflow this job was
+    /// declared in.
+    pub(crate) called_by: Option<String>,
+    /// The `if` condition of the job.
+    pub(crate) condition: Option<JobCondition>,
+    /// Conditions of the jobs which called the reusable workflow this job was
+    /// declared in.
+    pub(crate) caller_conditions: Vec<JobCondition>,
+    /// Cancel the remaining matrix combinations once one has failed.
+    pub(crate) fail_fast: bool,
+    /// The maximum number of matrix combinations to run at the same time.
+    pub(crate) max_parallel: Option<usize>,
+    /// Set if the matrix of the job, or the inputs of the reusable workflow it
+    /// was declared in, depend on the `needs` context. In which case the job
+    /// has no matrices until it is built through [`DeferredJob::build`].
+    pub(crate) deferred: Option<DeferredJob>,
+    /// An error which prevented the job from being loaded, which is reported
+    /// once the job is run.
+    pub(crate) error: Option<String>,
+}
+
+/// A call to a reusable workflow from a job.
+pub(crate) struct WorkflowCall {
+    doc: Rc<yaml::Document>,
+    /// The calling job.
+    id: yaml::Id,
+    /// The tree of the calling workflow.
+    tree: Rc<Tree>,
+    /// Inputs declared by the called workflow.
+    inputs: Vec<WorkflowInput>,
+    /// Whether `with` or `secrets` refer to the `needs` context.
+    uses_needs: bool,
+}
+
+impl WorkflowCall {
+    /// Insert the inputs and secrets passed to the called workflow into
+    /// `tree`, evaluating `with` and `secrets` against the `caller` tree.
+    ///
+    /// If `caller` is `None`, only the defaults of inputs are inserted.
+    fn apply(&self, caller: Option<&Tree>, tree: &mut Tree) -> Result<()> {
+        let Some(job) = self.doc.value(self.id).as_mapping() else {
+            bail!("Job is not a mapping");
+        };
+
+        let with = job.get("with").and_then(|v| v.as_mapping());
+
+        for (name, _) in with.iter().flatten() {
+            let name = str::from_utf8(name).context("Decoding key")?;
+
+            if !self.inputs.iter().any(|input| input.name == name) {
+                bail!(".with.{name}: Input is not declared by the called workflow");
+            }
+        }
+
+        let mut inputs = match caller {
+            Some(caller) => extract_values(Eval::new(caller), &job, "with")?,
+            None => BTreeMap::new(),
+        };
+
+        for input in &self.inputs {
+            if with.as_ref().and_then(|w| w.get(&input.name)).is_some() {
+                continue;
+            }
+
+            match &input.default {
+                Some(default) => {
+                    inputs.insert(input.name.clone(), RString::from(default.as_str()));
+                }
+                None if input.required => {
+                    bail!(".with.{}: Missing required input", input.name);
+                }
+                None => {}
+            }
+        }
+
+        tree.remove(["inputs"]);
+        tree.insert_prefix(["inputs"], inputs);
+
+        // NB: Secrets are only passed on as-is if they are inherited, the
+        // `GITHUB_TOKEN` is always available.
+        if job.get("secrets").and_then(|v| v.as_str()) != Some("inherit") {
+            let token = tree
+                .get(["secrets", "GITHUB_TOKEN"])
+                .first()
+                .map(|t| RString::from(&**t));
+            tree.remove(["secrets"]);
+
+            if let Some(token) = token {
+                tree.insert(["secrets", "GITHUB_TOKEN"], token);
+            }
+
+            if let Some(caller) = caller {
+                let secrets = extract_values(Eval::new(caller), &job, "secrets")?;
+                tree.insert_prefix(["secrets"], secrets);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A job whose matrix depends on the `needs` context, and which can therefore
+/// only be built once the jobs it needs have completed.
+pub(crate) struct DeferredJob {
+    doc: Rc<yaml::Document>,
+    id: yaml::Id,
+    tree: Rc<Tree>,
+    defaults: RunDefaults,
+    ignore: HashSet<String>,
+    filter: Vec<(String, String)>,
+    /// Calls to reusable workflows which lead to the job, outermost first.
+    calls: Vec<Rc<WorkflowCall>>,
+}
+
+impl DeferredJob {
+    /// Construct the tree the job is built with, with the inputs and secrets
+    /// of the calls to reusable workflows evaluated.
+    ///
+    /// The `needs` tree is expected to contain the `needs` context of the job.
+    pub(crate) fn tree(&self, needs: &Tree) -> Result<Tree> {
+        let mut resolved = None::<(&WorkflowCall, Tree)>;
+
+        for call in &self.calls {
+            let mut caller = call.tree.as_ref().clone();
+
+            if let Some((outer, tree)) = &resolved {
+                outer.apply(Some(tree), &mut caller)?;
+            }
+
+            caller.extend(needs);
+            resolved = Some((call, caller));
+        }
+
+        let mut tree = self.tree.as_ref().clone();
+
+        if let Some((call, caller)) = &resolved {
+            call.apply(Some(caller), &mut tree)
+                .context("Evaluating inputs of the called workflow")?;
+        }
+
+        tree.extend(needs);
+        Ok(tree)
+    }
+
+    /// Build the matrices of the job.
+    ///
+    /// The `needs` tree is expected to contain the `needs` context of the job.
+    pub(crate) fn build(&self, needs: &Tree) -> Result<Vec<(Matrix, Steps)>> {
+        let Some(value) = self.doc.value(self.id).as_mapping() else {
+            bail!("Job is not a mapping");
+        };
+
+        let tree = self.tree(needs)?;
+
+        build_job_matrices(
+            &value,
+            &self.ignore,
+            &self.filter,
+            Eval::new(&tree),
+            &self.defaults,
+        )
+    }
 }
 
 pub(crate) struct Steps {
     pub(crate) runs_on: RString,
+    /// The container the job should run in.
+    pub(crate) container: Option<Container>,
+    /// Service containers which run alongside the job.
+    pub(crate) services: Vec<(String, Container)>,
     pub(crate) name: Option<RString>,
     pub(crate) steps: Vec<Rc<Step>>,
     pub(crate) step_mappings: Vec<StepMapping>,
+    /// The tree the steps of the job were loaded with.
+    pub(crate) tree: Rc<Tree>,
+    /// Whether the job is allowed to fail.
+    pub(crate) continue_on_error: bool,
+    /// The maximum amount of time the job is allowed to run for.
+    pub(crate) timeout: Option<Duration>,
+}
+
+/// A container that a job or a service runs in.
+#[derive(Clone)]
+pub(crate) struct Container {
+    /// The image to run.
+    pub(crate) image: RString,
+    /// Environment variables to set in the container.
+    pub(crate) env: BTreeMap<String, RString>,
+    /// Ports to publish from the container.
+    pub(crate) ports: Vec<String>,
+    /// Additional options to use when creating the container.
+    pub(crate) options: Vec<String>,
 }
 
 pub(crate) struct StepMapping {
@@ -606,6 +1791,10 @@ pub(crate) struct Step {
     pub(crate) name: Option<String>,
     pub(crate) run: Option<String>,
     pub(crate) shell: Option<String>,
+    /// Unevaluated `continue-on-error` of the step.
+    pub(crate) continue_on_error: Option<String>,
+    /// Unevaluated `timeout-minutes` of the step.
+    pub(crate) timeout_minutes: Option<String>,
 }
 
 #[derive(Default, Clone)]
@@ -621,6 +1810,24 @@ impl Node {
             children: BTreeMap::new(),
         }
     }
+
+    /// Convert the node into an expression, where a node with children is an
+    /// object of them.
+    fn to_expr(&self) -> Expr<'_> {
+        if self.children.is_empty() {
+            return match self.value.as_deref() {
+                Some(value) => Expr::String(Cow::Borrowed(value)),
+                None => Expr::Null,
+            };
+        }
+
+        Expr::Object(
+            self.children
+                .iter()
+                .map(|(key, node)| (key.clone(), node.to_expr()))
+                .collect(),
+        )
+    }
 }
 
 /// A tree used for variable evaluation.
@@ -723,8 +1930,75 @@ impl Tree {
         current.value = Some(value.as_ref().to_owned());
     }
 
+    /// Remove the value and all children under the given key.
+    pub(crate) fn remove(&mut self, keys: impl IntoIterator<Item: AsRef<str>>) {
+        let mut keys = keys.into_iter().peekable();
+        let mut current = &mut self.root;
+
+        while let Some(key) = keys.next() {
+            let key = key.as_ref();
+
+            if keys.peek().is_none() {
+                current.children.remove(key);
+                return;
+            }
+
+            let Some(next) = current.children.get_mut(key) else {
+                return;
+            };
+
+            current = next;
+        }
+    }
+
+    /// Get all values under the given key, along with their keys relative to
+    /// it.
+    pub(crate) fn values_under(
+        &self,
+        keys: impl IntoIterator<Item: AsRef<str>>,
+    ) -> Vec<(Vec<String>, &RStr)> {
+        let mut current = &self.root;
+
+        for key in keys {
+            let Some(next) = current.children.get(key.as_ref()) else {
+                return Vec::new();
+            };
+
+            current = next;
+        }
+
+        let mut output = Vec::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((current, Vec::new()));
+
+        while let Some((node, path)) = queue.pop_front() {
+            if let Some(value) = node.value.as_deref() {
+                output.push((path.clone(), value));
+            }
+
+            for (key, child) in &node.children {
+                let mut path = path.clone();
+                path.push(key.clone());
+                queue.push_back((child, path));
+            }
+        }
+
+        output
+    }
+
     /// Get a value from the tree.
     pub(crate) fn get<K>(&self, key: K) -> Vec<&RStr>
+    where
+        K: IntoIterator<Item: AsRef<str>, IntoIter: Clone>,
+    {
+        self.nodes(key)
+            .into_iter()
+            .filter_map(|node| node.value.as_deref())
+            .collect()
+    }
+
+    /// Get the nodes matching the given key, where `*` matches every child.
+    fn nodes<K>(&self, key: K) -> Vec<&Node>
     where
         K: IntoIterator<Item: AsRef<str>, IntoIter: Clone>,
     {
@@ -737,7 +2011,7 @@ impl Tree {
 
         while let Some((node, mut keys)) = queue.pop_front() {
             let Some(head) = keys.next() else {
-                output.extend(node.value.as_deref());
+                output.push(node);
                 continue;
             };
 
@@ -830,34 +2104,7 @@ impl Eval {
                 continue;
             };
 
-            let mut e = 0;
-            let mut level = 2usize;
-
-            let expr = 'expr: {
-                let mut it = rest.chars();
-
-                loop {
-                    if level == 2
-                        && let Some(o) = it.as_str().strip_prefix("}}")
-                    {
-                        current = o;
-                        found = true;
-                        break 'expr &rest[..e];
-                    };
-
-                    let Some(c) = it.next() else {
-                        break;
-                    };
-
-                    level = level.wrapping_add_signed(match c {
-                        '{' => 1,
-                        '}' => -1,
-                        _ => 0,
-                    });
-
-                    e += c.len_utf8();
-                }
-
+            let Some((expr, rest)) = split_expr(rest) else {
                 if !found {
                     return Ok(Cow::Borrowed(RStr::new(source)));
                 }
@@ -867,10 +2114,13 @@ impl Eval {
                 return Ok(Cow::Owned(result));
             };
 
+            current = rest;
+            found = true;
+
             result.push_rstr(take(&mut head));
 
             match self.expr(expr)? {
-                Expr::Array(..) => {}
+                Expr::Array(..) | Expr::Object(..) => {}
                 Expr::String(s) => result.push_rstr(s.as_ref()),
                 Expr::Float(f) => {
                     write!(result, "{f}").map_err(|_| ExprError::FormatError)?;
@@ -911,12 +2161,16 @@ impl Eval {
         Ok(self.expr(source)?.as_bool())
     }
 
-    /// Get a variable from the matrix.
-    fn lookup<I>(&self, key: I) -> Vec<&RStr>
+    /// Look up variables, where variables which have children are objects.
+    fn lookup<I>(&self, key: I) -> Vec<Expr<'_>>
     where
         I: IntoIterator<Item: AsRef<str>, IntoIter: Clone>,
     {
-        self.tree.get(key)
+        self.tree
+            .nodes(key)
+            .into_iter()
+            .map(Node::to_expr)
+            .collect()
     }
 }
 
@@ -949,9 +2203,44 @@ impl Matrix {
         K: AsRef<str>,
         V: AsRef<RStr>,
     {
+        self.insert_value(key, value, Some(id));
+    }
+
+    /// Insert a value into the matrix, which might not have an associated id
+    /// if it was produced by an expression.
+    fn insert_value<K, V>(&mut self, key: K, value: V, id: Option<yaml::Id>)
+    where
+        K: AsRef<str>,
+        V: AsRef<RStr>,
+    {
+        let key = key.as_ref();
         self.matrix
-            .insert(key.as_ref().to_owned(), value.as_ref().to_owned());
-        self.ids.insert(key.as_ref().to_owned(), id);
+            .insert(key.to_owned(), value.as_ref().to_owned());
+
+        match id {
+            Some(id) => {
+                self.ids.insert(key.to_owned(), id);
+            }
+            None => {
+                self.ids.remove(key);
+            }
+        }
+    }
+
+    /// Extend this matrix with the values of another matrix.
+    fn extend(&mut self, other: &Matrix) {
+        for (key, value) in &other.matrix {
+            self.insert_value(key, value, other.ids.get(key).copied());
+        }
+    }
+
+    /// Test if this matrix contains all the values of another matrix.
+    fn is_superset(&self, other: &Matrix) -> bool {
+        other.matrix.iter().all(|(key, value)| {
+            self.matrix
+                .get(key)
+                .is_some_and(|existing| existing.exposed_eq(value))
+        })
     }
 
     /// Test if the matrix is empty.
@@ -107,6 +107,43 @@ fn lazy_expansion() {
     );
 }
 
+#[test]
+fn needs_outputs() {
+    let doc = yaml::from_slice(
+        r#"
+        outputs:
+          version: ${{ steps.version.outputs.value }}
+          nested:
+            ignored: true
+        "#,
+    )
+    .expect("valid yaml");
+
+    let mapping = doc.as_ref().as_mapping().expect("root mapping");
+    let outputs = extract_raw_outputs(&mapping).unwrap();
+
+    // NB: Outputs are evaluated once the job has run.
+    assert_eq!(
+        outputs.into_iter().collect::<Vec<_>>(),
+        [(
+            "version".to_owned(),
+            "${{ steps.version.outputs.value }}".to_owned()
+        )]
+    );
+
+    let mut tree = Tree::new();
+    tree.insert(["needs", "build", "result"], "success");
+    tree.insert_prefix(["needs", "build", "outputs"], [("version", "1.2.3")]);
+    let eval = Eval::new(&tree);
+
+    assert_eq!(
+        eval.eval("v${{ needs.build.outputs.version }}"),
+        Ok(Cow::Owned(RString::from("v1.2.3")))
+    );
+    assert_eq!(eval.expr("needs.build.outputs.missing"), Ok(Expr::Null));
+    assert_eq!(eval.test("needs.build.result == 'success'"), Ok(true));
+}
+
 #[test]
 fn comparisons() {
     let eval = Eval::empty();
@@ -148,6 +185,25 @@ fn lookup_star() {
 
     let expected = Expr::Array(["first".into(), "second".into()].into());
     assert_eq!(eval.expr("matrix.*"), Ok(expected));
+
+    let mut tree = Tree::new();
+    tree.insert(["needs", "build", "result"], "success");
+    let eval = Eval::new(&tree);
+
+    let expected = Expr::Array(["success".into()].into());
+    assert_eq!(eval.expr("needs.*.result"), Ok(expected));
+    assert_eq!(
+        eval.expr("toJSON(needs.*.result)"),
+        Ok(Expr::from("[\n  \"success\"\n]"))
+    );
+    assert_eq!(
+        eval.expr("contains(needs.*.result, 'fail')"),
+        Ok(Expr::Bool(false))
+    );
+    assert_eq!(
+        eval.expr("needs.*.missing"),
+        Ok(Expr::Array(Vec::new().into()))
+    );
 }
 
 #[test]
@@ -186,6 +242,10 @@ fn function_contains() {
         eval.expr("contains(matrix.a, 'baz')"),
         Ok(Expr::Bool(false))
     );
+    assert_eq!(eval.expr("contains('ABC', 'b')"), Ok(Expr::Bool(true)));
+    assert_eq!(eval.expr("contains('1.5', 1.5)"), Ok(Expr::Bool(true)));
+    assert_eq!(eval.expr("contains('true', true)"), Ok(Expr::Bool(true)));
+    assert_eq!(eval.expr("contains(null, '')"), Ok(Expr::Bool(true)));
 }
 
 #[test]
@@ -212,6 +272,26 @@ fn function_contains_array() {
         eval.expr("contains(fromJSON('[\"push\", \"pull_request\"]'), github.event_name)"),
         Ok(Expr::Bool(true))
     );
+
+    assert_eq!(
+        eval.expr("contains(fromJSON('[\"Success\", \"failure\"]'), 'FAILURE')"),
+        Ok(Expr::Bool(true))
+    );
+
+    assert_eq!(
+        eval.expr("contains(fromJSON('[\"failure-x\"]'), 'failure')"),
+        Ok(Expr::Bool(false))
+    );
+
+    assert_eq!(
+        eval.expr("contains(fromJSON('[1, true, null]'), 1)"),
+        Ok(Expr::Bool(true))
+    );
+
+    assert_eq!(
+        eval.expr("contains(fromJSON('[[1]]'), fromJSON('[1]'))"),
+        Ok(Expr::Bool(false))
+    );
 }
 
 #[test]
@@ -240,3 +320,1047 @@ fn test_eval() {
         )))
     );
 }
+
+fn matrices(eval: &Eval, source: &str) -> Vec<Vec<(String, String)>> {
+    let doc = yaml::from_slice(source).expect("valid yaml");
+    let mapping = doc.as_ref().as_mapping().expect("root mapping");
+
+    let matrices = build_matrices(&mapping, &HashSet::new(), &[], eval).expect("valid matrix");
+
+    matrices
+        .iter()
+        .map(|m| {
+            m.matrix
+                .iter()
+                .map(|(k, v)| (k.clone(), v.to_exposed().into_owned()))
+                .collect()
+        })
+        .collect()
+}
+
+fn row(values: &[(&str, &str)]) -> Vec<(String, String)> {
+    let mut row = values
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<Vec<_>>();
+
+    row.sort();
+    row
+}
+
+#[test]
+fn matrix_exclude() {
+    let output = matrices(
+        Eval::empty(),
+        r#"
+        strategy:
+          matrix:
+            os: [macos, windows]
+            version: [12, 14, 16]
+            environment: [staging, production]
+            exclude:
+              - os: macos
+                version: 12
+                environment: production
+              - os: windows
+                version: 16
+        "#,
+    );
+
+    assert_eq!(output.len(), 9);
+    assert!(!output.contains(&row(&[
+        ("os", "macos"),
+        ("version", "12"),
+        ("environment", "production")
+    ])));
+    assert!(!output.contains(&row(&[
+        ("os", "windows"),
+        ("version", "16"),
+        ("environment", "staging")
+    ])));
+    assert!(!output.contains(&row(&[
+        ("os", "windows"),
+        ("version", "16"),
+        ("environment", "production")
+    ])));
+    assert!(output.contains(&row(&[
+        ("os", "macos"),
+        ("version", "12"),
+        ("environment", "staging")
+    ])));
+}
+
+#[test]
+fn matrix_include() {
+    let output = matrices(
+        Eval::empty(),
+        r#"
+        strategy:
+          matrix:
+            fruit: [apple, pear]
+            animal: [cat, dog]
+            include:
+              - color: green
+              - color: pink
+                animal: cat
+              - fruit: apple
+                shape: circle
+              - fruit: banana
+              - fruit: banana
+                animal: cat
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        vec![
+            row(&[
+                ("fruit", "apple"),
+                ("animal", "cat"),
+                ("color", "pink"),
+                ("shape", "circle")
+            ]),
+            row(&[("fruit", "pear"), ("animal", "cat"), ("color", "pink")]),
+            row(&[
+                ("fruit", "apple"),
+                ("animal", "dog"),
+                ("color", "green"),
+                ("shape", "circle")
+            ]),
+            row(&[("fruit", "pear"), ("animal", "dog"), ("color", "green")]),
+            row(&[("fruit", "banana")]),
+            row(&[("fruit", "banana"), ("animal", "cat")]),
+        ]
+    );
+}
+
+#[test]
+fn matrix_include_only() {
+    let output = matrices(
+        Eval::empty(),
+        r#"
+        strategy:
+          matrix:
+            include:
+              - os: windows
+              - os: linux
+                rust: stable
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        vec![
+            row(&[("os", "windows")]),
+            row(&[("os", "linux"), ("rust", "stable")]),
+        ]
+    );
+}
+
+#[test]
+fn matrix_from_json() {
+    let mut tree = Tree::new();
+    tree.insert(
+        ["env", "MATRIX"],
+        r#"{"os": ["a", "b"], "include": [{"os": "c"}], "exclude": [{"os": "a"}]}"#,
+    );
+    tree.insert(["env", "VERSIONS"], "[1, 2]");
+    tree.insert(["env", "INCLUDE"], r#"[{"version": "1", "extra": "yes"}]"#);
+
+    let eval = Eval::new(&tree);
+
+    let output = matrices(
+        eval,
+        r#"
+        strategy:
+          matrix: ${{ fromJSON(env.MATRIX) }}
+        "#,
+    );
+
+    assert_eq!(output, vec![row(&[("os", "b")]), row(&[("os", "c")])]);
+
+    let output = matrices(
+        eval,
+        r#"
+        strategy:
+          matrix:
+            version: ${{ fromJSON(env.VERSIONS) }}
+            include: ${{ fromJSON(env.INCLUDE) }}
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        vec![
+            row(&[("version", "1"), ("extra", "yes")]),
+            row(&[("version", "2")]),
+        ]
+    );
+}
+
+#[test]
+fn function_hash_files() {
+    use sha2::{Digest, Sha256};
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("a/target")).unwrap();
+    std::fs::write(dir.path().join("Cargo.lock"), "lock").unwrap();
+    std::fs::write(dir.path().join("a/Cargo.lock"), "nested").unwrap();
+    std::fs::write(dir.path().join("a/target/Cargo.lock"), "ignored").unwrap();
+
+    let mut tree = Tree::new();
+    tree.insert(["github", "workspace"], &*dir.path().to_string_lossy());
+    let eval = Eval::new(&tree);
+
+    let expected = |contents: &[&str]| {
+        let mut result = Sha256::new();
+
+        for c in contents {
+            result.update(Sha256::digest(c));
+        }
+
+        let hex = result
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+
+        Ok(Expr::String(Cow::Owned(RString::from(hex))))
+    };
+
+    assert_eq!(
+        eval.expr("hashFiles('**/Cargo.lock', '!**/target/**')"),
+        expected(&["lock", "nested"])
+    );
+
+    assert_eq!(
+        eval.expr("hashFiles('a/**/Cargo.lock')"),
+        expected(&["nested", "ignored"])
+    );
+
+    assert_eq!(
+        eval.expr("hashFiles('Cargo.lock', 'a/Cargo.lock')"),
+        eval.expr("hashFiles('a/Cargo.lock', 'Cargo.lock')")
+    );
+
+    assert_eq!(
+        eval.expr("hashFiles('**/*.missing')"),
+        Ok(Expr::String(Cow::Owned(RString::new())))
+    );
+}
+
+#[test]
+fn function_format() {
+    let mut tree = Tree::new();
+    tree.insert(["matrix", "os"], "linux");
+    tree.insert(["secrets", "token"], RString::redacted("hunter2").unwrap());
+    let eval = Eval::new(&tree);
+
+    assert_eq!(
+        eval.expr("format('{0}-{1}-{0}', matrix.os, 42)"),
+        Ok(Expr::from("linux-42-linux"))
+    );
+    assert_eq!(
+        eval.expr("format('{{{0}}} {1} {2}', true, null, 1.5)"),
+        Ok(Expr::from("{true}  1.5"))
+    );
+    assert!(eval.expr("format('{1}', 'a')").is_err());
+    assert!(eval.expr("format('{0', 'a')").is_err());
+
+    let Ok(Expr::String(s)) = eval.expr("format('token={0}', secrets.token)") else {
+        panic!("expected string");
+    };
+
+    assert_eq!(s.to_string_lossy(), "token=***");
+    assert_eq!(s.to_exposed(), "token=hunter2");
+}
+
+#[test]
+fn function_join() {
+    let mut tree = Tree::new();
+    tree.insert(["matrix", "targets"], "[\"a\", \"b\", 3]");
+    tree.insert(["secrets", "token"], RString::redacted("hunter2").unwrap());
+    let eval = Eval::new(&tree);
+
+    assert_eq!(
+        eval.expr("join(fromJSON(matrix.targets))"),
+        Ok(Expr::from("a,b,3"))
+    );
+    assert_eq!(
+        eval.expr("join(fromJSON(matrix.targets), ' + ')"),
+        Ok(Expr::from("a + b + 3"))
+    );
+    assert_eq!(eval.expr("join('single')"), Ok(Expr::from("single")));
+
+    let Ok(Expr::String(s)) = eval.expr("join(fromJSON('[\"x\"]'), secrets.token)") else {
+        panic!("expected string");
+    };
+
+    assert_eq!(s.to_string_lossy(), "x");
+
+    let Ok(Expr::String(s)) = eval.expr("join(fromJSON('[1, 2]'), secrets.token)") else {
+        panic!("expected string");
+    };
+
+    assert_eq!(s.to_string_lossy(), "1***2");
+}
+
+#[test]
+fn function_to_json() {
+    let mut tree = Tree::new();
+    tree.insert(
+        ["matrix", "value"],
+        "{\"b\": [1, true, null], \"a\": \"x\\\"y\"}",
+    );
+    tree.insert(["secrets", "token"], RString::redacted("hunter2").unwrap());
+    let eval = Eval::new(&tree);
+
+    assert_eq!(
+        eval.expr("toJSON(fromJSON(matrix.value))"),
+        Ok(Expr::from(
+            "{\n  \"a\": \"x\\\"y\",\n  \"b\": [\n    1,\n    true,\n    null\n  ]\n}"
+        ))
+    );
+    assert_eq!(eval.expr("toJSON('a')"), Ok(Expr::from("\"a\"")));
+    assert_eq!(eval.expr("toJSON(fromJSON('[]'))"), Ok(Expr::from("[]")));
+
+    let Ok(Expr::String(s)) = eval.expr("toJSON(secrets.token)") else {
+        panic!("expected string");
+    };
+
+    assert_eq!(s.to_string_lossy(), "\"***\"");
+    assert_eq!(s.to_exposed(), "\"hunter2\"");
+}
+
+#[test]
+fn function_to_json_context() {
+    let mut tree = Tree::new();
+    tree.insert(["steps", "build", "outcome"], "success");
+    tree.insert(["steps", "build", "outputs", "version"], "1.2.3");
+    tree.insert(["steps", "build", "outputs", "name"], "kick");
+    tree.insert(["steps", "test", "outcome"], "failure");
+    let eval = Eval::new(&tree);
+
+    assert_eq!(
+        eval.expr("toJSON(steps)"),
+        Ok(Expr::from(
+            "{\n  \"build\": {\n    \"outcome\": \"success\",\n    \"outputs\": {\n      \"name\": \"kick\",\n      \"version\": \"1.2.3\"\n    }\n  },\n  \"test\": {\n    \"outcome\": \"failure\"\n  }\n}"
+        ))
+    );
+
+    assert_eq!(
+        eval.expr("toJSON(steps.build.outputs)"),
+        Ok(Expr::from(
+            "{\n  \"name\": \"kick\",\n  \"version\": \"1.2.3\"\n}"
+        ))
+    );
+
+    assert_eq!(
+        eval.expr("steps.build.outputs.version"),
+        Ok(Expr::from("1.2.3"))
+    );
+    assert_eq!(eval.expr("toJSON(steps.missing)"), Ok(Expr::from("null")));
+    assert_eq!(eval.test("steps.build"), Ok(true));
+}
+
+#[test]
+fn function_starts_ends_with() {
+    let mut tree = Tree::new();
+    tree.insert(["github", "ref"], "refs/tags/V1.0");
+    let eval = Eval::new(&tree);
+
+    assert_eq!(
+        eval.expr("startsWith(github.ref, 'refs/tags/')"),
+        Ok(Expr::Bool(true))
+    );
+    assert_eq!(
+        eval.expr("endsWith(github.ref, 'v1.0')"),
+        Ok(Expr::Bool(true))
+    );
+    assert_eq!(
+        eval.expr("endsWith(github.ref, '2.0')"),
+        Ok(Expr::Bool(false))
+    );
+    assert_eq!(eval.expr("endsWith(10, 0)"), Ok(Expr::Bool(true)));
+    assert_eq!(eval.expr("always()"), Ok(Expr::Bool(true)));
+}
+
+#[test]
+fn index_lookup() {
+    let mut tree = Tree::new();
+    tree.insert(["matrix", "rust-version"], "1.70");
+    tree.insert(["matrix", "name"], "inputs");
+    tree.insert(["github", "event", "inputs", "linux-target"], "x86_64");
+    tree.insert(["needs", "a", "result"], "success");
+    tree.insert(["needs", "b", "result"], "failure");
+    let eval = Eval::new(&tree);
+
+    assert_eq!(eval.expr("matrix['rust-version']"), Ok(Expr::from("1.70")));
+    assert_eq!(
+        eval.expr("matrix[\"rust-version\"]"),
+        Ok(Expr::from("1.70"))
+    );
+    assert_eq!(
+        eval.expr("github.event.inputs[format('{0}-target', 'linux')]"),
+        Ok(Expr::from("x86_64"))
+    );
+    assert_eq!(
+        eval.expr("github.event[matrix.name]['linux-target']"),
+        Ok(Expr::from("x86_64"))
+    );
+    assert_eq!(eval.expr("matrix['missing']"), Ok(Expr::Null));
+    assert_eq!(
+        eval.expr("needs.*.result"),
+        Ok(Expr::Array(
+            [Expr::from("success"), Expr::from("failure")].into()
+        ))
+    );
+    assert_eq!(
+        eval.expr("contains(needs.*.result, 'failure')"),
+        Ok(Expr::Bool(true))
+    );
+    assert_eq!(
+        eval.expr("matrix['rust-version'] == '1.70' && needs.a.result == 'success'"),
+        Ok(Expr::Bool(true))
+    );
+}
+
+#[test]
+fn index_access() {
+    let mut tree = Tree::new();
+    tree.insert(
+        ["env", "JSON"],
+        "[{\"name\": \"a\", \"v\": 1}, {\"name\": \"b\"}, {\"v\": 3}]",
+    );
+    let eval = Eval::new(&tree);
+
+    assert_eq!(eval.expr("fromJSON(env.JSON)[1].name"), Ok(Expr::from("b")));
+    assert_eq!(
+        eval.expr("fromJSON(env.JSON)[0]['v']"),
+        Ok(Expr::Float(1.0))
+    );
+    assert_eq!(
+        eval.expr("fromJSON(env.JSON).*.name"),
+        Ok(Expr::Array([Expr::from("a"), Expr::from("b")].into()))
+    );
+    assert_eq!(
+        eval.expr("(fromJSON(env.JSON)).*.v"),
+        Ok(Expr::Array([Expr::Float(1.0), Expr::Float(3.0)].into()))
+    );
+    assert_eq!(eval.expr("fromJSON(env.JSON)[5].name"), Ok(Expr::Null));
+    assert_eq!(
+        eval.expr("fromJSON('{\"a\": {\"b\": \"c\"}}')['a'].b"),
+        Ok(Expr::from("c"))
+    );
+    assert!(eval.expr("matrix['a'").is_err());
+}
+
+#[test]
+fn step_limits() {
+    let mut tree = Tree::new();
+    tree.insert(["matrix", "experimental"], "true");
+    tree.insert(["matrix", "stable"], "false");
+    tree.insert(["matrix", "minutes"], "1.5");
+    let eval = Eval::new(&tree);
+
+    assert!(eval_bool(eval, "true").unwrap());
+    assert!(!eval_bool(eval, "false").unwrap());
+    assert!(eval_bool(eval, "${{ matrix.experimental }}").unwrap());
+    assert!(!eval_bool(eval, "${{ matrix.stable }}").unwrap());
+    assert!(!eval_bool(eval, "${{ matrix.missing }}").unwrap());
+    assert!(eval_bool(eval, "maybe").is_err());
+
+    assert_eq!(
+        eval_minutes(eval, "10").unwrap(),
+        std::time::Duration::from_secs(600)
+    );
+    assert_eq!(
+        eval_minutes(eval, "${{ matrix.minutes }}").unwrap(),
+        std::time::Duration::from_secs(90)
+    );
+    assert!(eval_minutes(eval, "0").is_err());
+    assert!(eval_minutes(eval, "soon").is_err());
+}
+
+fn container(eval: &Eval, source: &str) -> Option<Container> {
+    let doc = yaml::from_slice(source).expect("valid yaml");
+    let mapping = doc.as_ref().as_mapping().expect("root mapping");
+    extract_container(eval, &mapping).expect("valid container")
+}
+
+#[test]
+fn job_container() {
+    let mut tree = Tree::new();
+    tree.insert(["matrix", "image"], "fedora:40");
+    tree.insert(["matrix", "empty"], "");
+    let eval = Eval::new(&tree);
+
+    assert!(container(eval, "runs-on: ubuntu-latest").is_none());
+
+    let c = container(eval, "container: ${{ matrix.image }}").expect("container");
+    assert_eq!(c.image.to_exposed(), "fedora:40");
+    assert!(c.env.is_empty());
+    assert!(c.options.is_empty());
+
+    let c = container(
+        eval,
+        r#"
+container:
+  image: node:18
+  env:
+    NODE_ENV: ${{ matrix.image }}
+  options: --cpus 1 --memory 1g
+"#,
+    )
+    .expect("container");
+
+    assert_eq!(c.image.to_exposed(), "node:18");
+    assert_eq!(c.env["NODE_ENV"].to_exposed(), "fedora:40");
+    assert_eq!(c.options, ["--cpus", "1", "--memory", "1g"]);
+
+    assert!(container(eval, "container: ${{ matrix.empty }}").is_none());
+}
+
+#[test]
+fn job_services() {
+    let tree = Tree::new();
+    let eval = Eval::new(&tree);
+
+    let doc = yaml::from_slice(
+        r#"
+services:
+  postgres:
+    image: postgres:14
+    env:
+      POSTGRES_PASSWORD: postgres
+    ports:
+      - 5432
+      - 6379:6379
+    options: --health-cmd "pg_isready -U postgres" --health-retries=5
+  redis: ${{ '' }}
+"#,
+    )
+    .expect("valid yaml");
+
+    let mapping = doc.as_ref().as_mapping().expect("root mapping");
+    let services = extract_services(eval, &mapping).expect("valid services");
+
+    assert_eq!(services.len(), 1);
+    let (id, service) = &services[0];
+    assert_eq!(id, "postgres");
+    assert_eq!(service.image.to_exposed(), "postgres:14");
+    assert_eq!(service.env["POSTGRES_PASSWORD"].to_exposed(), "postgres");
+    assert_eq!(service.ports, ["5432", "6379:6379"]);
+    assert_eq!(
+        service.options,
+        [
+            "--health-cmd",
+            "pg_isready -U postgres",
+            "--health-retries=5"
+        ]
+    );
+}
+
+#[test]
+fn container_options() {
+    assert_eq!(split_options("").unwrap(), Vec::<String>::new());
+    assert_eq!(split_options("  --a  b ").unwrap(), ["--a", "b"]);
+    assert_eq!(
+        split_options(r#"--x 'a "b"' "c d""#).unwrap(),
+        ["--x", r#"a "b""#, "c d"]
+    );
+    assert_eq!(split_options(r#"a\ b "" c"#).unwrap(), ["a b", "", "c"]);
+    assert!(split_options("'unterminated").is_err());
+}
+
+fn triggers(source: &str) -> Triggers {
+    let doc = yaml::from_slice(source).expect("valid yaml");
+    let mapping = doc.as_ref().as_mapping().expect("root mapping");
+    Triggers::parse(mapping.get("on")).expect("valid triggers")
+}
+
+#[test]
+fn filter_patterns() {
+    let p = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+    assert!(triggers::matches_patterns(&p(&["main"]), "main").unwrap());
+    assert!(!triggers::matches_patterns(&p(&["main"]), "mainline").unwrap());
+    assert!(triggers::matches_patterns(&p(&["release/*"]), "release/v1").unwrap());
+    assert!(!triggers::matches_patterns(&p(&["release/*"]), "release/v1/rc").unwrap());
+    assert!(triggers::matches_patterns(&p(&["release/**"]), "release/v1/rc").unwrap());
+    assert!(triggers::matches_patterns(&p(&["v[12].*"]), "v2.0").unwrap());
+    assert!(!triggers::matches_patterns(&p(&["v[12].*"]), "v3.0").unwrap());
+
+    let negated = p(&["**.md", "!docs/**", "docs/README.md"]);
+    assert!(triggers::matches_patterns(&negated, "README.md").unwrap());
+    assert!(!triggers::matches_patterns(&negated, "docs/guide.md").unwrap());
+    assert!(triggers::matches_patterns(&negated, "docs/README.md").unwrap());
+}
+
+#[test]
+fn workflow_triggers() {
+    let none = || -> Result<Vec<String>> { panic!("paths should not be computed") };
+    let paths =
+        |paths: &'static [&'static str]| move || Ok(paths.iter().map(|p| p.to_string()).collect());
+
+    let t = triggers("on: push");
+    assert_eq!(t.test("push", Some("refs/heads/main"), none).unwrap(), None);
+    assert!(t.test("pull_request", None, none).unwrap().is_some());
+
+    let t = triggers("on: [push, pull_request]");
+    assert_eq!(t.test("pull_request", None, none).unwrap(), None);
+
+    let t = triggers(
+        r#"
+on:
+  push:
+    branches:
+      - main
+      - 'release/**'
+    tags: ['v*']
+    paths-ignore:
+      - 'docs/**'
+  workflow_dispatch:
+    inputs:
+      level:
+        default: info
+      target:
+        required: true
+"#,
+    );
+
+    let main = Some("refs/heads/main");
+    assert_eq!(t.test("push", main, paths(&["src/lib.rs"])).unwrap(), None);
+    assert!(
+        t.test("push", main, paths(&["docs/a.md"]))
+            .unwrap()
+            .is_some()
+    );
+    assert!(
+        t.test("push", Some("refs/heads/dev"), none)
+            .unwrap()
+            .is_some()
+    );
+    assert_eq!(t.test("push", Some("refs/tags/v1.0"), none).unwrap(), None);
+    assert!(
+        t.test("push", Some("refs/tags/1.0"), none)
+            .unwrap()
+            .is_some()
+    );
+
+    let inputs = t.inputs("workflow_dispatch");
+    assert_eq!(inputs.len(), 2);
+    assert_eq!(inputs[0].name, "level");
+    assert_eq!(inputs[0].default.as_deref(), Some("info"));
+    assert!(!inputs[0].required);
+    assert_eq!(inputs[1].name, "target");
+    assert!(inputs[1].required);
+    assert!(t.inputs("push").is_empty());
+}
+
+#[test]
+fn workflow_call_triggers() {
+    let t = triggers(
+        r#"
+on:
+  workflow_call:
+    inputs:
+      greeting:
+        type: string
+        required: true
+    outputs:
+      message:
+        value: ${{ jobs.build.outputs.message }}
+"#,
+    );
+
+    let outputs = t.outputs("workflow_call").expect("workflow_call outputs");
+    assert_eq!(outputs["message"], "${{ jobs.build.outputs.message }}");
+    assert!(t.outputs("push").is_none());
+
+    let inputs = t.inputs("workflow_call");
+    assert_eq!(inputs.len(), 1);
+    assert!(inputs[0].required);
+}
+
+#[test]
+fn tree_remove() {
+    let mut tree = Tree::new();
+    tree.insert(["inputs", "a"], "1");
+    tree.insert(["inputs", "b"], "2");
+    tree.insert(["secrets", "token"], "3");
+
+    tree.remove(["inputs"]);
+    tree.remove(["missing", "key"]);
+
+    assert!(tree.get(["inputs", "*"]).is_empty());
+    assert_eq!(tree.get(["secrets", "token"]), [RStr::new("3")]);
+}
+
+#[test]
+fn tree_values_under() {
+    let mut tree = Tree::new();
+    tree.insert(["steps", "a", "outcome"], "success");
+    tree.insert(["steps", "a", "outputs", "value"], "42");
+    tree.insert(["matrix", "os"], "linux");
+
+    let values = tree
+        .values_under(["steps"])
+        .into_iter()
+        .map(|(key, value)| (key.join("."), value.to_exposed().into_owned()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        values,
+        [
+            (String::from("a.outcome"), String::from("success")),
+            (String::from("a.outputs.value"), String::from("42")),
+        ]
+    );
+
+    assert!(tree.values_under(["missing"]).is_empty());
+}
+
+fn step_defaults(source: &str, job: &str) -> Vec<(Option<String>, Option<String>)> {
+    let doc = yaml::from_slice(source).expect("valid yaml");
+    let mapping = doc.as_ref().as_mapping().expect("root mapping");
+    let defaults = RunDefaults::extract(&mapping, &RunDefaults::default());
+
+    let job = mapping
+        .get("jobs")
+        .and_then(|jobs| jobs.as_mapping())
+        .and_then(|jobs| jobs.get(job))
+        .and_then(|job| job.as_mapping())
+        .expect("job");
+
+    let defaults = RunDefaults::extract(&job, &defaults);
+
+    let tree = Tree::new();
+    let (steps, _, _) = load_steps(&job, Eval::new(&tree), &defaults).expect("valid steps");
+
+    steps
+        .iter()
+        .map(|step| (step.shell.clone(), step.working_directory.clone()))
+        .collect()
+}
+
+#[test]
+fn run_defaults() {
+    let source = r#"
+defaults:
+  run:
+    shell: sh
+    working-directory: workflow
+jobs:
+  inherit:
+    steps:
+      - run: echo
+  job:
+    defaults:
+      run:
+        working-directory: job
+    steps:
+      - run: echo
+      - run: echo
+        shell: python
+        working-directory: step
+      - uses: actions/checkout@v4
+"#;
+
+    let some = |shell: &str, dir: &str| (Some(shell.to_owned()), Some(dir.to_owned()));
+
+    assert_eq!(step_defaults(source, "inherit"), [some("sh", "workflow")]);
+
+    assert_eq!(
+        step_defaults(source, "job"),
+        [some("sh", "job"), some("python", "step"), (None, None)]
+    );
+
+    let source = r#"
+jobs:
+  job:
+    steps:
+      - run: echo
+"#;
+
+    assert_eq!(step_defaults(source, "job"), [(None, None)]);
+}
+
+fn job(eval: &Eval, source: &str) -> Result<Job> {
+    let doc = Rc::new(yaml::from_slice(source).expect("valid yaml"));
+    let mapping = doc.as_ref().as_ref().as_mapping().expect("root mapping");
+    build_job(
+        "test",
+        &doc,
+        mapping,
+        &HashSet::new(),
+        &[],
+        eval,
+        &RunDefaults::default(),
+        &[],
+    )
+}
+
+#[test]
+fn job_strategy() {
+    let mut tree = Tree::new();
+    tree.insert(["github", "event_name"], "push");
+    tree.insert(["vars", "jobs"], "2");
+    let eval = Eval::new(&tree);
+
+    let j = job(eval, "runs-on: ubuntu-latest").unwrap();
+    assert!(j.condition.is_none());
+    assert!(j.fail_fast);
+    assert_eq!(j.max_parallel, None);
+
+    let j = job(
+        eval,
+        r#"
+runs-on: ubuntu-latest
+if: github.event_name == 'push'
+strategy:
+  fail-fast: false
+  max-parallel: ${{ vars.jobs }}
+  matrix:
+    n: [1, 2]
+"#,
+    )
+    .unwrap();
+
+    let condition = j.condition.as_ref().expect("condition");
+    assert_eq!(condition.source, "github.event_name == 'push'");
+    assert!(Eval::new(&condition.tree).test(&condition.source).unwrap());
+    assert!(!j.fail_fast);
+    assert_eq!(j.max_parallel, Some(2));
+    assert_eq!(j.matrices.len(), 2);
+
+    let source = r#"
+runs-on: ubuntu-latest
+strategy:
+  max-parallel: 0
+"#;
+
+    assert!(job(eval, source).is_err());
+}
+
+#[test]
+fn status_functions() {
+    assert!(has_status_function("always()"));
+    assert!(has_status_function("${{ !cancelled() }}"));
+    assert!(has_status_function("needs.a.result == 'x' && failure ()"));
+    assert!(!has_status_function("github.event_name == 'push'"));
+    assert!(!has_status_function("contains('failure()', 'x')"));
+    assert!(!has_status_function("steps.success.outputs.value"));
+
+    let tree = Tree::new();
+    let eval = Eval::new(&tree);
+    assert_eq!(eval.test("success()"), Ok(true));
+    assert_eq!(eval.test("failure()"), Ok(false));
+
+    let mut tree = Tree::new();
+    tree.insert(["job", "status"], "failure");
+    let eval = Eval::new(&tree);
+    assert_eq!(eval.test("success()"), Ok(false));
+    assert_eq!(eval.test("failure()"), Ok(true));
+    assert_eq!(eval.test("!cancelled()"), Ok(true));
+
+    let mut tree = Tree::new();
+    tree.insert(["job", "status"], "skipped");
+    let eval = Eval::new(&tree);
+    assert_eq!(eval.test("success() || failure()"), Ok(false));
+    assert_eq!(eval.test("always()"), Ok(true));
+}
+
+#[test]
+fn matrix_errors() {
+    let build = |source: &str| {
+        let doc = yaml::from_slice(source).expect("valid yaml");
+        let mapping = doc.as_ref().as_mapping().expect("root mapping");
+        let Err(error) = build_matrices(&mapping, &HashSet::new(), &[], Eval::empty()) else {
+            panic!("expected matrix error");
+        };
+
+        error.to_string()
+    };
+
+    assert_eq!(
+        build(
+            r#"
+            strategy:
+              matrix:
+                os: []
+                version: [1, 2]
+            "#
+        ),
+        "Matrix vector `os` does not contain any values"
+    );
+
+    assert_eq!(
+        build(
+            r#"
+            strategy:
+              matrix:
+                os: [linux, windows]
+                exclude:
+                  - os: linux
+                  - os: windows
+            "#
+        ),
+        "Matrix exclusions remove every combination"
+    );
+
+    // Includes are added as combinations of their own if everything else has
+    // been excluded.
+    let output = matrices(
+        Eval::empty(),
+        r#"
+        strategy:
+          matrix:
+            os: [linux]
+            exclude:
+              - os: linux
+            include:
+              - os: macos
+        "#,
+    );
+
+    assert_eq!(output, vec![row(&[("os", "macos")])]);
+}
+
+#[test]
+fn needs_in_expressions() {
+    let uses = |source: &str| {
+        let doc = yaml::from_slice(source).expect("valid yaml");
+        uses_needs(Some(doc.as_ref()))
+    };
+
+    assert!(uses("${{ needs.setup.outputs.matrix }}"));
+    assert!(uses("${{ needs['setup'].result }}"));
+    assert!(uses("${{ toJSON(needs) }}"));
+    assert!(uses("${{ fromJSON(needs.setup.outputs.matrix).os }}"));
+    assert!(uses("prefix-${{ github.ref }}-${{ needs.setup.result }}"));
+    assert!(uses("who: ${{ needs.setup.outputs.who }}"));
+    assert!(uses("[a, '${{ (needs).setup.result }}']"));
+
+    assert!(!uses("needs.setup.result"));
+    assert!(!uses("${{ 'needs.setup.result' }}"));
+    assert!(!uses("${{ github.needs }}"));
+    assert!(!uses("${{ steps.needs.outputs.value }}"));
+    assert!(!uses("${{ inputs.needs-review }}"));
+    assert!(!uses("[1, 2]"));
+}
+
+#[test]
+fn matrix_from_needs() {
+    let tree = Tree::new();
+    let eval = Eval::new(&tree);
+
+    let source = r#"
+runs-on: ubuntu-latest
+needs: setup
+strategy:
+  matrix: ${{ fromJSON(needs.setup.outputs.matrix) }}
+steps:
+  - run: echo ${{ matrix.os }}
+"#;
+
+    let j = job(eval, source).unwrap();
+    assert!(j.matrices.is_empty());
+
+    let deferred = j.deferred.as_ref().expect("deferred job");
+
+    let mut needs = Tree::new();
+    needs.insert(["needs", "setup", "result"], "success");
+    needs.insert(
+        ["needs", "setup", "outputs", "matrix"],
+        r#"{"os": ["linux", "windows"]}"#,
+    );
+
+    let matrices = deferred.build(&needs).unwrap();
+    let mut os = matrices
+        .iter()
+        .map(|(matrix, _)| matrix.matrix.get("os").unwrap().to_exposed().into_owned())
+        .collect::<Vec<_>>();
+    os.sort();
+    assert_eq!(os, ["linux", "windows"]);
+
+    let mut needs = Tree::new();
+    needs.insert(["needs", "setup", "outputs", "matrix"], "[1, 2]");
+    assert!(deferred.build(&needs).is_err());
+}
+
+#[test]
+fn call_inputs_from_needs() {
+    let caller = r#"
+needs: setup
+uses: ./.github/workflows/called.yml
+with:
+  who: ${{ needs.setup.outputs.who }}
+secrets:
+  token: ${{ needs.setup.result }}
+"#;
+
+    let caller = Rc::new(yaml::from_slice(caller).expect("valid yaml"));
+    let mapping = caller.as_ref().as_ref().as_mapping().expect("root mapping");
+
+    let call = Rc::new(WorkflowCall {
+        doc: caller.clone(),
+        id: mapping.id(),
+        tree: Rc::new(Tree::new()),
+        inputs: vec![
+            WorkflowInput {
+                name: "who".to_owned(),
+                default: None,
+                required: true,
+            },
+            WorkflowInput {
+                name: "greeting".to_owned(),
+                default: Some("Hello".to_owned()),
+                required: false,
+            },
+        ],
+        uses_needs: true,
+    });
+
+    let mut tree = Tree::new();
+    call.apply(None, &mut tree).unwrap();
+    assert_eq!(tree.get(["inputs", "greeting"]), [RStr::new("Hello")]);
+    assert!(tree.get(["inputs", "who"]).is_empty());
+
+    let source = r#"
+runs-on: ubuntu-latest
+if: inputs.who == 'world'
+steps:
+  - run: echo ${{ inputs.greeting }}
+"#;
+
+    let doc = Rc::new(yaml::from_slice(source).expect("valid yaml"));
+    let mapping = doc.as_ref().as_ref().as_mapping().expect("root mapping");
+    let eval = Eval::new(&tree);
+
+    let j = build_job(
+        "test",
+        &doc,
+        mapping,
+        &HashSet::new(),
+        &[],
+        eval,
+        &RunDefaults::default(),
+        &[call],
+    )
+    .unwrap();
+
+    assert!(j.matrices.is_empty());
+    let deferred = j.deferred.as_ref().expect("deferred job");
+
+    let mut needs = Tree::new();
+    needs.insert(["needs", "setup", "result"], "success");
+    needs.insert(["needs", "setup", "outputs", "who"], "world");
+
+    let tree = deferred.tree(&needs).unwrap();
+    assert_eq!(tree.get(["inputs", "who"]), [RStr::new("world")]);
+    assert_eq!(tree.get(["inputs", "greeting"]), [RStr::new("Hello")]);
+    assert_eq!(tree.get(["secrets", "token"]), [RStr::new("success")]);
+    assert!(Eval::new(&tree).test("inputs.who == 'world'").unwrap());
+    assert_eq!(deferred.build(&needs).unwrap().len(), 1);
+}
@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::str;
+
+use anyhow::{Context, Result, anyhow, bail};
+use nondestructive::yaml;
+use regex::Regex;
+
+use super::extract_scalar;
+
+/// The events which trigger a workflow, as declared in its `on` block.
+#[derive(Default)]
+pub(crate) struct Triggers {
+    events: BTreeMap<String, EventFilters>,
+}
+
+/// Filters and inputs of a single event.
+#[derive(Default)]
+pub(crate) struct EventFilters {
+    branches: Option<Vec<String>>,
+    branches_ignore: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    tags_ignore: Option<Vec<String>>,
+    paths: Option<Vec<String>>,
+    paths_ignore: Option<Vec<String>>,
+    inputs: Vec<WorkflowInput>,
+    /// Outputs declared by `workflow_call`, mapped to their unevaluated
+    /// values.
+    outputs: BTreeMap<String, String>,
+}
+
+/// An input declared by an event like `workflow_dispatch`.
+#[derive(Clone)]
+pub(crate) struct WorkflowInput {
+    pub(crate) name: String,
+    pub(crate) default: Option<String>,
+    pub(crate) required: bool,
+}
+
+impl Triggers {
+    /// Parse triggers from the `on` value of a workflow, which is either an
+    /// event name, a sequence of event names or a mapping of events to their
+    /// configuration.
+    pub(crate) fn parse(value: Option<yaml::Value<'_>>) -> Result<Self> {
+        let mut events = BTreeMap::new();
+
+        let Some(value) = value else {
+            return Ok(Self { events });
+        };
+
+        if let Some(event) = value.as_str() {
+            events.insert(event.to_owned(), EventFilters::default());
+        } else if let Some(sequence) = value.as_sequence() {
+            for (index, value) in sequence.iter().enumerate() {
+                let event = value
+                    .as_str()
+                    .with_context(|| anyhow!("[{index}]: Expected an event name"))?;
+
+                events.insert(event.to_owned(), EventFilters::default());
+            }
+        } else if let Some(m) = value.as_mapping() {
+            for (event, value) in m {
+                let event = str::from_utf8(event).context("Decoding event name")?;
+
+                let filters = match value.as_mapping() {
+                    Some(m) => EventFilters::parse(&m).with_context(|| anyhow!(".{event}"))?,
+                    None => EventFilters::default(),
+                };
+
+                events.insert(event.to_owned(), filters);
+            }
+        } else {
+            bail!("Expected an event, a sequence of events or a mapping");
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Get the inputs declared for the given event.
+    pub(crate) fn inputs(&self, event: &str) -> &[WorkflowInput] {
+        match self.events.get(event) {
+            Some(filters) => &filters.inputs,
+            None => &[],
+        }
+    }
+
+    /// Get the outputs declared for the given event.
+    pub(crate) fn outputs(&self, event: &str) -> Option<&BTreeMap<String, String>> {
+        Some(&self.events.get(event)?.outputs)
+    }
+
+    /// Test if the given event triggers the workflow.
+    ///
+    /// The `reference` is the full git reference the event applies to, like
+    /// `refs/heads/main`, and `changed` lazily provides the paths which have
+    /// changed.
+    ///
+    /// Returns a human readable reason if the workflow would not trigger.
+    pub(crate) fn test<F>(
+        &self,
+        event: &str,
+        reference: Option<&str>,
+        changed: F,
+    ) -> Result<Option<String>>
+    where
+        F: FnOnce() -> Result<Vec<String>>,
+    {
+        let Some(filters) = self.events.get(event) else {
+            return Ok(Some(format!("not triggered by `{event}`")));
+        };
+
+        let is_tag;
+
+        if let Some(reference) = reference {
+            let (branch, tag) = match reference.strip_prefix("refs/tags/") {
+                Some(tag) => (None, Some(tag)),
+                None => (
+                    Some(reference.strip_prefix("refs/heads/").unwrap_or(reference)),
+                    None,
+                ),
+            };
+
+            is_tag = tag.is_some();
+
+            let has_branches = filters.branches.is_some() || filters.branches_ignore.is_some();
+            let has_tags = filters.tags.is_some() || filters.tags_ignore.is_some();
+
+            if let Some(branch) = branch {
+                if has_tags && !has_branches {
+                    return Ok(Some(String::from("only triggered by tags")));
+                }
+
+                if let Some(reason) = filter(
+                    "branch",
+                    branch,
+                    filters.branches.as_deref(),
+                    filters.branches_ignore.as_deref(),
+                )? {
+                    return Ok(Some(reason));
+                }
+            }
+
+            if let Some(tag) = tag {
+                if has_branches && !has_tags {
+                    return Ok(Some(String::from("only triggered by branches")));
+                }
+
+                if let Some(reason) = filter(
+                    "tag",
+                    tag,
+                    filters.tags.as_deref(),
+                    filters.tags_ignore.as_deref(),
+                )? {
+                    return Ok(Some(reason));
+                }
+            }
+        } else {
+            is_tag = false;
+        }
+
+        // NB: Path filters are not evaluated for tags.
+        if is_tag || (filters.paths.is_none() && filters.paths_ignore.is_none()) {
+            return Ok(None);
+        }
+
+        let changed = changed()?;
+
+        if let Some(paths) = &filters.paths {
+            let mut any = false;
+
+            for path in &changed {
+                if matches_patterns(paths, path)? {
+                    any = true;
+                    break;
+                }
+            }
+
+            if !any {
+                return Ok(Some(String::from("no changed paths match `paths`")));
+            }
+        }
+
+        if let Some(paths_ignore) = &filters.paths_ignore {
+            let mut all = true;
+
+            for path in &changed {
+                if !matches_patterns(paths_ignore, path)? {
+                    all = false;
+                    break;
+                }
+            }
+
+            if all {
+                return Ok(Some(String::from("all changed paths match `paths-ignore`")));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl EventFilters {
+    fn parse(m: &yaml::Mapping<'_>) -> Result<Self> {
+        let mut inputs = Vec::new();
+
+        if let Some(m) = m.get("inputs").and_then(|v| v.as_mapping()) {
+            for (name, value) in m {
+                let name = str::from_utf8(name).context("Decoding input name")?;
+                let value = value.as_mapping();
+
+                let default = value
+                    .as_ref()
+                    .and_then(|m| m.get("default"))
+                    .and_then(extract_scalar);
+
+                let required = value
+                    .as_ref()
+                    .and_then(|m| m.get("required"))
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or_default();
+
+                inputs.push(WorkflowInput {
+                    name: name.to_owned(),
+                    default,
+                    required,
+                });
+            }
+        }
+
+        let mut outputs = BTreeMap::new();
+
+        if let Some(m) = m.get("outputs").and_then(|v| v.as_mapping()) {
+            for (name, value) in m {
+                let name = str::from_utf8(name).context("Decoding output name")?;
+
+                let value = value
+                    .as_mapping()
+                    .and_then(|m| m.get("value"))
+                    .and_then(|v| v.as_str())
+                    .with_context(|| anyhow!(".outputs.{name}: Missing value"))?;
+
+                outputs.insert(name.to_owned(), value.to_owned());
+            }
+        }
+
+        Ok(Self {
+            branches: patterns(m, "branches")?,
+            branches_ignore: patterns(m, "branches-ignore")?,
+            tags: patterns(m, "tags")?,
+            tags_ignore: patterns(m, "tags-ignore")?,
+            paths: patterns(m, "paths")?,
+            paths_ignore: patterns(m, "paths-ignore")?,
+            inputs,
+            outputs,
+        })
+    }
+}
+
+fn patterns(m: &yaml::Mapping<'_>, key: &str) -> Result<Option<Vec<String>>> {
+    let Some(value) = m.get(key) else {
+        return Ok(None);
+    };
+
+    let mut patterns = Vec::new();
+
+    if let Some(pattern) = value.as_str() {
+        patterns.push(pattern.to_owned());
+    } else if let Some(sequence) = value.as_sequence() {
+        for (index, value) in sequence.iter().enumerate() {
+            let pattern = extract_scalar(value)
+                .with_context(|| anyhow!(".{key}[{index}]: Expected a pattern"))?;
+            patterns.push(pattern);
+        }
+    } else {
+        bail!(".{key}: Expected a pattern or a sequence of patterns");
+    }
+
+    Ok(Some(patterns))
+}
+
+/// Apply an include and an exclude filter to a value.
+fn filter(
+    what: &str,
+    value: &str,
+    include: Option<&[String]>,
+    exclude: Option<&[String]>,
+) -> Result<Option<String>> {
+    if let Some(include) = include
+        && !matches_patterns(include, value)?
+    {
+        return Ok(Some(format!("{what} `{value}` does not match filter")));
+    }
+
+    if let Some(exclude) = exclude
+        && matches_patterns(exclude, value)?
+    {
+        return Ok(Some(format!("{what} `{value}` is ignored")));
+    }
+
+    Ok(None)
+}
+
+/// Test if a value matches a list of filter patterns.
+///
+/// Patterns are evaluated in order, where a pattern prefixed with `!` negates
+/// any earlier matches.
+pub(crate) fn matches_patterns(patterns: &[String], value: &str) -> Result<bool> {
+    let mut matched = false;
+
+    for pattern in patterns {
+        let (negate, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern.as_str()),
+        };
+
+        if matched == negate && compile_pattern(pattern)?.is_match(value) {
+            matched = !negate;
+        }
+    }
+
+    Ok(matched)
+}
+
+/// Compile a filter pattern into a regular expression.
+///
+/// This supports the special characters `*`, `**`, `?`, `+` and `[]` with the
+/// same meaning as in Github workflow filters.
+fn compile_pattern(pattern: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    let mut it = pattern.chars().peekable();
+
+    while let Some(c) = it.next() {
+        match c {
+            '*' => {
+                if it.peek() == Some(&'*') {
+                    it.next();
+                    regex.push_str(".*");
+                } else {
+                    regex.push_str("[^/]*");
+                }
+            }
+            '?' => regex.push('?'),
+            '+' => regex.push('+'),
+            '[' => {
+                regex.push('[');
+
+                for c in it.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+
+                    if c == '\\' {
+                        regex.push_str("\\\\");
+                    } else {
+                        regex.push(c);
+                    }
+                }
+
+                regex.push(']');
+            }
+            '\\' => {
+                if let Some(c) = it.next() {
+                    regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+                }
+            }
+            c => {
+                regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            }
+        }
+    }
+
+    regex.push('$');
+    Regex::new(&regex).with_context(|| anyhow!("Invalid filter pattern `{pattern}`"))
+}